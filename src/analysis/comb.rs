@@ -0,0 +1,369 @@
+// Copyright (c) 2017-2020 Fabian Schuiki
+
+use crate::ir::prelude::*;
+use num::Zero;
+use std::collections::{HashMap, HashSet};
+
+/// Find the combinational loops in an entity.
+///
+/// Builds a graph of the dependencies among the instructions in `unit`, where
+/// instructions depend on the values they use, and probes depend on the drives
+/// of the probed signal. Drives and delays by a nonzero amount of physical
+/// time break the dependency, as do registers. Signals that are connected, or
+/// derived from one another by field or slice extraction, are treated as one.
+///
+/// Returns the instructions of each cycle in the graph, in layout order.
+pub fn comb_loops(unit: &Unit) -> Vec<Vec<Inst>> {
+    let insts: Vec<Inst> = unit.all_insts().collect();
+    let indices: HashMap<Inst, usize> = insts.iter().enumerate().map(|(i, &x)| (x, i)).collect();
+    let mut succs = vec![vec![]; insts.len()];
+
+    let mut classes = SignalClasses::new(unit);
+
+    // Collect the instructions reading and writing each signal without delay.
+    let delayed = |value| {
+        unit.get_const_time(value)
+            .map(|t| !t.time().is_zero())
+            .unwrap_or(false)
+    };
+    let mut readers: HashMap<Value, Vec<usize>> = HashMap::new();
+    let mut writers: HashMap<Value, Vec<usize>> = HashMap::new();
+    for (i, &inst) in insts.iter().enumerate() {
+        let data = &unit[inst];
+        match data.opcode() {
+            Opcode::Prb => {
+                readers
+                    .entry(classes.find(data.args()[0]))
+                    .or_default()
+                    .push(i);
+            }
+            Opcode::Drv | Opcode::DrvCond if !delayed(data.args()[2]) => {
+                writers
+                    .entry(classes.find(data.args()[0]))
+                    .or_default()
+                    .push(i);
+            }
+            Opcode::Del if !delayed(data.args()[2]) => {
+                readers
+                    .entry(classes.find(data.args()[1]))
+                    .or_default()
+                    .push(i);
+                writers
+                    .entry(classes.find(data.args()[0]))
+                    .or_default()
+                    .push(i);
+            }
+            _ => (),
+        }
+    }
+    for (signal, writers) in &writers {
+        for &w in writers {
+            succs[w].extend(readers.get(signal).into_iter().flatten());
+        }
+    }
+
+    // Add the dependencies on values other than signals.
+    for (i, &inst) in insts.iter().enumerate() {
+        if unit[inst].opcode() == Opcode::Reg {
+            continue;
+        }
+        for &arg in unit[inst].args() {
+            if unit.value_type(arg).is_signal() {
+                continue;
+            }
+            if let Some(def) = unit.get_value_inst(arg) {
+                succs[indices[&def]].push(i);
+            }
+        }
+    }
+
+    strongly_connected(&succs)
+        .into_iter()
+        .filter(|scc| scc.len() > 1 || succs[scc[0]].contains(&scc[0]))
+        .map(|scc| scc.into_iter().map(|i| insts[i]).collect())
+        .collect()
+}
+
+/// Find the instructions that feed into an instruction combinationally.
+///
+/// Walks backwards from `inst` over the operands of each instruction. Probes
+/// of a signal continue at the instructions in `unit` that drive the signal,
+/// again treating connected and derived signals as one. The walk stops at
+/// arguments and constants, as well as registers and instances, whose operands
+/// are not followed.
+///
+/// The returned set does not contain `inst` itself, unless it feeds into
+/// itself.
+pub fn fanin_cone(unit: &Unit, inst: Inst) -> HashSet<Inst> {
+    let mut classes = SignalClasses::new(unit);
+    let mut writers: HashMap<Value, Vec<Inst>> = HashMap::new();
+    for other in unit.all_insts() {
+        let data = &unit[other];
+        let targets = match data.opcode() {
+            Opcode::Drv | Opcode::DrvCond | Opcode::Del | Opcode::Reg => &data.args()[..1],
+            Opcode::Inst => data.output_args(),
+            _ => continue,
+        };
+        for &target in targets {
+            writers.entry(classes.find(target)).or_default().push(other);
+        }
+    }
+
+    let mut cone = HashSet::new();
+    let mut todo = vec![inst];
+    while let Some(inst) = todo.pop() {
+        let data = &unit[inst];
+        let sources = match data.opcode() {
+            Opcode::Reg | Opcode::Inst => continue,
+            Opcode::Prb => &data.args()[..1],
+            Opcode::Del => &data.args()[1..2],
+            _ => &[],
+        };
+        for &signal in sources {
+            for &writer in writers.get(&classes.find(signal)).into_iter().flatten() {
+                if cone.insert(writer) {
+                    todo.push(writer);
+                }
+            }
+        }
+        for &arg in data.args() {
+            if unit.value_type(arg).is_signal() {
+                continue;
+            }
+            if let Some(def) = unit.get_value_inst(arg) {
+                if cone.insert(def) {
+                    todo.push(def);
+                }
+            }
+        }
+    }
+    cone
+}
+
+/// A union-find structure over signals.
+struct SignalClasses(HashMap<Value, Value>);
+
+impl SignalClasses {
+    /// Group the signals in a unit that are connected, or derived from one
+    /// another.
+    fn new(unit: &Unit) -> Self {
+        let mut classes = SignalClasses(HashMap::new());
+        for inst in unit.all_insts() {
+            let data = &unit[inst];
+            if data.opcode() == Opcode::Con {
+                classes.union(data.args()[0], data.args()[1]);
+            } else if let Some(result) = unit.get_inst_result(inst) {
+                if unit.value_type(result).is_signal() {
+                    for &arg in data.args() {
+                        if unit.value_type(arg).is_signal() {
+                            classes.union(result, arg);
+                        }
+                    }
+                }
+            }
+        }
+        classes
+    }
+
+    fn find(&mut self, value: Value) -> Value {
+        let parent = *self.0.get(&value).unwrap_or(&value);
+        if parent == value {
+            return value;
+        }
+        let root = self.find(parent);
+        self.0.insert(value, root);
+        root
+    }
+
+    fn union(&mut self, a: Value, b: Value) {
+        let a = self.find(a);
+        let b = self.find(b);
+        if a != b {
+            self.0.insert(a, b);
+        }
+    }
+}
+
+/// Compute the strongly connected components of a graph.
+///
+/// Uses Tarjan's algorithm with an explicit stack. The nodes of each component
+/// are sorted, and the components are ordered by their first node.
+fn strongly_connected(succs: &[Vec<usize>]) -> Vec<Vec<usize>> {
+    const UNVISITED: usize = usize::MAX;
+    let mut index = vec![UNVISITED; succs.len()];
+    let mut lowlink = vec![0; succs.len()];
+    let mut on_stack = vec![false; succs.len()];
+    let mut stack = vec![];
+    let mut next = 0;
+    let mut sccs = vec![];
+
+    for root in 0..succs.len() {
+        if index[root] != UNVISITED {
+            continue;
+        }
+        let mut work = vec![(root, 0)];
+        index[root] = next;
+        lowlink[root] = next;
+        next += 1;
+        stack.push(root);
+        on_stack[root] = true;
+        while let Some((v, i)) = work.pop() {
+            if i < succs[v].len() {
+                work.push((v, i + 1));
+                let w = succs[v][i];
+                if index[w] == UNVISITED {
+                    index[w] = next;
+                    lowlink[w] = next;
+                    next += 1;
+                    stack.push(w);
+                    on_stack[w] = true;
+                    work.push((w, 0));
+                } else if on_stack[w] {
+                    lowlink[v] = lowlink[v].min(index[w]);
+                }
+                continue;
+            }
+            if lowlink[v] == index[v] {
+                let mut scc = vec![];
+                loop {
+                    let w = stack.pop().unwrap();
+                    on_stack[w] = false;
+                    scc.push(w);
+                    if w == v {
+                        break;
+                    }
+                }
+                scc.sort();
+                sccs.push(scc);
+            }
+            if let Some(&(u, _)) = work.last() {
+                lowlink[u] = lowlink[u].min(lowlink[v]);
+            }
+        }
+    }
+
+    sccs.sort();
+    sccs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn loops(input: &str) -> Vec<Vec<String>> {
+        let module = crate::assembly::parse_module(input).unwrap();
+        let unit = module.units().next().unwrap();
+        comb_loops(&unit)
+            .into_iter()
+            .map(|insts| {
+                insts
+                    .into_iter()
+                    .map(|inst| inst.dump(&unit).to_string())
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn cross_coupled_gates() {
+        let found = loops(
+            "
+            entity @latch (i1$ %a) -> (i1$ %x, i1$ %y) {
+                %t = const time 0s 1e
+                %ap = prb i1$ %a
+                %yp = prb i1$ %y
+                %x0 = and i1 %ap, %yp
+                %x1 = not i1 %x0
+                drv i1$ %x, %x1, %t
+                %xp = prb i1$ %x
+                %y0 = and i1 %ap, %xp
+                %y1 = not i1 %y0
+                drv i1$ %y, %y1, %t
+            }
+            ",
+        );
+        assert_eq!(
+            found,
+            vec![vec![
+                "%yp = prb i1 %y",
+                "%x0 = and i1 %ap, %yp",
+                "%x1 = not i1 %x0",
+                "drv %x, %x1, %t",
+                "%xp = prb i1 %x",
+                "%y0 = and i1 %ap, %xp",
+                "%y1 = not i1 %y0",
+                "drv %y, %y1, %t",
+            ]]
+        );
+    }
+
+    #[test]
+    fn output_cone() {
+        let module = crate::assembly::parse_module(
+            "
+            entity @gates (i1$ %a, i1$ %b, i1$ %c) -> (i1$ %x, i1$ %y) {
+                %t = const time 0s 1e
+                %ap = prb i1$ %a
+                %bp = prb i1$ %b
+                %cp = prb i1$ %c
+                %zero = const i1 0
+                %n = sig i1 %zero
+                %ab = and i1 %ap, %bp
+                drv i1$ %n, %ab, %t
+                %np = prb i1$ %n
+                %x0 = or i1 %np, %cp
+                drv i1$ %x, %x0, %t
+                %y0 = not i1 %cp
+                drv i1$ %y, %y0, %t
+            }
+            ",
+        )
+        .unwrap();
+        let unit = module.units().next().unwrap();
+        let drv = unit
+            .all_insts()
+            .filter(|&inst| unit[inst].opcode() == Opcode::Drv)
+            .nth(1)
+            .unwrap();
+        let mut cone: Vec<_> = fanin_cone(&unit, drv)
+            .into_iter()
+            .map(|inst| inst.dump(&unit).to_string())
+            .collect();
+        cone.sort();
+        assert_eq!(
+            cone,
+            vec![
+                "%ab = and i1 %ap, %bp",
+                "%ap = prb i1 %a",
+                "%bp = prb i1 %b",
+                "%cp = prb i1 %c",
+                "%np = prb i1 %n",
+                "%t = const time 0s 1e",
+                "%x0 = or i1 %np, %cp",
+                "drv %n, %ab, %t",
+            ]
+        );
+    }
+
+    #[test]
+    fn delay_breaks_loop() {
+        let found = loops(
+            "
+            entity @osc (i1$ %a) -> (i1$ %x, i1$ %y) {
+                %t = const time 0s 1e
+                %d = const time 1ns
+                %ap = prb i1$ %a
+                %yp = prb i1$ %y
+                %x0 = and i1 %ap, %yp
+                %x1 = not i1 %x0
+                drv i1$ %x, %x1, %t
+                %xp = prb i1$ %x
+                %y0 = and i1 %ap, %xp
+                %y1 = not i1 %y0
+                drv i1$ %y, %y1, %d
+            }
+            ",
+        );
+        assert!(found.is_empty(), "{:?}", found);
+    }
+}
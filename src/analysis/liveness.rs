@@ -0,0 +1,144 @@
+// Copyright (c) 2017-2020 Fabian Schuiki
+
+use crate::analysis::PredecessorTable;
+use crate::ir::prelude::*;
+use std::collections::{HashMap, HashSet};
+
+/// The values live at the boundaries of each block.
+///
+/// A value is live at a point if it is used later on some path through the
+/// control flow graph without being redefined in between. Values flowing into
+/// a phi node are considered to be used at the end of the corresponding
+/// predecessor block, rather than at the beginning of the phi's block.
+#[derive(Debug, Clone)]
+pub struct Liveness {
+    live_in: HashMap<Block, HashSet<Value>>,
+    live_out: HashMap<Block, HashSet<Value>>,
+}
+
+impl Liveness {
+    /// Compute the liveness of the values in a unit.
+    pub fn new(unit: &Unit, pt: &PredecessorTable) -> Self {
+        // Determine the values each block uses before defining them, the values
+        // it defines, and the values its phi nodes take from each predecessor.
+        let mut uses = HashMap::<Block, HashSet<Value>>::new();
+        let mut defs = HashMap::<Block, HashSet<Value>>::new();
+        let mut phi_uses = HashMap::<(Block, Block), HashSet<Value>>::new();
+        for bb in unit.blocks() {
+            let uses = uses.entry(bb).or_default();
+            let defs = defs.entry(bb).or_default();
+            for inst in unit.insts(bb) {
+                let data = &unit[inst];
+                if data.opcode().is_phi() {
+                    for (&arg, &from) in data.args().iter().zip(data.blocks()) {
+                        if !arg.is_invalid() {
+                            phi_uses.entry((from, bb)).or_default().insert(arg);
+                        }
+                    }
+                } else {
+                    for &arg in data.args() {
+                        if !arg.is_invalid() && !defs.contains(&arg) {
+                            uses.insert(arg);
+                        }
+                    }
+                }
+                if let Some(value) = unit.get_inst_result(inst) {
+                    defs.insert(value);
+                }
+            }
+        }
+
+        // Propagate liveness backwards until a fixpoint is reached.
+        let mut live_in: HashMap<Block, HashSet<Value>> = uses.clone();
+        let mut live_out = HashMap::<Block, HashSet<Value>>::new();
+        let order: Vec<Block> = unit.blocks().collect();
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for &bb in order.iter().rev() {
+                let mut out = HashSet::new();
+                for succ in pt.succ(bb) {
+                    out.extend(live_in[&succ].iter().cloned());
+                    if let Some(values) = phi_uses.get(&(bb, succ)) {
+                        out.extend(values.iter().cloned());
+                    }
+                }
+                let mut inp = uses[&bb].clone();
+                inp.extend(out.iter().filter(|v| !defs[&bb].contains(v)).cloned());
+                if inp != live_in[&bb] {
+                    live_in.insert(bb, inp);
+                    changed = true;
+                }
+                if live_out.get(&bb) != Some(&out) {
+                    live_out.insert(bb, out);
+                    changed = true;
+                }
+            }
+        }
+
+        Self { live_in, live_out }
+    }
+
+    /// Get the values live at the beginning of a block.
+    pub fn live_in(&self, bb: Block) -> &HashSet<Value> {
+        &self.live_in[&bb]
+    }
+
+    /// Get the values live at the end of a block.
+    pub fn live_out(&self, bb: Block) -> &HashSet<Value> {
+        &self.live_out[&bb]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::assembly::parse_module;
+
+    #[test]
+    fn straight_line_and_diamond() {
+        let module = parse_module(
+            "func @foo (i1 %c, i32 %a) i32 {
+            %entry:
+                %x = add i32 %a, %a
+                %y = add i32 %x, %a
+                br %c, %left, %right
+            %left:
+                %l = add i32 %y, %a
+                br %merge
+            %right:
+                br %merge
+            %merge:
+                %p = phi i32 [%l, %left], [%a, %right]
+                %z = add i32 %p, %y
+                ret i32 %z
+            }",
+        )
+        .unwrap();
+        let unit = module.units().next().unwrap();
+        let liveness = unit.liveness();
+        let block = |name| {
+            unit.blocks()
+                .find(|&bb| unit.get_block_name(bb) == Some(name))
+                .unwrap()
+        };
+        let names = |set: &std::collections::HashSet<_>| {
+            let mut names: Vec<_> = set
+                .iter()
+                .map(|&v| unit.get_name(v).unwrap().to_owned())
+                .collect();
+            names.sort();
+            names
+        };
+
+        // Straight-line code in the entry block only needs the arguments.
+        assert_eq!(names(liveness.live_in(block("entry"))), vec!["a", "c"]);
+        assert_eq!(names(liveness.live_out(block("entry"))), vec!["a", "y"]);
+
+        // %y is live across the merge, and phi operands are live on the edges.
+        assert_eq!(names(liveness.live_in(block("left"))), vec!["a", "y"]);
+        assert_eq!(names(liveness.live_out(block("left"))), vec!["l", "y"]);
+        assert_eq!(names(liveness.live_out(block("right"))), vec!["a", "y"]);
+        assert_eq!(names(liveness.live_in(block("merge"))), vec!["y"]);
+        assert!(liveness.live_out(block("merge")).is_empty());
+    }
+}
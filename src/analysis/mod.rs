@@ -4,10 +4,16 @@
 //!
 //! This module implements various analysis passes on the IR.
 
+mod comb;
 mod domtree;
+mod liveness;
 mod preds;
+mod sensitivity;
 mod trg;
 
+pub use self::comb::*;
 pub use self::domtree::*;
+pub use self::liveness::*;
 pub use self::preds::*;
+pub use self::sensitivity::*;
 pub use self::trg::*;
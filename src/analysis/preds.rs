@@ -100,4 +100,90 @@ impl PredecessorTable {
     pub fn is_sole_succ(&self, bb: Block, succ_of: Block) -> bool {
         self.succ(succ_of).all(|x| x == bb)
     }
+
+    /// Get the blocks reachable from `entry` in post-order.
+    ///
+    /// Every block appears after all of its successors, except for successors
+    /// reached through a back edge. Blocks which are not reachable from
+    /// `entry` are omitted.
+    pub fn post_order(&self, entry: Block) -> Vec<Block> {
+        let mut order = vec![];
+        let mut visited = HashSet::new();
+        let mut stack = vec![(entry, self.sorted_succs(entry))];
+        visited.insert(entry);
+        while let Some((bb, succs)) = stack.last_mut() {
+            match succs.pop() {
+                Some(succ) => {
+                    if visited.insert(succ) {
+                        let succs = self.sorted_succs(succ);
+                        stack.push((succ, succs));
+                    }
+                }
+                None => {
+                    order.push(*bb);
+                    stack.pop();
+                }
+            }
+        }
+        order
+    }
+
+    /// Get the blocks reachable from `entry` in reverse post-order.
+    ///
+    /// Every block appears before all of its successors, except for successors
+    /// reached through a back edge. Blocks which are not reachable from
+    /// `entry` are omitted.
+    pub fn reverse_post_order(&self, entry: Block) -> Vec<Block> {
+        let mut order = self.post_order(entry);
+        order.reverse();
+        order
+    }
+
+    /// Get the successors of a block in descending order, such that popping
+    /// them visits them in ascending order.
+    fn sorted_succs(&self, bb: Block) -> Vec<Block> {
+        let mut succs: Vec<_> = self.succ(bb).collect();
+        succs.sort_unstable_by(|a, b| b.cmp(a));
+        succs
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::assembly::parse_module;
+
+    #[test]
+    fn loop_order() {
+        let module = parse_module(
+            "func @foo (i1 %c) void {
+            %entry:
+                br %header
+            %header:
+                br %c, %body, %exit
+            %body:
+                br %header
+            %exit:
+                ret
+            %dead:
+                br %exit
+            }",
+        )
+        .unwrap();
+        let unit = module.units().next().unwrap();
+        let names = |order: Vec<_>| -> Vec<String> {
+            order
+                .into_iter()
+                .map(|bb| unit.get_block_name(bb).unwrap().to_owned())
+                .collect()
+        };
+        let pt = unit.predtbl();
+        assert_eq!(
+            names(pt.reverse_post_order(unit.entry())),
+            vec!["entry", "header", "exit", "body"]
+        );
+        assert_eq!(
+            names(pt.post_order(unit.entry())),
+            vec!["body", "exit", "header", "entry"]
+        );
+    }
 }
@@ -0,0 +1,169 @@
+// Copyright (c) 2017-2020 Fabian Schuiki
+
+use crate::ir::prelude::*;
+use std::collections::HashSet;
+
+/// Compute the forward cone of instructions sensitive to a signal.
+///
+/// Returns all instructions in `unit` whose results depend on `signal`, either
+/// directly or through other signals driven or connected within the unit. This
+/// is the set of instructions that must be re-evaluated when `signal` changes.
+/// The instructions are returned in layout order.
+pub fn sensitivity_cone(unit: &Unit, signal: Value) -> Vec<Inst> {
+    let mut seen_values = HashSet::new();
+    let mut seen_insts = HashSet::new();
+    let mut todo = vec![signal];
+    seen_values.insert(signal);
+
+    while let Some(value) = todo.pop() {
+        for &inst in unit.uses(value) {
+            let data = &unit[inst];
+
+            // Driving or registering a signal does not depend on the signal's
+            // current value, and neither do the outputs of an instance.
+            let is_target = match data.opcode() {
+                Opcode::Drv | Opcode::DrvCond | Opcode::Reg => {
+                    data.args()[0] == value && !data.args()[1..].contains(&value)
+                }
+                Opcode::Inst => !data.input_args().contains(&value),
+                _ => false,
+            };
+            if is_target || !seen_insts.insert(inst) {
+                continue;
+            }
+
+            // Determine which values are affected by the instruction.
+            let affected: Vec<Value> = match data.opcode() {
+                Opcode::Drv | Opcode::DrvCond | Opcode::Reg => vec![data.args()[0]],
+                Opcode::Con => data.args().to_vec(),
+                Opcode::Inst => data.output_args().to_vec(),
+                _ => unit.get_inst_result(inst).into_iter().collect(),
+            };
+            for v in affected {
+                if seen_values.insert(v) {
+                    todo.push(v);
+                }
+            }
+        }
+    }
+
+    unit.all_insts()
+        .filter(|inst| seen_insts.contains(inst))
+        .collect()
+}
+
+/// Collect the signals a process is sensitive to.
+///
+/// Returns the union of the signals that the `wait` instructions in `unit` wait
+/// for, in the order they first appear, without duplicates. The time operand of
+/// a `wait` with a timeout is not included.
+pub fn sensitivity_list(unit: &Unit) -> Vec<Value> {
+    let mut seen = HashSet::new();
+    let mut list = vec![];
+    for inst in unit.all_insts() {
+        let data = &unit[inst];
+        let signals = match data.opcode() {
+            Opcode::Wait => data.args(),
+            Opcode::WaitTime => &data.args()[1..],
+            _ => continue,
+        };
+        for &signal in signals {
+            if seen.insert(signal) {
+                list.push(signal);
+            }
+        }
+    }
+    list
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn two_independent_cones() {
+        let module = crate::assembly::parse_module(
+            "
+            entity @top (i1$ %a, i1$ %b) -> (i1$ %x, i1$ %y) {
+                %ap = prb i1$ %a
+                %bp = prb i1$ %b
+                %na = not i1 %ap
+                %nb = not i1 %bp
+                %t = const time 0s 1e
+                drv i1$ %x, %na, %t
+                drv i1$ %y, %nb, %t
+            }
+            ",
+        )
+        .unwrap();
+        let unit = module.units().next().unwrap();
+        let names = |insts: Vec<Inst>| -> Vec<String> {
+            insts
+                .into_iter()
+                .map(|inst| inst.dump(&unit).to_string())
+                .collect()
+        };
+        assert_eq!(
+            names(sensitivity_cone(&unit, unit.input_arg(0))),
+            vec!["%ap = prb i1 %a", "%na = not i1 %ap", "drv %x, %na, %t"]
+        );
+        assert_eq!(
+            names(sensitivity_cone(&unit, unit.input_arg(1))),
+            vec!["%bp = prb i1 %b", "%nb = not i1 %bp", "drv %y, %nb, %t"]
+        );
+    }
+
+    #[test]
+    fn cone_through_reg() {
+        let module = crate::assembly::parse_module(
+            "
+            entity @top (i1$ %a, i1$ %clk) -> (i1$ %q, i1$ %y) {
+                %ap = prb i1$ %a
+                %clkp = prb i1$ %clk
+                reg i1$ %q, [%ap, rise %clkp]
+                %qp = prb i1$ %q
+                %nq = not i1 %qp
+                %t = const time 0s 1e
+                drv i1$ %y, %nq, %t
+            }
+            ",
+        )
+        .unwrap();
+        let unit = module.units().next().unwrap();
+        let opcodes: Vec<Opcode> = sensitivity_cone(&unit, unit.input_arg(0))
+            .into_iter()
+            .map(|inst| unit[inst].opcode())
+            .collect();
+        assert_eq!(
+            opcodes,
+            vec![
+                Opcode::Prb,
+                Opcode::Reg,
+                Opcode::Prb,
+                Opcode::Not,
+                Opcode::Drv
+            ]
+        );
+    }
+
+    #[test]
+    fn overlapping_waits() {
+        let module = crate::assembly::parse_module(
+            "
+            proc @p (i1$ %a, i1$ %b, i1$ %c) -> () {
+            init:
+                wait %mid, %a, %b
+            mid:
+                %t = const time 1ns
+                wait %init for %t, %b, %c, %a
+            }
+            ",
+        )
+        .unwrap();
+        let unit = module.units().next().unwrap();
+        assert_eq!(
+            sensitivity_list(&unit),
+            vec![unit.input_arg(0), unit.input_arg(1), unit.input_arg(2)]
+        );
+    }
+}
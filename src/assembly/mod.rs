@@ -3,13 +3,21 @@
 //! Facilities to emit a module as human-readable assembly, or to parse such
 //! assembly back into a module.
 
-use crate::{ir::Module, ty::Type, value::TimeValue};
+use crate::{
+    ir::{Module, Span, Unit, UnitBuilder, UnitData, UnitKind},
+    ty::Type,
+    value::TimeValue,
+};
+use std::collections::HashMap;
 
 #[allow(unused_parens)]
 mod grammar;
 mod reader;
 mod writer;
 
+pub(crate) use self::writer::escape_name;
+pub use self::writer::{ArrayStyle, Writer};
+
 /// Emit assembly for a module.
 pub fn write_module(sink: impl std::io::Write, module: &Module) {
     writer::Writer::new(sink).write_module(module).unwrap();
@@ -22,29 +30,247 @@ pub fn write_module_string(module: &Module) -> String {
     String::from_utf8(asm).expect("writer should emit proper utf8")
 }
 
+/// Emit assembly for a single function, process, or entity.
+///
+/// Named types are spelled out in full, such that the output can be parsed on
+/// its own, for example with `parse_units`.
+pub fn write_unit(sink: impl std::io::Write, unit: Unit) {
+    writer::Writer::new(sink).write_unit(unit).unwrap();
+}
+
+/// Emit assembly for a single function, process, or entity as string.
+pub fn write_unit_string(unit: Unit) -> String {
+    let mut asm = vec![];
+    write_unit(&mut asm, unit);
+    String::from_utf8(asm).expect("writer should emit proper utf8")
+}
+
 /// Parse a type.
 ///
 /// Parses the `input` string into a type.
-pub fn parse_type(input: impl AsRef<str>) -> Result<Type, String> {
+pub fn parse_type(input: impl AsRef<str>) -> Result<Type, ParseError> {
     reader::TypeParser::new()
-        .parse(input.as_ref())
-        .map_err(|e| format!("{}", e))
+        .parse(&Default::default(), input.as_ref())
+        .map_err(Into::into)
 }
 
 /// Parse a time.
 ///
 /// Parses the `input` string into a time constant.
-pub fn parse_time(input: impl AsRef<str>) -> Result<TimeValue, String> {
+pub fn parse_time(input: impl AsRef<str>) -> Result<TimeValue, ParseError> {
     reader::TimeValueParser::new()
-        .parse(input.as_ref())
-        .map_err(|e| format!("{}", e))
+        .parse(&Default::default(), input.as_ref())
+        .map_err(Into::into)
 }
 
 /// Parse a module.
 ///
-/// Parses the `input` string into a module.
-pub fn parse_module(input: impl AsRef<str>) -> Result<Module, String> {
-    parse_module_unchecked(input).map(|mut module| {
+/// Parses the `input` string into a module, using the default `ParseOptions`.
+pub fn parse_module(input: impl AsRef<str>) -> Result<Module, ParseError> {
+    parse_module_with(input, &Default::default())
+}
+
+/// Options controlling how assembly is parsed.
+///
+/// The default options are used by `parse_module`, `parse_module_unchecked`,
+/// and `parse_units`. Pass custom options to `parse_module_with`,
+/// `parse_module_unchecked_with`, or `parse_units_with` to relax or tighten the
+/// parser.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseOptions {
+    /// Whether names consisting only of digits, such as `%0`, are kept as
+    /// explicit names.
+    ///
+    /// Such names are usually treated as temporaries and discarded. Keeping
+    /// them makes them survive a round trip through the parser and writer,
+    /// which quotes them as in `%"0"`.
+    pub numeric_names: bool,
+    /// The largest width of `iN` and `nN` types accepted.
+    pub max_int_width: usize,
+    /// The deepest nesting of types accepted, such as a struct in a struct in a
+    /// struct. This guards against inputs that would overflow the stack.
+    pub max_type_depth: usize,
+    /// Whether comments are attached to instructions.
+    ///
+    /// Comments are usually discarded. If kept, each `; ...` comment is
+    /// attached to an instruction, such that the writer emits it again. A
+    /// comment on the same line as an instruction is attached to that
+    /// instruction. Comments on a line of their own are attached to the next
+    /// instruction in the unit, or dropped if there is none.
+    pub preserve_comments: bool,
+    /// Whether every block of a function or process must end in a terminator,
+    /// such as `br` or `ret`.
+    ///
+    /// Otherwise a missing terminator is only reported when the module is
+    /// verified.
+    pub require_terminators: bool,
+    /// Whether integer constants that do not fit their type are rejected.
+    ///
+    /// Otherwise they are truncated to the width of the type, such that
+    /// `const i8 256` becomes `const i8 0`.
+    pub check_int_ranges: bool,
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        Self {
+            numeric_names: false,
+            max_int_width: DEFAULT_MAX_INT_WIDTH,
+            max_type_depth: DEFAULT_MAX_TYPE_DEPTH,
+            preserve_comments: false,
+            require_terminators: false,
+            check_int_ranges: true,
+        }
+    }
+}
+
+/// The largest width of `iN` and `nN` types accepted by the parser by default.
+pub const DEFAULT_MAX_INT_WIDTH: usize = 1 << 24;
+
+/// The deepest nesting of types accepted by the parser by default.
+pub const DEFAULT_MAX_TYPE_DEPTH: usize = 256;
+
+/// An error encountered while parsing assembly.
+///
+/// The variants other than the syntax errors carry a message that describes
+/// the error in detail, often prefixed with the unit it occurred in. Use
+/// `to_string()` to obtain the full message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseError {
+    /// A character that does not start any token.
+    InvalidToken { location: usize },
+    /// The input ended where more tokens were expected.
+    UnexpectedEof {
+        location: usize,
+        expected: Vec<String>,
+    },
+    /// A token that may not appear where it was found.
+    UnexpectedToken {
+        token: String,
+        start: usize,
+        end: usize,
+        expected: Vec<String>,
+    },
+    /// A reference to a type or constant that has not been declared.
+    UndeclaredName(String),
+    /// A type or constant that is declared multiple times.
+    Redefinition(String),
+    /// A value whose type does not fit where it is used.
+    TypeMismatch(String),
+    /// A constant or type that exceeds its range or the parser's limits.
+    Overflow(String),
+    /// Any other malformed input, such as an instruction outside of a block.
+    Malformed(String),
+    /// Multiple errors, for example in different units of a module.
+    Multiple(Vec<ParseError>),
+}
+
+impl ParseError {
+    /// Combine a list of errors into one.
+    fn from_errors(mut errors: Vec<ParseError>) -> Self {
+        if errors.len() == 1 {
+            errors.pop().unwrap()
+        } else {
+            ParseError::Multiple(errors)
+        }
+    }
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        // The syntax errors are formatted the same way as by lalrpop.
+        let fmt_expected = |f: &mut std::fmt::Formatter, expected: &[String]| {
+            if !expected.is_empty() {
+                writeln!(f)?;
+                for (i, e) in expected.iter().enumerate() {
+                    let sep = match i {
+                        0 => "Expected one of",
+                        _ if i < expected.len() - 1 => ",",
+                        _ => " or",
+                    };
+                    write!(f, "{} {}", sep, e)?;
+                }
+            }
+            Ok(())
+        };
+        match self {
+            ParseError::InvalidToken { location } => write!(f, "Invalid token at {}", location),
+            ParseError::UnexpectedEof { location, expected } => {
+                write!(f, "Unrecognized EOF found at {}", location)?;
+                fmt_expected(f, expected)
+            }
+            ParseError::UnexpectedToken {
+                token,
+                start,
+                end,
+                expected,
+            } => {
+                write!(
+                    f,
+                    "Unrecognized token `{}` found at {}:{}",
+                    token, start, end
+                )?;
+                fmt_expected(f, expected)
+            }
+            ParseError::UndeclaredName(msg)
+            | ParseError::Redefinition(msg)
+            | ParseError::TypeMismatch(msg)
+            | ParseError::Overflow(msg)
+            | ParseError::Malformed(msg) => write!(f, "{}", msg),
+            ParseError::Multiple(errors) => {
+                for (i, error) in errors.iter().enumerate() {
+                    if i > 0 {
+                        writeln!(f)?;
+                    }
+                    write!(f, "{}", error)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+impl<T: std::fmt::Display> From<lalrpop_util::ParseError<usize, T, ParseError>> for ParseError {
+    fn from(error: lalrpop_util::ParseError<usize, T, ParseError>) -> Self {
+        use lalrpop_util::ParseError::*;
+        match error {
+            InvalidToken { location } => ParseError::InvalidToken { location },
+            UnrecognizedEOF { location, expected } => {
+                ParseError::UnexpectedEof { location, expected }
+            }
+            UnrecognizedToken {
+                token: (start, token, end),
+                expected,
+            } => ParseError::UnexpectedToken {
+                token: token.to_string(),
+                start,
+                end,
+                expected,
+            },
+            ExtraToken {
+                token: (start, token, end),
+            } => ParseError::UnexpectedToken {
+                token: token.to_string(),
+                start,
+                end,
+                expected: vec![],
+            },
+            User { error } => error,
+        }
+    }
+}
+
+/// Parse a module with custom options.
+///
+/// Parses the `input` string into a module, and links and verifies it like
+/// `parse_module`.
+pub fn parse_module_with(
+    input: impl AsRef<str>,
+    options: &ParseOptions,
+) -> Result<Module, ParseError> {
+    parse_module_unchecked_with(input, options).map(|mut module| {
         module.link();
         module.verify();
         module
@@ -52,12 +278,1510 @@ pub fn parse_module(input: impl AsRef<str>) -> Result<Module, String> {
 }
 
 /// Parse a module without linking and verifying it.
-pub fn parse_module_unchecked(input: impl AsRef<str>) -> Result<Module, String> {
-    reader::ModuleParser::new()
-        .parse(input.as_ref())
+///
+/// Block comments of the form `/* ... */` are removed before parsing. Blocks of
+/// the form `repeat N { ... }` are unrolled, with every `$i` in the block
+/// replaced by the iteration index. Functions are checked to not contain any
+/// instructions reserved for processes and entities, such as `wait` or `drv`,
+/// and calls and instances to units in the module are checked to pass the
+/// expected number of arguments.
+pub fn parse_module_unchecked(input: impl AsRef<str>) -> Result<Module, ParseError> {
+    parse_module_unchecked_with(input, &Default::default())
+}
+
+/// Parse a module with custom options, without linking and verifying it.
+///
+/// Performs the same checks as `parse_module_unchecked`.
+pub fn parse_module_unchecked_with(
+    input: impl AsRef<str>,
+    options: &ParseOptions,
+) -> Result<Module, ParseError> {
+    parse_module_impl(input.as_ref(), reader::ParseState::new(options))
+}
+
+/// Parse the units of a module one at a time.
+///
+/// Yields each function, process, and entity in the `input` as soon as it has
+/// been parsed, without building a module. Named types and constants are
+/// remembered across units, such that later units may refer to them.
+/// Declarations are skipped. The units are checked like by
+/// `parse_module_unchecked`, except that calls and instances can only be
+/// checked against the units and declarations that precede them. Offsets in
+/// errors and location hints refer to the full input. Iteration stops after
+/// the first error.
+pub fn parse_units(input: &str) -> impl Iterator<Item = Result<UnitData, ParseError>> + '_ {
+    parse_units_with(input, &Default::default())
+}
+
+/// Parse the units of a module one at a time, with custom options.
+///
+/// Yields the units like `parse_units`.
+pub fn parse_units_with<'a>(
+    input: &'a str,
+    options: &ParseOptions,
+) -> impl Iterator<Item = Result<UnitData, ParseError>> + 'a {
+    let expanded = reader::strip_block_comments(input)
+        .and_then(|input| reader::expand_repeats(&input).map(|expanded| expanded.into_owned()));
+    let (expanded, mut error) = match expanded {
+        Ok(expanded) => (Some(expanded), None),
+        Err(e) => (None, Some(e)),
+    };
+    let mut state = reader::ParseState::new(options);
+    let parser = reader::ItemParser::new();
+    let mut symbols = HashMap::new();
+    let mut pos = 0;
+    std::iter::from_fn(move || {
+        if let Some(e) = error.take() {
+            return Some(Err(e));
+        }
+        let expanded = expanded.as_ref()?;
+        let input = &expanded.text[..];
+        while pos < input.len() {
+            // Each chunk extends from the end of the previous item up to the
+            // keyword that starts the item after the current one.
+            let rest = &input[pos..];
+            let end = match reader::find_item(rest) {
+                Some(start) => reader::find_item(&rest[start + 1..])
+                    .map(|next| start + 1 + next)
+                    .unwrap_or(rest.len()),
+                None => rest.len(),
+            };
+            let offset = pos;
+            pos += end;
+            if state.preserve_comments {
+                state.newlines = rest[..end].match_indices('\n').map(|(i, _)| i).collect();
+            }
+            let mut item = parser.parse(&state, &rest[..end]).map_err(|e| {
+                ParseError::from(e.map_location(|loc| expanded.source_offset(loc + offset)))
+            });
+            let mut errors = state.errors.replace(vec![]);
+            if let Ok(Some(reader::Unit::Data(data, _))) = &mut item {
+                let mut builder = UnitBuilder::new_anonymous(data);
+                for named in state.named_ports.take() {
+                    let result =
+                        reader::resolve_named_ports(&mut builder, &named, &state.ports.borrow());
+                    errors.extend(result.err());
+                }
+                // The hints refer to the chunk; make them refer to the input.
+                rebase_hints(&mut builder, |loc| expanded.source_offset(loc + offset));
+            }
+            if let Ok(Some(reader::Unit::Declare(name, sig, _))) = &item {
+                symbols.insert(name.clone(), sig.clone());
+            }
+            let result = match item {
+                Ok(Some(reader::Unit::Data(data, _))) if errors.is_empty() => {
+                    let unit = Unit::new_anonymous(&data);
+                    symbols.insert(unit.name().clone(), unit.sig().clone());
+                    let mut arity = vec![];
+                    crate::verifier::verify_unit_arity(unit, &symbols, &mut arity);
+                    match unit.kind() {
+                        UnitKind::Function => unit
+                            .validate()
+                            .map_err(|errs| ParseError::Malformed(errs.to_string())),
+                        _ => Ok(()),
+                    }
+                    .and_then(|_| {
+                        if arity.is_empty() {
+                            Ok(())
+                        } else {
+                            Err(ParseError::Malformed(arity.join("\n")))
+                        }
+                    })
+                    .map(|_| data)
+                }
+                Ok(Some(reader::Unit::Data(..))) => Err(ParseError::from_errors(errors)),
+                Ok(_) => continue,
+                Err(e) => Err(e),
+            };
+            if result.is_err() {
+                pos = input.len();
+            }
+            return Some(result);
+        }
+        None
+    })
+}
+
+fn parse_module_impl(input: &str, mut state: reader::ParseState) -> Result<Module, ParseError> {
+    let input = reader::strip_block_comments(input)?;
+    let expanded = reader::expand_repeats(&input)?;
+    let input = &expanded.text[..];
+    if state.preserve_comments {
+        state.newlines = input.match_indices('\n').map(|(i, _)| i).collect();
+    }
+    let mut module = reader::ModuleParser::new()
+        .parse(&state, input)
         .map(|m| {
             debug!("Parsed module:\n{}", m.dump());
             m
         })
-        .map_err(|e| format!("{}", e))
+        .map_err(|e| ParseError::from(e.map_location(|loc| expanded.source_offset(loc))))?;
+    for named in state.named_ports.take() {
+        let id = module.lookup_unit(&named.unit).unwrap();
+        let result =
+            reader::resolve_named_ports(&mut module.unit_mut(id), &named, &state.ports.borrow());
+        state.errors.borrow_mut().extend(result.err());
+    }
+    // The hints refer to the expanded text; make them refer to the input.
+    for mut unit in module.units_mut() {
+        rebase_hints(&mut unit, |loc| expanded.source_offset(loc));
+    }
+    let errors = state.errors.into_inner();
+    if !errors.is_empty() {
+        return Err(ParseError::from_errors(errors));
+    }
+    for unit in module.functions() {
+        unit.validate()
+            .map_err(|errs| ParseError::Malformed(errs.to_string()))?;
+    }
+    let errors = crate::verifier::verify_arity(&module);
+    if !errors.is_empty() {
+        return Err(ParseError::Malformed(errors.join("\n")));
+    }
+    Ok(module)
+}
+
+/// Map the location and span hints of the instructions in a unit through
+/// `to_input`.
+fn rebase_hints(builder: &mut UnitBuilder, to_input: impl Fn(usize) -> usize) {
+    for inst in builder.all_insts().collect::<Vec<_>>() {
+        if let Some(loc) = builder.location_hint(inst) {
+            builder.set_location_hint(inst, to_input(loc));
+        }
+        if let Some(span) = builder.span_hint(inst) {
+            let span = Span {
+                start: to_input(span.start),
+                end: to_input(span.end),
+            };
+            builder.set_span_hint(inst, span);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn numeric_names() {
+        let input = "func @foo () i32 {
+            %entry:
+                %1 = const i32 1
+                %2 = const i32 2
+                %0 = add i32 %1, %2
+                ret i32 %0
+            }";
+
+        // By default numeric names are only kept as hints.
+        let module = parse_module(input).unwrap();
+        let unit = module.units().next().unwrap();
+        let value = unit
+            .all_insts()
+            .nth(2)
+            .map(|i| unit.inst_result(i))
+            .unwrap();
+        assert_eq!(unit.get_name(value), None);
+        assert_eq!(unit.get_anonymous_hint(value), Some(0));
+
+        // Optionally they are kept as names and survive a round trip.
+        let options = ParseOptions {
+            numeric_names: true,
+            ..Default::default()
+        };
+        let module = parse_module_with(input, &options).unwrap();
+        let unit = module.units().next().unwrap();
+        let value = unit
+            .all_insts()
+            .nth(2)
+            .map(|i| unit.inst_result(i))
+            .unwrap();
+        assert_eq!(unit.get_name(value), Some("0"));
+        let output = write_module_string(&module);
+        assert!(output.contains("%\"0\" = add i32 %\"1\", %\"2\""));
+        let module = parse_module_with(&output, &options).unwrap();
+        assert_eq!(write_module_string(&module), output);
+        let module = parse_module(&output).unwrap();
+        assert_eq!(write_module_string(&module), output);
+    }
+
+    #[test]
+    fn int_const_range() {
+        let parse = |imm: &str| {
+            parse_module_unchecked(format!(
+                "func @foo () void {{\n%entry:\n    %x = const i8 {}\n    ret\n}}",
+                imm
+            ))
+        };
+        assert!(parse("127").is_ok());
+        assert!(parse("128").is_ok());
+        assert!(parse("255").is_ok());
+        assert!(parse("256").is_err());
+        assert!(parse("-128").is_ok());
+        assert!(parse("-129").is_err());
+    }
+
+    #[test]
+    fn named_types() {
+        let module = parse_module(
+            "type @pixel = {i32, i32, i32}
+            func @foo (@pixel %p) void {
+            %entry:
+                ret
+            }",
+        )
+        .unwrap();
+        let pixel = crate::struct_ty(vec![crate::int_ty(32); 3]);
+        assert_eq!(module.get_type("pixel"), Some(&pixel));
+        let unit = module.units().next().unwrap();
+        assert_eq!(
+            unit.sig().arg_type(unit.sig().args().next().unwrap()),
+            pixel
+        );
+        assert!(write_module_string(&module).starts_with("type @pixel = {i32, i32, i32}\n"));
+        assert!(parse_module("type @pixel = {i32}\ntype @pixel = {i32}").is_err());
+    }
+
+    #[test]
+    fn use_before_def() {
+        let err = parse_module_unchecked(
+            "func @foo (i32 %a) i32 {
+            %entry:
+                %1 = add i32 %a, %2
+                %2 = add i32 %a, %a
+                ret i32 %1
+            }",
+        )
+        .err()
+        .unwrap()
+        .to_string();
+        assert_eq!(err, "func @foo: %2 used before its definition");
+
+        // Forward references from phi nodes and other blocks are fine.
+        assert!(parse_module(
+            "func @foo (i32 %a, i1 %c) i32 {
+            %entry:
+                br %loop
+            %loop:
+                %x = phi i32 [%a, %entry], [%y, %loop]
+                %y = add i32 %x, %a
+                br %c, %loop, %exit
+            %exit:
+                ret i32 %x
+            }",
+        )
+        .is_ok());
+    }
+
+    #[test]
+    fn result_type_annotations() {
+        let module = parse_module(
+            "func @foo (i32 %a, i32 %b) i1 {
+            %entry:
+                %r: i32 = add i32 %a, %b
+                %c: i1 = eq i32 %r, %b
+                br %exit
+            %exit:
+                ret i1 %c
+            }
+            entity @bar () -> () {
+                %z: i8 = const i8 0
+                %s: i8$ = sig i8 %z
+            }",
+        )
+        .unwrap();
+        let plain = parse_module(
+            "func @foo (i32 %a, i32 %b) i1 {
+            %entry:
+                %r = add i32 %a, %b
+                %c = eq i32 %r, %b
+                br %exit
+            %exit:
+                ret i1 %c
+            }
+            entity @bar () -> () {
+                %z = const i8 0
+                %s = sig i8 %z
+            }",
+        )
+        .unwrap();
+        assert_eq!(write_module_string(&module), write_module_string(&plain));
+
+        let err = parse_module_unchecked(
+            "func @foo (i32 %a, i32 %b) i32 {
+            %entry:
+                %r: i32 = add i32 %a, %b
+                %c: i32 = eq i32 %r, %b
+                ret i32 %r
+            }",
+        )
+        .err()
+        .unwrap()
+        .to_string();
+        assert_eq!(err, "func @foo: %c annotated as i32, but has type i1");
+    }
+
+    #[test]
+    fn probe_and_drive_signals() {
+        assert!(parse_module(
+            "entity @foo (i32$ %s) -> () {
+                %x = prb i32$ %s
+                %t = const time 1ns
+                drv i32$ %s, %x, %t
+            }",
+        )
+        .is_ok());
+
+        let err = parse_module_unchecked(
+            "entity @foo (i32 %a) -> () {
+                %x = prb i32 %a
+            }",
+        )
+        .err()
+        .unwrap()
+        .to_string();
+        assert_eq!(err, "entity @foo: %a probed, but is not a signal");
+
+        let err = parse_module_unchecked(
+            "entity @foo (i32 %a) -> () {
+                %t = const time 1ns
+                %c = const i1 1
+                drv i32 %a, %a, %t
+                drv i32 %a if %c, %a, %t
+            }",
+        )
+        .err()
+        .unwrap()
+        .to_string();
+        assert_eq!(
+            err,
+            "entity @foo: %a driven, but is not a signal\n\
+             entity @foo: %a driven, but is not a signal"
+        );
+    }
+
+    #[test]
+    fn handle_aggregates() {
+        let module = parse_module(
+            "func @foo (i32* %p, i8$ %s) void {
+            %entry:
+                %a = {i32* %p, i8$ %s}
+                %b = [i32* %p, %p]
+                ret
+            }",
+        )
+        .unwrap();
+        let unit = module.units().next().unwrap();
+        let tys: Vec<_> = unit
+            .all_insts()
+            .filter_map(|inst| unit.get_inst_result(inst))
+            .map(|value| unit.value_type(value))
+            .collect();
+        assert_eq!(
+            tys,
+            vec![
+                crate::struct_ty(vec![
+                    crate::pointer_ty(crate::int_ty(32)),
+                    crate::signal_ty(crate::int_ty(8)),
+                ]),
+                crate::array_ty(2, crate::pointer_ty(crate::int_ty(32))),
+            ]
+        );
+
+        let err = parse_module_unchecked(
+            "func @foo (i32* %p) void {
+            %entry:
+                %a = {i32* %p, i8$ 4}
+                ret
+            }",
+        )
+        .err()
+        .unwrap()
+        .to_string();
+        assert!(err.contains("aggregate elements must be named values"));
+    }
+
+    #[test]
+    fn sorted_units() {
+        let mut module = Module::new();
+        for name in &["c", "a", "b"] {
+            let input = format!("func @{} () void {{\n%entry:\n    ret\n}}", name);
+            module.merge(parse_module(input).unwrap()).unwrap();
+        }
+        assert!(write_module_string(&module).starts_with("func @c"));
+
+        let mut asm = vec![];
+        let mut writer = Writer::new(&mut asm);
+        writer.set_sorted(true);
+        writer.write_module(&module).unwrap();
+        let asm = String::from_utf8(asm).unwrap();
+        let names: Vec<_> = asm
+            .lines()
+            .filter(|line| line.starts_with("func"))
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                "func @a () void {",
+                "func @b () void {",
+                "func @c () void {",
+            ]
+        );
+    }
+
+    #[test]
+    fn array_styles() {
+        let input = "entity @foo () -> ([4 x i8]$ %out) {\n    %0 = const i8 0\n    %1 = [4 x i8 %0]\n    %2 = sig [4 x i8] %1\n}\n";
+        let module = parse_module(input).unwrap();
+        assert_eq!(write_module_string(&module), input);
+        assert_eq!(parse_type("<4 x i8>"), parse_type("[4 x i8]"));
+
+        let mut asm = vec![];
+        let mut writer = Writer::new(&mut asm);
+        writer.set_array_style(ArrayStyle::Angle);
+        writer.write_module(&module).unwrap();
+        let asm = String::from_utf8(asm).unwrap();
+        assert_eq!(
+            asm,
+            "entity @foo () -> (<4 x i8>$ %out) {\n    %0 = const i8 0\n    %1 = [4 x i8 %0]\n    %2 = sig <4 x i8> %1\n}\n"
+        );
+        assert_eq!(write_module_string(&parse_module(asm).unwrap()), input);
+    }
+
+    #[test]
+    fn nested_arrays() {
+        let ty = parse_type("[2 x [3 x i8]]").unwrap();
+        assert_eq!(ty, crate::array_ty(2, crate::array_ty(3, crate::int_ty(8))));
+        assert_eq!(ty.bit_width(), Some(48));
+        assert_eq!(ty.to_string(), "[2 x [3 x i8]]");
+        assert_eq!(parse_type("<2 x [3 x i8]>"), Ok(ty.clone()));
+        assert_eq!(parse_type("[2 x <3 x i8>]$"), Ok(crate::signal_ty(ty)));
+
+        let input = "entity @foo () -> ([2 x [3 x i8]]$ %out) {
+    %0 = const i8 0
+    %1 = [3 x i8 %0]
+    %2 = [2 x [3 x i8] %1]
+    %3 = extf [3 x i8], [2 x [3 x i8]] %2, 1
+    %4 = extf i8, [3 x i8] %3, 2
+    %5 = sig [2 x [3 x i8]] %2
+}
+";
+        let module = parse_module(input).unwrap();
+        assert_eq!(write_module_string(&module), input);
+        let mut asm = vec![];
+        let mut writer = Writer::new(&mut asm);
+        writer.set_array_style(ArrayStyle::Angle);
+        writer.write_module(&module).unwrap();
+        let asm = String::from_utf8(asm).unwrap();
+        assert!(asm.contains("%5 = sig <2 x <3 x i8>> %2"), "{}", asm);
+        assert_eq!(write_module_string(&parse_module(asm).unwrap()), input);
+    }
+
+    #[test]
+    fn type_comments() {
+        let module = parse_module(
+            "func @foo (i32 %a, i32 %b) i32 {
+            %entry:
+                %c = add i32 %a, %b
+                %d = eq i32 %a, %c
+                ret i32 %c
+            }",
+        )
+        .unwrap();
+        let mut asm = vec![];
+        let mut writer = Writer::new(&mut asm);
+        writer.set_type_comments(true);
+        writer.write_module(&module).unwrap();
+        let asm = String::from_utf8(asm).unwrap();
+        assert!(asm.contains("%c = add i32 %a, %b ; -> i32\n"));
+        assert!(asm.contains("%d = eq i32 %a, %c ; -> i1\n"));
+        assert!(asm.contains("ret i32 %c\n"));
+        assert_eq!(
+            write_module_string(&parse_module(asm).unwrap()),
+            write_module_string(&module)
+        );
+    }
+
+    #[test]
+    fn bare_copies() {
+        let module = parse_module(
+            "func @foo (i32 %a, i1$ %s) i32 {
+            %entry:
+                %b = i32 %a
+                %t = i1$ %s
+                %c = add i32 %b, %a
+                ret i32 %c
+            }",
+        )
+        .unwrap();
+        let asm = write_module_string(&module);
+        assert!(asm.contains("%b = alias i32 %a\n"));
+        assert!(asm.contains("%t = alias i1$ %s\n"));
+        assert!(asm.contains("%c = add i32 %b, %a\n"));
+    }
+
+    #[test]
+    fn preserve_comments() {
+        let input = "func @foo (i32 %a) i32 {
+entry:
+    ; Double the input.
+    %b = add i32 %a, %a ; twice
+    ;
+    ; Then return it.
+    ret i32 %b
+}
+
+entity @bar (i32$ %x) -> () {
+    ; Sample the signal.
+    %y = prb i32$ %x ; current value
+}
+";
+        let options = ParseOptions {
+            preserve_comments: true,
+            ..Default::default()
+        };
+        let module = parse_module_with(input, &options).unwrap();
+        assert_eq!(write_module_string(&module), input);
+        let module = parse_module(input).unwrap();
+        assert!(!write_module_string(&module).contains(';'));
+    }
+
+    #[test]
+    fn indirect_calls() {
+        let input = "func @apply ((i32, i32) i32 %fp, i32 %x) i32 {
+entry:
+    %y = call i32 %fp (i32 %x, i32 %x)
+    %z = call i32 @apply ((i32, i32) i32 %fp, i32 %y)
+    ret i32 %z
+}
+";
+        let module = parse_module(input).unwrap();
+        assert_eq!(write_module_string(&module), input);
+        let unit = module.units().next().unwrap();
+        let opcodes: Vec<_> = unit.all_insts().map(|inst| unit[inst].opcode()).collect();
+        assert_eq!(opcodes[0], crate::ir::Opcode::CallIndirect);
+        assert_eq!(opcodes[1], crate::ir::Opcode::Call);
+
+        let err = parse_module_unchecked(
+            "func @foo ((i32) i32 %fp, i32 %x) void {\n%entry:\n    %y = call i1 %fp (i32 %x)\n    ret\n}",
+        )
+        .err()
+        .unwrap()
+        .to_string();
+        assert_eq!(
+            err,
+            "func @foo: %fp called as (i32) i1, but is of type (i32) i32"
+        );
+    }
+
+    #[test]
+    fn time_unit() {
+        let module = parse_module(
+            "func @foo () time {
+            %entry:
+                %a = const time 1ns
+                %b = const time 1.5fs 2d
+                ret time %a
+            }",
+        )
+        .unwrap();
+        let mut asm = vec![];
+        let mut writer = Writer::new(&mut asm);
+        writer.set_time_unit(crate::value::SiPrefix::Pico);
+        writer.write_module(&module).unwrap();
+        let asm = String::from_utf8(asm).unwrap();
+        assert!(asm.contains("%a = const time 1000ps\n"));
+        assert!(asm.contains("%b = const time 1500as 2d\n"));
+        assert_eq!(
+            write_module_string(&parse_module(asm).unwrap()),
+            write_module_string(&module)
+        );
+    }
+
+    #[test]
+    fn max_type_depth() {
+        let nested = |n| format!("{}i1{}", "{".repeat(n), "}".repeat(n));
+        assert!(parse_type(nested(DEFAULT_MAX_TYPE_DEPTH)).is_ok());
+        let err = parse_type(nested(10_000)).err().unwrap().to_string();
+        assert!(err.contains("type exceeds the maximum nesting depth"));
+        let err = parse_type(format!("{}i1", "() ".repeat(10_000)))
+            .err()
+            .unwrap()
+            .to_string();
+        assert!(err.contains("type exceeds the maximum nesting depth"));
+
+        let input = format!("func @foo ({} %a) void {{\n%entry:\n    ret\n}}", nested(3));
+        let max_type_depth = |max_type_depth| ParseOptions {
+            max_type_depth,
+            ..Default::default()
+        };
+        assert!(parse_module_with(&input, &max_type_depth(3)).is_ok());
+        assert!(parse_module_with(&input, &max_type_depth(2)).is_err());
+    }
+
+    #[test]
+    fn aggregate_signal_init() {
+        let module = parse_module(
+            "entity @foo () -> () {
+                %one = const i8 1
+                %two = const i8 2
+                %a = [i8 %one, %two]
+                %b = {i8 %one, i8 %two}
+                %s = sig [2 x i8] %a
+                %t = sig {i8, i8} %b
+            }",
+        )
+        .unwrap();
+        let asm = write_module_string(&module);
+        assert!(asm.contains("%s = sig [2 x i8] %a\n"));
+        assert!(asm.contains("%t = sig {i8, i8} %b\n"));
+
+        let err = parse_module_unchecked(
+            "entity @foo () -> () {
+                %one = const i8 1
+                %two = const i8 2
+                %a = [i8 %one, %two]
+                %s = sig {i8, i8} %a
+            }",
+        )
+        .err()
+        .unwrap()
+        .to_string();
+        assert_eq!(
+            err,
+            "entity @foo: signal of type {i8, i8} initialized with %a of type [2 x i8]"
+        );
+    }
+
+    #[test]
+    fn poison() {
+        let input = "func @foo () i32 {\nentry:\n    %p = const i32 poison\n    ret i32 %p\n}\n";
+        let module = parse_module(input).unwrap();
+        let unit = module.units().next().unwrap();
+        let inst = unit.all_insts().next().unwrap();
+        assert_eq!(unit[inst].opcode(), crate::ir::Opcode::ConstPoison);
+        assert_eq!(unit.inst_type(inst), crate::int_ty(32));
+        assert_eq!(write_module_string(&module), input);
+    }
+
+    #[test]
+    fn block_params() {
+        let module = parse_module(
+            "func @foo (i1 %c, i32 %a, i32 %b) i32 {
+            %entry:
+                br %c, %left, %right
+            %left:
+                br %join(%a)
+            %right:
+                %d = add i32 %b, %b
+                br %join(%d)
+            %join(i32 %x):
+                ret i32 %x
+            }",
+        )
+        .unwrap();
+        assert_eq!(
+            write_module_string(&module),
+            "func @foo (i1 %c, i32 %a, i32 %b) i32 {\n\
+             entry:\n    \
+                 br %c, %left, %right\n\
+             left:\n    \
+                 br %join\n\
+             right:\n    \
+                 %d = add i32 %b, %b\n    \
+                 br %join\n\
+             join:\n    \
+                 %x = phi i32 [%a, %left], [%d, %right]\n    \
+                 ret i32 %x\n\
+             }\n"
+        );
+
+        let err = parse_module(
+            "func @foo (i32 %a) i32 {
+            %entry:
+                br %join(%a, %a)
+            %join(i32 %x):
+                ret i32 %x
+            }",
+        )
+        .err()
+        .unwrap()
+        .to_string();
+        assert!(
+            err.contains(
+                "func @foo: branch from %entry to %join passes 2 arguments, but the block has 1 \
+                 parameters"
+            ),
+            "{}",
+            err
+        );
+        let err = parse_module(
+            "func @foo (i8 %a) i32 {
+            %entry:
+                br %join(%a)
+            %join(i32 %x):
+                ret i32 %x
+            }",
+        )
+        .err()
+        .unwrap()
+        .to_string();
+        assert!(
+            err.contains(
+                "func @foo: branch to %join passes %a of type i8 for parameter %x of type i32"
+            ),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn named_consts() {
+        let input = "const @max = i32 255
+            const @period = time 1ns
+
+            func @clamp (i32 %a) i32 {
+            entry:
+                %b = add i32 %a, @max
+                br %next
+            next:
+                %c = umul i32 %b, @max
+                ret i32 %c
+            }";
+        let module = parse_module(input).unwrap();
+        assert_eq!(
+            module.get_const("max"),
+            Some(&crate::IntValue::from_usize(32, 255).into())
+        );
+        assert_eq!(module.consts().count(), 2);
+        let asm = write_module_string(&module);
+        assert_eq!(
+            asm,
+            "const @max = i32 255\n\
+             const @period = time 1ns\n\
+             \n\
+             func @clamp (i32 %a) i32 {\n\
+             entry:\n    \
+                 %0 = const i32 255\n    \
+                 %b = add i32 %a, %0\n    \
+                 br %next\n\
+             next:\n    \
+                 %c = umul i32 %b, %0\n    \
+                 ret i32 %c\n\
+             }\n"
+        );
+        let module = parse_module(&asm).unwrap();
+        assert_eq!(write_module_string(&module), asm);
+        assert!(parse_module("func @f () void { entry: %a = not i1 @x ret }").is_err());
+        assert!(parse_module("const @x = i1 0\nconst @x = i1 1").is_err());
+    }
+
+    #[test]
+    fn bool_literals() {
+        let module = parse_module(
+            "func @f (i1 %a) i1 {
+            entry:
+                br true, %t, %f
+            t:
+                %b = and i1 %a, false
+                ret i1 %b
+            f:
+                ret i1 true
+            }",
+        )
+        .unwrap();
+        assert_eq!(
+            write_module_string(&module),
+            "func @f (i1 %a) i1 {\n\
+             entry:\n    \
+                 %0 = const i1 1\n    \
+                 %1 = const i1 0\n    \
+                 br %0, %t, %f\n\
+             t:\n    \
+                 %b = and i1 %a, %1\n    \
+                 ret i1 %b\n\
+             f:\n    \
+                 ret i1 %0\n\
+             }\n"
+        );
+        let err = parse_module("func @f (i32 %a) i32 { entry: %b = add i32 %a, true ret i32 %b }")
+            .err()
+            .unwrap()
+            .to_string();
+        assert!(
+            err.contains("true can only be used where an i1 is expected"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn parse_options() {
+        let input = "func @f ({i64, {i1}} %a) i64 {
+            entry:
+                %0 = extf i64, {i64, {i1}} %a, 0
+                ret i64 %0
+            }";
+        let strict = ParseOptions {
+            max_int_width: 32,
+            max_type_depth: 1,
+            ..Default::default()
+        };
+        let lenient = ParseOptions {
+            numeric_names: true,
+            max_int_width: 64,
+            ..Default::default()
+        };
+        assert!(parse_module_with(input, &strict).is_err());
+        let module = parse_module_with(input, &lenient).unwrap();
+        assert!(write_module_string(&module).contains("%\"0\" = extf"));
+        assert_eq!(
+            parse_module_with(input, &Default::default()).is_ok(),
+            parse_module(input).is_ok()
+        );
+
+        // Missing terminators and truncated constants are only rejected on
+        // request, by every entry point.
+        let input = "func @g () i8 {
+            entry:
+                %a = const i8 256
+            }";
+        let strict = ParseOptions {
+            require_terminators: true,
+            ..Default::default()
+        };
+        let lenient = ParseOptions {
+            check_int_ranges: false,
+            ..Default::default()
+        };
+        let err = parse_module_unchecked_with(input, &strict).err().unwrap();
+        assert!(err.to_string().contains("integer constant out of range"));
+        let err = parse_module_unchecked_with(input.replace("256", "0"), &strict)
+            .err()
+            .unwrap();
+        assert_eq!(
+            err.to_string(),
+            "func @g: block %entry does not end in a terminator"
+        );
+        assert!(parse_units_with(&input.replace("256", "0"), &strict)
+            .next()
+            .unwrap()
+            .is_err());
+        let module = parse_module_unchecked_with(input, &lenient).unwrap();
+        assert!(write_module_string(&module).contains("%a = const i8 0"));
+        assert!(parse_units_with(input, &lenient).next().unwrap().is_ok());
+        assert!(parse_units(input).next().unwrap().is_err());
+    }
+
+    #[test]
+    fn max_int_width() {
+        let input = "func @foo (i64 %a, n300 %b) void {\n%entry:\n    ret\n}";
+        let max_int_width = |max_int_width| ParseOptions {
+            max_int_width,
+            ..Default::default()
+        };
+        assert!(parse_module_with(input, &max_int_width(300)).is_ok());
+        assert_eq!(
+            parse_module_with(input, &max_int_width(32))
+                .err()
+                .unwrap()
+                .to_string(),
+            "integer type exceeds the maximum width"
+        );
+        assert_eq!(
+            parse_module_with(input, &max_int_width(64))
+                .err()
+                .unwrap()
+                .to_string(),
+            "enum type exceeds the maximum number of states"
+        );
+        assert!(parse_type("i16777216").is_ok());
+        assert!(parse_type("i16777217").is_err());
+        assert!(parse_type("i99999999999999999999999").is_err());
+    }
+
+    #[test]
+    fn parse_error_kinds() {
+        match parse_module("func @f () void {\n%entry:\n    ret\n") {
+            Err(ParseError::UnexpectedEof { location, expected }) => {
+                assert_eq!(location, 33);
+                assert!(expected.contains(&"\"}\"".to_owned()));
+            }
+            _ => panic!("expected an unexpected end of input"),
+        }
+        assert!(matches!(
+            parse_module("func @f () void { %entry: ret ret ) }"),
+            Err(ParseError::UnexpectedToken { ref token, start: 34, end: 35, .. }) if token == ")"
+        ));
+        assert!(matches!(
+            parse_module("func @f () void { %entry: ret ? }"),
+            Err(ParseError::InvalidToken { location: 30 })
+        ));
+        assert!(matches!(
+            parse_type("@pixel"),
+            Err(ParseError::UndeclaredName(_))
+        ));
+        assert!(matches!(
+            parse_module("type @pixel = {i32}\ntype @pixel = {i8}"),
+            Err(ParseError::Redefinition(_))
+        ));
+        assert!(matches!(
+            parse_module("func @f (i32 %a) void { %entry: %b: i1 = add i32 %a, %a ret }"),
+            Err(ParseError::TypeMismatch(_))
+        ));
+        assert!(matches!(
+            parse_type("i99999999999999999999999"),
+            Err(ParseError::Overflow(_))
+        ));
+        assert!(matches!(
+            parse_module("func @f () void { %entry: %0 = const i8 256 ret }"),
+            Err(ParseError::Overflow(_))
+        ));
+        assert!(matches!(
+            parse_module("func @f () void { %entry: halt }"),
+            Err(ParseError::Malformed(_))
+        ));
+
+        // Errors in several units are reported together.
+        let err = parse_module(
+            "entity @a (i1 %x) -> () { drv i1 %x, %x, %x }\n\
+             entity @b (i1 %y) -> () { drv i1 %y, %y, %y }",
+        )
+        .err()
+        .unwrap();
+        match &err {
+            ParseError::Multiple(errors) => assert_eq!(errors.len(), 2),
+            _ => panic!("expected multiple errors, got {:?}", err),
+        }
+        assert_eq!(
+            err.to_string(),
+            "entity @a: %x driven, but is not a signal\n\
+             entity @b: %y driven, but is not a signal"
+        );
+    }
+
+    #[test]
+    fn sel_condition() {
+        assert!(parse_module(
+            "func @f (i1 %c, i32 %a, i32 %b) i32 {\n%entry:\n    %x = sel i32 %c, %a, %b\n    ret i32 %x\n}"
+        )
+        .is_ok());
+        assert_eq!(
+            parse_module(
+                "func @f (i8 %c, i32 %a, i32 %b) i32 {\n%entry:\n    %x = sel i32 %c, %a, %b\n    ret i32 %x\n}"
+            )
+            .err(),
+            Some(ParseError::TypeMismatch(
+                "func @f: sel condition %c must be i1, but is i8".to_owned()
+            ))
+        );
+    }
+
+    #[test]
+    fn zero_width_types() {
+        assert_eq!(parse_type("i1"), Ok(crate::int_ty(1)));
+        assert_eq!(parse_type("n1"), Ok(crate::enum_ty(1)));
+        assert_eq!(
+            parse_type("i0"),
+            Err(ParseError::Malformed(
+                "integer type must be at least 1 bit wide".to_owned()
+            ))
+        );
+        assert_eq!(
+            parse_type("n0"),
+            Err(ParseError::Malformed(
+                "enum type must have at least 1 state".to_owned()
+            ))
+        );
+        assert!(parse_type("{i32, i0}").is_err());
+    }
+
+    #[test]
+    fn delta_drive() {
+        let input = "entity @foo (i1$ %s) -> () {
+    %v = const i1 1
+    %t = const time 0s 1d
+    drv i1$ %s, %v, %t
+}
+";
+        let module = parse_module(input).unwrap();
+        let unit = module.units().next().unwrap();
+        let drv = unit
+            .all_insts()
+            .find(|&inst| unit[inst].opcode() == crate::ir::Opcode::Drv)
+            .unwrap();
+        let delay = unit.get_const_time(unit[drv].args()[2]).unwrap();
+        assert!(num::Zero::is_zero(delay.time()));
+        assert_eq!(delay.delta(), 1);
+        assert_eq!(delay.epsilon(), 0);
+        assert_eq!(write_module_string(&module), input);
+    }
+
+    #[test]
+    fn call_arity() {
+        let callee = "func @f (i32 %x) void {\n%entry:\n    ret\n}\n";
+        let caller = |args: &str| {
+            format!(
+                "{}func @g (i32 %a) void {{\n%entry:\n    call void @f ({})\n    ret\n}}",
+                callee, args
+            )
+        };
+        assert!(parse_module(caller("i32 %a")).is_ok());
+        assert_eq!(
+            parse_module(caller("")).err().unwrap().to_string(),
+            "func @g: call @f (): @f expects 1 inputs and 0 outputs, but 0 and 0 were given"
+        );
+        assert_eq!(
+            parse_module(caller("i32 %a, i32 %a"))
+                .err()
+                .unwrap()
+                .to_string(),
+            "func @g: call @f (%a, %a): @f expects 1 inputs and 0 outputs, but 2 and 0 were given"
+        );
+    }
+
+    #[test]
+    fn discarded_results() {
+        let callee = "func @f () i32 {\n%entry:\n    %x = const i32 42\n    ret i32 %x\n}\n";
+        let caller = |body: &str| {
+            format!(
+                "{}func @g () i32 {{\n%entry:\n    %_ = call i32 @f ()\n    %_ = const i32 1\n{}}}",
+                callee, body
+            )
+        };
+        let module = parse_module(caller("    %y = call i32 @f ()\n    ret i32 %y\n")).unwrap();
+        let unit = module.units().nth(1).unwrap();
+        let calls: Vec<_> = unit
+            .all_insts()
+            .filter(|&inst| unit[inst].opcode() == crate::ir::Opcode::Call)
+            .collect();
+        assert_eq!(calls.len(), 2);
+        assert_eq!(unit.get_name(unit.inst_result(calls[0])), None);
+        assert!(write_module_string(&module).contains("    %0 = call i32 @f ()\n"));
+        assert_eq!(
+            parse_module(caller("    ret i32 %_\n"))
+                .err()
+                .unwrap()
+                .to_string(),
+            "func @g: %_ discards a result and cannot be used as a value"
+        );
+    }
+
+    #[test]
+    fn named_ports() {
+        let sub = "entity @sub (i1$ %a, i8$ %b) -> (i8$ %o) {\n}\n";
+        let top = |ports: &str| {
+            format!(
+                "{}entity @top (i1$ %x, i8$ %y) -> (i8$ %z) {{\n    inst @sub {}\n}}\n",
+                sub, ports
+            )
+        };
+        let positional = parse_module(top("(i1$ %x, i8$ %y) -> (i8$ %z)")).unwrap();
+        let named = parse_module(top("(.b i8$ %y, .a i1$ %x) -> (.o i8$ %z)")).unwrap();
+        assert_eq!(
+            write_module_string(&named),
+            write_module_string(&positional)
+        );
+
+        let err = |ports| parse_module(top(ports)).err().unwrap().to_string();
+        assert_eq!(
+            err("(.a i1$ %x, .c i8$ %y) -> (.o i8$ %z)"),
+            "entity @top: @sub has no port .c"
+        );
+        assert_eq!(
+            err("(.a i1$ %x, .a i1$ %x) -> (.o i8$ %z)"),
+            "entity @top: port .a of @sub connected multiple times"
+        );
+        assert_eq!(
+            err("(.a i1$ %x) -> (.o i8$ %z)"),
+            "entity @top: port .b of @sub is not connected"
+        );
+        assert_eq!(
+            err("(.a i1$ %x, i8$ %y) -> (.o i8$ %z)"),
+            "entity @top: inst @sub mixes named and positional ports"
+        );
+
+        // Streaming resolves the ports against the units parsed before.
+        let input = top("(.b i8$ %y, .a i1$ %x) -> (.o i8$ %z)");
+        let units: Vec<_> = parse_units(&input).collect();
+        assert!(units.iter().all(Result::is_ok));
+    }
+
+    #[test]
+    fn repeat_error_offsets() {
+        let input = "func @foo (i8 %a) void {
+            %entry:
+                repeat 3 {
+                    %c$i = const i8 $i
+                }
+                %x = add i8 %a, %a )
+                ret
+            }";
+        let expected = input.rfind(")").unwrap();
+        match parse_module(input).err().unwrap() {
+            ParseError::UnexpectedToken { start, .. } => assert_eq!(start, expected),
+            e => panic!("unexpected error {:?}", e),
+        }
+        match parse_units(input).next().unwrap().err().unwrap() {
+            ParseError::UnexpectedToken { start, .. } => assert_eq!(start, expected),
+            e => panic!("unexpected error {:?}", e),
+        }
+
+        let input = "func @foo () void {
+            %entry:
+                repeat 2 {
+                    %c$i = const i8 $i $i
+                }
+                ret
+            }";
+        let expected = input.rfind("$i").unwrap();
+        match parse_module(input).err().unwrap() {
+            ParseError::UnexpectedToken { start, .. } => assert_eq!(start, expected),
+            e => panic!("unexpected error {:?}", e),
+        }
+    }
+
+    #[test]
+    fn repeat_spans() {
+        let input = "func @foo (i8 %a) i8 {
+            %entry:
+                repeat 3 {
+                    %c$i = const i8 $i
+                }
+                %x = add i8 %a, %a
+                ret i8 %x
+            }";
+        let check = |unit: Unit| {
+            let add = unit.all_insts().nth(3).unwrap();
+            let span = unit.span_hint(add).unwrap();
+            assert_eq!(&input[span.start..span.end], "%x = add i8 %a, %a");
+            assert_eq!(unit.location_hint(add), Some(span.start));
+        };
+        let module = parse_module(input).unwrap();
+        check(module.units().next().unwrap());
+        let data = parse_units(input).next().unwrap().unwrap();
+        check(Unit::new_anonymous(&data));
+    }
+
+    #[test]
+    fn repeat_in_comments() {
+        for input in &[
+            "; repeat 2 { foo",
+            "/* repeat 2 { */",
+            "/* a /* nested */ repeat 2 { */",
+        ] {
+            assert_eq!(reader::expand_repeats(input).unwrap().text, *input);
+        }
+        let input = "repeat 2 { %x$i /* } */ ; }\n}";
+        assert_eq!(
+            reader::expand_repeats(input).unwrap().text,
+            " %x0 /* } */ ; }\n\n %x1 /* } */ ; }\n\n"
+        );
+    }
+
+    #[test]
+    fn block_comments_in_quoted_names() {
+        let input = "func @\"a/*b\" (i8 %\"c*/\\\"/*\") void {\nentry:\n    ret\n}\n";
+        assert_eq!(reader::strip_block_comments(input).unwrap(), input);
+        let module = parse_module(input).unwrap();
+        let unit = module.units().next().unwrap();
+        assert_eq!(unit.name().get_name(), Some("a/*b"));
+        assert_eq!(unit.get_name(unit.input_arg(0)), Some("c*/\"/*"));
+        assert_eq!(
+            reader::strip_block_comments("%\"/*\" /* x */").unwrap(),
+            "%\"/*\"        "
+        );
+    }
+
+    #[test]
+    fn streaming_units() {
+        let input = "; A few units.
+            type @pair = {i32, i32}
+
+            func @first (@pair %p) i32 {
+            %entry:
+                %x = extf i32, @pair %p, 0
+                ret i32 %x
+            }
+
+            declare @ext (i32) void
+
+            proc @second (i1$ %x) -> () {
+            %entry:
+                halt
+            }
+
+            entity @third (i1$ %x) -> (i1$ %y) {
+                con i1$ %x, %y
+            }
+            ; The end.
+            ";
+        let units: Vec<_> = parse_units(input)
+            .map(|unit| {
+                let unit = unit.unwrap();
+                let unit = Unit::new_anonymous(&unit);
+                format!("{} {}", unit.kind(), unit.name())
+            })
+            .collect();
+        assert_eq!(units, vec!["func @first", "proc @second", "entity @third"]);
+
+        let input =
+            "func @a () void {\n%entry:\n    ret\n}\nfunc @b () void {\n%entry:\n    bogus\n}";
+        let mut units = parse_units(input);
+        assert!(units.next().unwrap().is_ok());
+        assert!(units.next().unwrap().is_err());
+        assert!(units.next().is_none());
+    }
+
+    #[test]
+    fn streaming_units_checks() {
+        let input = "declare @ext (i32) void
+
+            func @f (i32 %a) void {
+            %entry:
+                call void @ext (i32 %a)
+                ret
+            }
+
+            func @g (i32 %a) void {
+            %entry:
+                call void @ext (i32 %a, i32 %a)
+                ret
+            }";
+        let mut units = parse_units(input);
+        let f = units.next().unwrap().ok().unwrap();
+        let f = Unit::new_anonymous(&f);
+        let call = f.all_insts().next().unwrap();
+        let span = f.span_hint(call).unwrap();
+        assert_eq!(&input[span.start..span.end], "call void @ext (i32 %a)");
+        assert_eq!(
+            units.next().unwrap().err().unwrap().to_string(),
+            "func @g: call @ext (%a, %a): @ext expects 1 inputs and 0 outputs, but 2 and 0 were given"
+        );
+        assert!(units.next().is_none());
+    }
+
+    #[test]
+    fn write_single_unit() {
+        let module = parse_module(
+            "type @pair = {i32, i32}
+
+            func @first (@pair %p) i32 {
+            entry:
+                %a = extf i32, @pair %p, 0
+                ret i32 %a
+            }
+
+            func @second (@pair %x) i32 {
+            entry:
+                %y = call i32 @first (@pair %x)
+                ret i32 %y
+            }",
+        )
+        .unwrap();
+        for unit in module.units() {
+            let asm = write_unit_string(unit);
+            assert!(!asm.contains("@pair"), "{}", asm);
+            let mut units = parse_units(&asm);
+            let data = units.next().unwrap().unwrap();
+            assert!(units.next().is_none());
+            assert_eq!(write_unit_string(Unit::new_anonymous(&data)), asm);
+        }
+        let first = module.units().next().unwrap();
+        assert_eq!(
+            write_unit_string(first),
+            "func @first ({i32, i32} %p) i32 {\n\
+             entry:\n    \
+                 %a = extf i32, {i32, i32} %p, 0\n    \
+                 ret i32 %a\n\
+             }\n"
+        );
+    }
+
+    #[test]
+    fn time_literals() {
+        use num::{BigInt, BigRational, One, Zero};
+        let pow10 = |n| num::pow(BigInt::from(10), n);
+
+        // Large positive scales without a fraction.
+        let t = parse_time("1Es").unwrap();
+        assert_eq!(t.time(), &BigRational::from_integer(pow10(18)));
+        assert!(parse_time("0Es").unwrap().time().is_zero());
+
+        // Fractions with a negative scale are reduced.
+        let t = parse_time("0.001ps").unwrap();
+        assert_eq!(t.time(), &BigRational::new(BigInt::one(), pow10(15)));
+        assert_eq!(t.time().denom(), &pow10(15));
+        let t = parse_time("-2.50ns").unwrap();
+        assert_eq!(t.time(), &BigRational::new(BigInt::from(-5), pow10(9) * 2));
+
+        // A leading integer digit is required.
+        assert!(parse_time(".5ns").is_err());
+        assert!(parse_time("-.5ns").is_err());
+    }
+
+    #[test]
+    fn time_steps() {
+        let t = parse_time("0s 1d 9001e").unwrap();
+        assert_eq!((t.delta(), t.epsilon()), (1, 9001));
+        assert_eq!(parse_time("0s 9001e 1d").unwrap(), t);
+        assert_eq!(parse_time("0s 9001e").unwrap().delta(), 0);
+        assert_eq!(
+            parse_time("0s 1d 2d").unwrap_err().to_string(),
+            "time 0s has more than one delta step"
+        );
+        assert!(parse_time("1ns 1e 2d 3e").is_err());
+
+        let module = parse_module(
+            "func @foo () time {\n%entry:\n    %t = const time 1ns 2e 3d\n    ret time %t\n}\n",
+        )
+        .unwrap();
+        assert!(write_module_string(&module).contains("%t = const time 1ns 3d 2e\n"));
+    }
+
+    #[test]
+    fn aggregate_comparison() {
+        let input = "func @foo ({i8, i8} %a, {i8, i8} %b, [2 x i8] %c) i1 {
+entry:
+    %x = eq {i8, i8} %a, %b
+    %y = neq [2 x i8] %c, %c
+    %z = and i1 %x, %y
+    ret i1 %z
+}
+";
+        let module = parse_module(input).unwrap();
+        module.verify();
+        assert_eq!(write_module_string(&module), input);
+        let mismatch =
+            parse_module_unchecked(input.replace("neq [2 x i8] %c", "neq [2 x i8] %a")).unwrap();
+        assert!(mismatch.typecheck().is_err());
+    }
+
+    #[test]
+    fn signal_arrays() {
+        use crate::ty::{array_ty, int_ty, signal_ty};
+        assert_eq!(
+            parse_type("[8 x i1$]").unwrap(),
+            array_ty(8, signal_ty(int_ty(1)))
+        );
+        assert_eq!(
+            parse_type("[8 x i1]$").unwrap(),
+            signal_ty(array_ty(8, int_ty(1)))
+        );
+        let input = "entity @foo ([8 x i1$] %bus, i1$ %a) -> () {
+    %t = const time 0s 1e
+    %ap = prb i1$ %a
+    %b3 = extf i1$, [8 x i1$] %bus, 3
+    drv i1$ %b3, %ap, %t
+    %b3p = prb i1$ %b3
+}
+";
+        let module = parse_module(input).unwrap();
+        module.verify();
+        assert_eq!(write_module_string(&module), input);
+    }
+
+    #[test]
+    fn quoted_names() {
+        let input = r#"type @"pixel$t" = {i8, i8}
+const @"one$" = i8 1
+
+func @"foo$bar" (i8 %"a$b", @"pixel$t" %"quote\"back\\slash") i8 {
+"entry$":
+    %"x y" = add i8 %"a$b", %"a$b"
+    br %"next$"
+"next$":
+    ret i8 %"x y"
+}
+
+declare %"local$" (i8) void
+"#;
+        let module = parse_module(input).unwrap();
+        let unit = module.units().next().unwrap();
+        assert_eq!(unit.name().get_name(), Some("foo$bar"));
+        assert_eq!(unit.get_name(unit.input_arg(1)), Some("quote\"back\\slash"));
+        assert_eq!(unit.get_block_name(unit.entry()), Some("entry$"));
+        assert!(module.get_type("pixel$t").is_some());
+        assert_eq!(write_module_string(&module), input);
+
+        // Names that look like comments, braces, `repeat` blocks, or
+        // temporaries survive a round trip through the writer and parser.
+        for name in &[
+            "a/*b",
+            "a;b",
+            "a{b",
+            "a}b",
+            "x repeat 2 {",
+            "func x",
+            "1",
+            "2x",
+        ] {
+            let mut module = Module::new();
+            let mut sig = crate::ir::Signature::new();
+            let arg = sig.add_input(crate::int_ty(8));
+            sig.set_return_type(crate::int_ty(8));
+            let mut func =
+                UnitData::new(UnitKind::Function, crate::ir::UnitName::global(*name), sig);
+            let mut builder = UnitBuilder::new_anonymous(&mut func);
+            let arg = builder.arg_value(arg);
+            builder.set_name(arg, name.to_string());
+            let bb = builder.named_block(*name);
+            builder.append_to(bb);
+            builder.ins().ret_value(arg);
+            module.add_unit(func);
+            let output = write_module_string(&module);
+            let parsed = parse_module(&output).unwrap();
+            assert_eq!(write_module_string(&parsed), output);
+            let unit = parsed.units().next().unwrap();
+            assert_eq!(unit.get_name(unit.input_arg(0)), Some(*name));
+            let units: Vec<_> = parse_units(&output).collect::<Result<_, _>>().unwrap();
+            assert_eq!(units.len(), 1);
+            assert_eq!(units[0].name.get_name(), Some(*name));
+        }
+
+        // Names starting with a digit are quoted.
+        let module =
+            parse_module("func @\"1\" (i8 %\"1\") i8 {\n%\"2\":\n    ret i8 %\"1\"\n}\n").unwrap();
+        assert_eq!(
+            write_module_string(&module),
+            "func @\"1\" (i8 %\"1\") i8 {\n\"2\":\n    ret i8 %\"1\"\n}\n"
+        );
+
+        // Quoted names that need no quotes are the same as bare names.
+        let module =
+            parse_module("func @\"foo\" (i8 %\"a\") i8 {\n%entry:\n    ret i8 %a\n}\n").unwrap();
+        assert_eq!(
+            write_module_string(&module),
+            "func @foo (i8 %a) i8 {\nentry:\n    ret i8 %a\n}\n"
+        );
+    }
+
+    /// Measure how long parsing a module with 100k instructions takes.
+    ///
+    /// Run with `cargo test --release parse_large_module -- --ignored
+    /// --nocapture`.
+    #[test]
+    #[ignore]
+    fn parse_large_module() {
+        // Every constant spells out its type, which the parser interns.
+        let mut input =
+            String::from("func @foo (i32 %a) i32 {\n%entry:\n    %v0 = add i32 %a, %a\n");
+        for i in 1..50_000 {
+            input.push_str(&format!("    %c{} = const i32 {}\n", i, i));
+            input.push_str(&format!("    %v{} = add i32 %v{}, %c{}\n", i, i - 1, i));
+        }
+        input.push_str("    ret i32 %v49999\n}\n");
+
+        let start = std::time::Instant::now();
+        let module = parse_module(&input).unwrap();
+        eprintln!("parsed 100k instructions in {:?}", start.elapsed());
+
+        // All values of type `i32` share a single allocation.
+        let unit = module.units().next().unwrap();
+        let types: std::collections::HashSet<_> = unit
+            .all_insts()
+            .filter_map(|inst| unit.get_inst_type(inst))
+            .map(|ty| &*ty as *const _)
+            .collect();
+        assert_eq!(types.len(), 1);
+    }
 }
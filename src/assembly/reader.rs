@@ -4,28 +4,328 @@
 
 use crate::{
     ir::{self, Opcode, Signature, UnitBuilder, UnitName},
-    ty::Type,
-    value::{IntValue, TimeValue},
+    ty::{Type, TypeContext},
+    value::{EnumValue, IntValue, TimeValue},
 };
+use lalrpop_util::ParseError;
 use num::{BigInt, BigRational};
-use std::collections::HashMap;
+use std::{borrow::Cow, cell::RefCell, collections::HashMap};
+
+/// State shared across the parsing of an entire module.
+pub struct ParseState {
+    /// Whether names consisting only of digits are kept as explicit names.
+    pub numeric_names: bool,
+    /// The largest width accepted for `iN` and `nN` types.
+    pub max_int_width: usize,
+    /// The deepest nesting of types accepted, such as structs in structs.
+    pub max_type_depth: usize,
+    /// Whether every block must end in a terminator.
+    pub require_terminators: bool,
+    /// Whether integer constants that do not fit their type are rejected.
+    pub check_int_ranges: bool,
+    /// The named types declared so far.
+    pub types: RefCell<HashMap<String, Type>>,
+    /// The types parsed so far, such that equal types share their storage.
+    pub interned: RefCell<TypeContext>,
+    /// The named constants declared so far.
+    pub consts: RefCell<HashMap<String, crate::Value>>,
+    /// The errors encountered while building units.
+    pub errors: RefCell<Vec<super::ParseError>>,
+    /// The names of the input and output ports of the processes and entities
+    /// parsed so far.
+    pub ports: RefCell<HashMap<UnitName, PortNames>>,
+    /// The instances with named ports that still need to be matched up with
+    /// the ports of the instantiated unit.
+    pub named_ports: RefCell<Vec<NamedPorts>>,
+    /// Whether comments are attached to the instructions they accompany.
+    pub preserve_comments: bool,
+    /// The offsets of the line breaks in the input, used to tell trailing
+    /// comments from comments on a line of their own.
+    pub newlines: Vec<usize>,
+}
+
+impl Default for ParseState {
+    fn default() -> Self {
+        Self::new(&Default::default())
+    }
+}
+
+impl ParseState {
+    /// Create the state for parsing with the given options.
+    pub fn new(options: &super::ParseOptions) -> Self {
+        Self {
+            numeric_names: options.numeric_names,
+            max_int_width: options.max_int_width,
+            max_type_depth: options.max_type_depth,
+            require_terminators: options.require_terminators,
+            check_int_ranges: options.check_int_ranges,
+            types: Default::default(),
+            interned: Default::default(),
+            consts: Default::default(),
+            errors: Default::default(),
+            ports: Default::default(),
+            named_ports: Default::default(),
+            preserve_comments: options.preserve_comments,
+            newlines: vec![],
+        }
+    }
+
+    /// Check that a type just built from its inner types is not nested too
+    /// deeply.
+    ///
+    /// Deeply nested types would overflow the stack when they are formatted,
+    /// compared, or dropped.
+    pub fn nest<L, T>(&self, ty: Type) -> Result<Type, ParseError<L, T, super::ParseError>> {
+        if type_depth(&ty) > self.max_type_depth {
+            Err(ParseError::User {
+                error: super::ParseError::Overflow("type exceeds the maximum nesting depth".into()),
+            })
+        } else {
+            Ok(self.intern(ty))
+        }
+    }
+
+    /// Share the storage of a type with all equal types parsed before.
+    pub fn intern(&self, ty: Type) -> Type {
+        self.interned.borrow_mut().intern(ty)
+    }
+
+    /// Create the context for building a unit body.
+    ///
+    /// The constants declared so far can be referred to within the body.
+    pub fn context<'a>(&self) -> Context<'a> {
+        Context {
+            consts: self.consts.borrow().clone(),
+            ..Default::default()
+        }
+    }
+
+    /// Record the results of building a unit.
+    ///
+    /// Keeps the errors and instances with named ports encountered in the
+    /// unit, as well as the names of its ports if it may be instantiated.
+    /// Checks that every block ends in a terminator if required, and interns
+    /// the types of the values in the unit.
+    pub fn finish_unit(&self, context: Context, unit: &mut UnitBuilder) {
+        self.errors.borrow_mut().extend(context.errors);
+        unit.intern_types(&mut self.interned.borrow_mut());
+        if self.require_terminators && !unit.is_entity() {
+            for bb in unit.blocks() {
+                let terminated = unit
+                    .last_inst(bb)
+                    .map(|inst| unit[inst].opcode().is_terminator())
+                    .unwrap_or(false);
+                if !terminated {
+                    self.errors
+                        .borrow_mut()
+                        .push(super::ParseError::Malformed(format!(
+                            "{} {}: block {} does not end in a terminator",
+                            unit.kind(),
+                            unit.name(),
+                            bb.dump(unit)
+                        )));
+                }
+            }
+        }
+        self.named_ports.borrow_mut().extend(context.named_ports);
+        if !unit.is_function() {
+            let name = |arg| unit.get_name(arg).map(String::from);
+            let ports = (
+                unit.input_args().map(name).collect(),
+                unit.output_args().map(name).collect(),
+            );
+            self.ports.borrow_mut().insert(unit.name().clone(), ports);
+        }
+    }
+
+    /// Convert a `%name` token into a local name.
+    pub fn local_name<'a>(&self, token: &'a str) -> LocalName<'a> {
+        if token[1..].starts_with('"') {
+            LocalName::Named(strip_quotes(&token[1..]))
+        } else if self.numeric_names {
+            LocalName::Named(&token[1..])
+        } else {
+            token[1..].into()
+        }
+    }
+
+    /// Attach comments to the instructions of a unit body.
+    ///
+    /// The first comment after an instruction is attached to it as a trailing
+    /// comment if it is on the same line. All other comments are attached to
+    /// the next instruction as leading comments, starting with the `head`
+    /// comments after the opening brace of the body. Comments after the last
+    /// instruction are dropped.
+    pub fn attach_comments(&self, head: &[(usize, &str)], items: &mut [BodyItem]) {
+        if !self.preserve_comments {
+            return;
+        }
+        let line = |offset| match self.newlines.binary_search(&offset) {
+            Ok(i) | Err(i) => i,
+        };
+        let text = |comment: &str| comment[1..].to_owned();
+        let mut pending: Vec<_> = head.iter().map(|&(_, c)| text(c)).collect();
+        for item in items {
+            match item {
+                BodyItem::Label(_, _, comments) => {
+                    pending.extend(comments.iter().map(|&(_, c)| text(c)));
+                }
+                BodyItem::Inst(inst) => {
+                    let mut attached = ir::Comments {
+                        leading: std::mem::take(&mut pending),
+                        trailing: None,
+                    };
+                    let mut comments = inst.comments.iter().peekable();
+                    if let (Some(&&(loc, c)), Some(end)) = (comments.peek(), inst.end) {
+                        if line(loc) == line(end) {
+                            attached.trailing = Some(text(c));
+                            comments.next();
+                        }
+                    }
+                    pending.extend(comments.map(|&(_, c)| text(c)));
+                    if attached != ir::Comments::default() {
+                        inst.attached_comments = Some(attached);
+                    }
+                }
+            }
+        }
+    }
+}
 
 #[derive(Default)]
 pub struct Context<'a> {
     pub value_names: HashMap<LocalName<'a>, ir::Value>,
     pub block_names: HashMap<LocalName<'a>, ir::Block>,
+    pub consts: HashMap<String, crate::Value>,
+    pub const_values: HashMap<Value<'a>, ir::Value>,
+    pub errors: Vec<super::ParseError>,
+    pub named_ports: Vec<NamedPorts>,
 }
 
 pub enum Unit {
     Data(ir::UnitData, usize),
     Declare(ir::UnitName, ir::Signature, usize),
+    Type(String, Type),
+    Const(String, crate::Value),
 }
 
 pub struct Block<'a> {
     pub name: LocalName<'a>,
+    pub params: Vec<(Type, LocalName<'a>)>,
     pub insts: Vec<Inst<'a>>,
 }
 
+pub enum BodyItem<'a> {
+    Label(
+        LocalName<'a>,
+        Vec<(Type, LocalName<'a>)>,
+        Vec<(usize, &'a str)>,
+    ),
+    Inst(Box<Inst<'a>>),
+}
+
+/// Build the blocks of a function or process.
+///
+/// Block parameters are sugar for phi nodes. Each parameter becomes a phi at
+/// the top of its block, with one edge for every branch that targets the
+/// block and passes a value for the parameter.
+pub fn build_blocks<'a>(
+    mut blocks: Vec<Block<'a>>,
+    builder: &mut UnitBuilder,
+    context: &mut Context<'a>,
+) {
+    let params: HashMap<LocalName, Vec<(Type, LocalName)>> = blocks
+        .iter()
+        .filter(|block| !block.params.is_empty())
+        .map(|block| (block.name, block.params.clone()))
+        .collect();
+    let mut edges: HashMap<LocalName, Vec<Vec<(TypedValue, Label)>>> = params
+        .iter()
+        .map(|(&name, params)| (name, params.iter().map(|_| vec![]).collect()))
+        .collect();
+    let mut checks = vec![];
+    for block in &mut blocks {
+        for inst in &mut block.insts {
+            let targets = match &mut inst.data {
+                InstData::Branch(_, bb0, bb1) => {
+                    let mut targets = vec![(bb0.0 .0, std::mem::take(&mut bb0.1))];
+                    targets.extend(bb1.as_mut().map(|bb| (bb.0 .0, std::mem::take(&mut bb.1))));
+                    targets
+                }
+                InstData::Wait(bb, ..) => vec![(bb.0, vec![])],
+                _ => continue,
+            };
+            for (target, args) in targets {
+                let target_params = params.get(&target).map(Vec::as_slice).unwrap_or(&[]);
+                if args.len() != target_params.len() {
+                    context.errors.push(super::ParseError::Malformed(format!(
+                        "{} {}: branch from {} to {} passes {} arguments, but the block has {} \
+                         parameters",
+                        builder.kind(),
+                        builder.name(),
+                        block.name,
+                        target,
+                        args.len(),
+                        target_params.len()
+                    )));
+                    continue;
+                }
+                for (i, (arg, (ty, param))) in args.into_iter().zip(target_params).enumerate() {
+                    checks.push((target, *param, ty.clone(), arg));
+                    edges.get_mut(&target).unwrap()[i]
+                        .push((arg.ty(ty.clone()), Label(block.name)));
+                }
+            }
+        }
+    }
+    for mut block in blocks {
+        let mut phis = vec![];
+        for ((ty, name), edges) in std::mem::take(&mut block.params)
+            .into_iter()
+            .zip(edges.remove(&block.name).unwrap_or_default())
+        {
+            if edges.is_empty() {
+                context.errors.push(super::ParseError::Malformed(format!(
+                    "{} {}: block {} has parameters, but no branches pass arguments to it",
+                    builder.kind(),
+                    builder.name(),
+                    block.name
+                )));
+                break;
+            }
+            phis.push(
+                Inst::new(Opcode::Phi)
+                    .name(name)
+                    .data(InstData::Phi(ty, edges)),
+            );
+        }
+        block.insts.splice(0..0, phis);
+        block.build(builder, context);
+    }
+    for (target, param, ty, arg) in checks {
+        let value = match arg {
+            Value::Local(name) => context.value_names.get(&name),
+            _ => context.const_values.get(&arg),
+        };
+        let actual = match value {
+            Some(&value) => builder.value_type(value),
+            None => continue,
+        };
+        if actual != ty {
+            context.errors.push(super::ParseError::TypeMismatch(format!(
+                "{} {}: branch to {} passes {} of type {} for parameter {} of type {}",
+                builder.kind(),
+                builder.name(),
+                target,
+                arg,
+                actual,
+                param,
+                ty
+            )));
+        }
+    }
+}
+
 impl<'a> Block<'a> {
     pub fn build(self, builder: &mut UnitBuilder, context: &mut Context<'a>) {
         let bb = match context.block_names.get(&self.name).cloned() {
@@ -38,7 +338,7 @@ impl<'a> Block<'a> {
         };
         match self.name {
             LocalName::Anonymous(index) => builder.set_anonymous_block_hint(bb, index),
-            LocalName::Named(name) => builder.set_block_name(bb, name.to_owned()),
+            LocalName::Named(name) => builder.set_block_name(bb, unescape_name(name).into_owned()),
         }
         builder.append_to(bb);
         for inst in self.insts {
@@ -51,12 +351,19 @@ pub struct Inst<'a> {
     pub opcode: Opcode,
     pub name: Option<LocalName<'a>>,
     pub data: InstData<'a>,
+    pub result_ty: Option<Type>,
     pub loc: Option<usize>,
+    pub end: Option<usize>,
+    pub comments: Vec<(usize, &'a str)>,
+    pub attached_comments: Option<ir::Comments>,
 }
 
 pub enum InstData<'a> {
     ConstInt(IntValue),
     ConstTime(TimeValue),
+    ConstEnum(EnumValue),
+    ConstUndef(Type),
+    ConstPoison(Type),
     Aggregate(usize, Vec<TypedValue<'a>>),
     Nullary,
     Unary(TypedValue<'a>),
@@ -79,10 +386,14 @@ pub enum InstData<'a> {
     ),
     Ins(TypedValue<'a>, TypedValue<'a>, [usize; 2]),
     Ext(Type, TypedValue<'a>, [usize; 2]),
-    Call(Type, UnitName, Vec<TypedValue<'a>>),
-    Inst(UnitName, Vec<TypedValue<'a>>, Vec<TypedValue<'a>>),
+    Call(Type, UnitName, Option<LocalName<'a>>, Vec<TypedValue<'a>>),
+    Inst(UnitName, Vec<Port<'a>>, Vec<Port<'a>>),
     Phi(Type, Vec<(TypedValue<'a>, Label<'a>)>),
-    Branch(Option<TypedValue<'a>>, Label<'a>, Option<Label<'a>>),
+    Branch(
+        Option<TypedValue<'a>>,
+        (Label<'a>, Vec<Value<'a>>),
+        Option<(Label<'a>, Vec<Value<'a>>)>,
+    ),
     Wait(Label<'a>, Option<TypedValue<'a>>, Vec<Value<'a>>),
 }
 
@@ -92,7 +403,21 @@ impl<'a> Inst<'a> {
             opcode,
             name: None,
             data: InstData::Nullary,
+            result_ty: None,
             loc: None,
+            end: None,
+            comments: vec![],
+            attached_comments: None,
+        }
+    }
+
+    /// Create a `const` instruction for an integer, time, or enum value.
+    pub fn constant(value: crate::Value) -> Self {
+        match value {
+            crate::Value::Int(v) => Self::new(Opcode::ConstInt).data(InstData::ConstInt(v)),
+            crate::Value::Time(v) => Self::new(Opcode::ConstTime).data(InstData::ConstTime(v)),
+            crate::Value::Enum(v) => Self::new(Opcode::ConstEnum).data(InstData::ConstEnum(v)),
+            v => unreachable!("constant {}", v),
         }
     }
 
@@ -102,12 +427,30 @@ impl<'a> Inst<'a> {
         x
     }
 
+    pub fn result_type(self, ty: Type) -> Self {
+        let mut x = self;
+        x.result_ty = Some(ty);
+        x
+    }
+
     pub fn location(self, loc: usize) -> Self {
         let mut x = self;
         x.loc = Some(loc);
         x
     }
 
+    pub fn span(self, loc: usize, end: usize) -> Self {
+        let mut x = self.location(loc);
+        x.end = Some(end);
+        x
+    }
+
+    pub fn comments(self, comments: Vec<(usize, &'a str)>) -> Self {
+        let mut x = self;
+        x.comments = comments;
+        x
+    }
+
     pub fn data(self, data: InstData<'a>) -> Self {
         let mut x = self;
         x.data = data;
@@ -118,6 +461,9 @@ impl<'a> Inst<'a> {
         let result: InstOrValue = match self.data {
             InstData::ConstInt(imm) => builder.ins().const_int(imm).into(),
             InstData::ConstTime(imm) => builder.ins().const_time(imm).into(),
+            InstData::ConstEnum(imm) => builder.ins().const_enum(imm).into(),
+            InstData::ConstUndef(ty) => builder.ins().const_undef(ty).into(),
+            InstData::ConstPoison(ty) => builder.ins().const_poison(ty).into(),
             InstData::Aggregate(size, args) => {
                 let args = args
                     .into_iter()
@@ -136,7 +482,35 @@ impl<'a> Inst<'a> {
                 x => unreachable!("nullary {:?}", x),
             },
             InstData::Unary(arg) => {
-                let arg = arg.build(builder, context);
+                let name = arg.value;
+                let annotated = arg.ty.clone();
+                let mut arg = arg.build(builder, context);
+                if self.opcode == Opcode::Sig {
+                    let ty = builder.value_type(arg);
+                    if ty != annotated {
+                        context.errors.push(super::ParseError::TypeMismatch(format!(
+                            "{} {}: signal of type {} initialized with {} of type {}",
+                            builder.kind(),
+                            builder.name(),
+                            annotated,
+                            name,
+                            ty
+                        )));
+                        arg = builder.add_placeholder(annotated);
+                    }
+                }
+                if self.opcode == Opcode::Prb {
+                    let ty = builder.value_type(arg);
+                    if !ty.is_signal() {
+                        context.errors.push(super::ParseError::TypeMismatch(format!(
+                            "{} {}: {} probed, but is not a signal",
+                            builder.kind(),
+                            builder.name(),
+                            name
+                        )));
+                        arg = builder.add_placeholder(crate::signal_ty(ty));
+                    }
+                }
                 match self.opcode {
                     Opcode::Alias => builder.ins().alias(arg).into(),
                     Opcode::Not => builder.ins().not(arg).into(),
@@ -166,6 +540,10 @@ impl<'a> Inst<'a> {
                     Opcode::Udiv => builder.ins().udiv(arg0, arg1).into(),
                     Opcode::Umod => builder.ins().umod(arg0, arg1).into(),
                     Opcode::Urem => builder.ins().urem(arg0, arg1).into(),
+                    Opcode::Uaddo => builder.ins().uaddo(arg0, arg1).into(),
+                    Opcode::Saddo => builder.ins().saddo(arg0, arg1).into(),
+                    Opcode::Usubo => builder.ins().usubo(arg0, arg1).into(),
+                    Opcode::Ssubo => builder.ins().ssubo(arg0, arg1).into(),
                     Opcode::Eq => builder.ins().eq(arg0, arg1).into(),
                     Opcode::Neq => builder.ins().neq(arg0, arg1).into(),
                     Opcode::Slt => builder.ins().slt(arg0, arg1).into(),
@@ -183,19 +561,39 @@ impl<'a> Inst<'a> {
                 }
             }
             InstData::Ternary(arg0, arg1, arg2) => {
-                let arg0 = arg0.build(builder, context);
+                let name = arg0.value;
+                let mut arg0 = arg0.build(builder, context);
+                if self.opcode == Opcode::Drv {
+                    check_driven(builder, context, name, arg0);
+                }
+                if self.opcode == Opcode::Sel {
+                    let ty = builder.value_type(arg0);
+                    if ty != crate::int_ty(1) {
+                        context.errors.push(super::ParseError::TypeMismatch(format!(
+                            "{} {}: sel condition {} must be i1, but is {}",
+                            builder.kind(),
+                            builder.name(),
+                            name,
+                            ty
+                        )));
+                        arg0 = builder.add_placeholder(crate::int_ty(1));
+                    }
+                }
                 let arg1 = arg1.build(builder, context);
                 let arg2 = arg2.build(builder, context);
                 match self.opcode {
                     Opcode::Drv => builder.ins().drv(arg0, arg1, arg2).into(),
                     Opcode::Shl => builder.ins().shl(arg0, arg1, arg2).into(),
                     Opcode::Shr => builder.ins().shr(arg0, arg1, arg2).into(),
+                    Opcode::Sel => builder.ins().sel(arg0, arg1, arg2).into(),
                     Opcode::Del => builder.ins().del(arg0, arg1, arg2).into(),
                     x => unreachable!("ternary {:?}", x),
                 }
             }
             InstData::Quaternary(arg0, arg1, arg2, arg3) => {
+                let name = arg0.value;
                 let arg0 = arg0.build(builder, context);
+                check_driven(builder, context, name, arg0);
                 let arg1 = arg1.build(builder, context);
                 let arg2 = arg2.build(builder, context);
                 let arg3 = arg3.build(builder, context);
@@ -239,37 +637,81 @@ impl<'a> Inst<'a> {
                 assert_eq!(builder.value_type(ins), ty);
                 ins.into()
             }
-            InstData::Call(ty, unit, args) => {
-                let mut sig = Signature::new();
-                sig.set_return_type(ty);
-                for arg in &args {
-                    sig.add_input(arg.ty.clone());
+            InstData::Call(ty, unit, callee, args) => {
+                // Call through a value if the callee names one.
+                let callee = callee.and_then(|c| Some((c, *context.value_names.get(&c)?)));
+                let mut indirect = None;
+                if let Some((name, value)) = callee {
+                    let callee_ty = builder.value_type(value);
+                    if callee_ty.is_func() && *callee_ty.unwrap_func().1 == ty {
+                        indirect = Some(value);
+                    } else {
+                        context.errors.push(super::ParseError::TypeMismatch(format!(
+                            "{} {}: {} called as {}, but is of type {}",
+                            builder.kind(),
+                            builder.name(),
+                            name,
+                            crate::func_ty(args.iter().map(|a| a.ty.clone()).collect(), ty.clone()),
+                            callee_ty
+                        )));
+                    }
+                }
+                let inst = match indirect {
+                    Some(value) => {
+                        let args = args
+                            .into_iter()
+                            .map(|v| v.build(builder, context))
+                            .collect();
+                        builder.ins().call_indirect(value, args)
+                    }
+                    None => {
+                        let mut sig = Signature::new();
+                        sig.set_return_type(ty);
+                        for arg in &args {
+                            sig.add_input(arg.ty.clone());
+                        }
+                        let ext = builder.add_extern(unit, sig);
+                        let args = args
+                            .into_iter()
+                            .map(|v| v.build(builder, context))
+                            .collect();
+                        builder.ins().call(ext, args)
+                    }
+                };
+                match builder.get_inst_result(inst) {
+                    Some(value) => value.into(),
+                    None => inst.into(),
                 }
-                let ext = builder.add_extern(unit, sig);
-                let args = args
-                    .into_iter()
-                    .map(|v| v.build(builder, context))
-                    .collect();
-                builder.ins().call(ext, args).into()
             }
             InstData::Inst(unit, input_args, output_args) => {
+                let inputs = port_names(builder, context, &unit, &input_args);
+                let outputs = port_names(builder, context, &unit, &output_args);
                 let mut sig = Signature::new();
-                for arg in &input_args {
+                for (_, arg) in &input_args {
                     sig.add_input(arg.ty.clone());
                 }
-                for arg in &output_args {
+                for (_, arg) in &output_args {
                     sig.add_output(arg.ty.clone());
                 }
                 let ext = builder.add_extern(unit, sig);
                 let input_args = input_args
                     .into_iter()
-                    .map(|v| v.build(builder, context))
+                    .map(|(_, v)| v.build(builder, context))
                     .collect();
                 let output_args = output_args
                     .into_iter()
-                    .map(|v| v.build(builder, context))
+                    .map(|(_, v)| v.build(builder, context))
                     .collect();
-                builder.ins().inst(ext, input_args, output_args).into()
+                let inst = builder.ins().inst(ext, input_args, output_args);
+                if !inputs.is_empty() || !outputs.is_empty() {
+                    context.named_ports.push(NamedPorts {
+                        unit: builder.name().clone(),
+                        inst,
+                        inputs,
+                        outputs,
+                    });
+                }
+                inst.into()
             }
             InstData::Phi(_, edges) => {
                 let mut args = vec![];
@@ -281,12 +723,12 @@ impl<'a> Inst<'a> {
                 builder.ins().phi(args, bbs).into()
             }
             InstData::Branch(cond, bb0, bb1) => {
-                let bb0 = bb0.build(builder, context);
+                let bb0 = bb0.0.build(builder, context);
                 match self.opcode {
                     Opcode::Br => builder.ins().br(bb0).into(),
                     Opcode::BrCond => {
                         let cond = cond.unwrap().build(builder, context);
-                        let bb1 = bb1.unwrap().build(builder, context);
+                        let bb1 = bb1.unwrap().0.build(builder, context);
                         builder.ins().br_cond(cond, bb0, bb1).into()
                     }
                     x => unreachable!("branch {:?}", x),
@@ -308,9 +750,39 @@ impl<'a> Inst<'a> {
                 }
             }
         };
-        if let (Some(name), InstOrValue::Value(value)) = (self.name, result) {
+        if let (Some(name), Some(ty), InstOrValue::Value(value)) =
+            (self.name, &self.result_ty, result)
+        {
+            let actual = builder.value_type(value);
+            if &actual != ty {
+                context.errors.push(super::ParseError::TypeMismatch(format!(
+                    "{} {}: {} annotated as {}, but has type {}",
+                    builder.kind(),
+                    builder.name(),
+                    name,
+                    ty,
+                    actual
+                )));
+            }
+        }
+        let name = self.name.filter(|name| !name.is_discard());
+        if let (Some(name), InstOrValue::Value(value)) = (name, result) {
             if let Some(ph) = context.value_names.insert(name, value) {
                 if builder.is_placeholder(ph) {
+                    // Uses earlier in the same block are ordering errors,
+                    // unless they are phi nodes looping back to the block.
+                    let bb = builder.inst_block(builder.value_inst(value));
+                    let early = builder.uses(ph).iter().any(|&inst| {
+                        builder[inst].opcode() != Opcode::Phi && builder.inst_block(inst) == bb
+                    });
+                    if early {
+                        context.errors.push(super::ParseError::Malformed(format!(
+                            "{} {}: {} used before its definition",
+                            builder.kind(),
+                            builder.name(),
+                            name
+                        )));
+                    }
                     builder.replace_use(ph, value);
                     builder.remove_placeholder(ph);
                 } else {
@@ -319,15 +791,21 @@ impl<'a> Inst<'a> {
             }
             match name {
                 LocalName::Anonymous(index) => builder.set_anonymous_hint(value, index),
-                LocalName::Named(name) => builder.set_name(value, name.to_owned()),
+                LocalName::Named(name) => builder.set_name(value, unescape_name(name).into_owned()),
             }
         }
+        let inst = match result {
+            InstOrValue::Inst(inst) => inst,
+            InstOrValue::Value(value) => builder.value_inst(value),
+        };
         if let Some(loc) = self.loc {
-            let inst = match result {
-                InstOrValue::Inst(inst) => inst,
-                InstOrValue::Value(value) => builder.value_inst(value),
-            };
             builder.set_location_hint(inst, loc);
+            if let Some(end) = self.end {
+                builder.set_span_hint(inst, ir::Span { start: loc, end });
+            }
+        }
+        if let Some(comments) = self.attached_comments {
+            builder.set_comments(inst, comments);
         }
     }
 }
@@ -350,6 +828,205 @@ impl From<ir::Value> for InstOrValue {
     }
 }
 
+/// Collect the names of the ports connected by an instance.
+///
+/// Returns an empty list if the ports are connected by position. Reports an
+/// error if named and positional ports are mixed, or if a port is connected
+/// multiple times.
+fn port_names(
+    builder: &UnitBuilder,
+    context: &mut Context,
+    target: &UnitName,
+    ports: &[Port],
+) -> Vec<String> {
+    if ports.iter().all(|(name, _)| name.is_none()) {
+        return vec![];
+    }
+    let mut names = vec![];
+    for (name, _) in ports {
+        let name = match name {
+            Some(name) => name.to_string(),
+            None => {
+                context.errors.push(super::ParseError::Malformed(format!(
+                    "{} {}: inst {} mixes named and positional ports",
+                    builder.kind(),
+                    builder.name(),
+                    target
+                )));
+                return vec![];
+            }
+        };
+        if names.contains(&name) {
+            context.errors.push(super::ParseError::Malformed(format!(
+                "{} {}: port .{} of {} connected multiple times",
+                builder.kind(),
+                builder.name(),
+                name,
+                target
+            )));
+            return vec![];
+        }
+        names.push(name);
+    }
+    names
+}
+
+/// Reorder the arguments of an instance with named ports to match the order
+/// of the ports of the instantiated unit.
+///
+/// Reports an error if the instantiated unit has not been parsed, if it has no
+/// port of a given name, or if one of its ports is not connected.
+pub fn resolve_named_ports(
+    builder: &mut UnitBuilder,
+    named: &NamedPorts,
+    ports: &HashMap<UnitName, PortNames>,
+) -> Result<(), super::ParseError> {
+    let ext = builder[named.inst].get_ext_unit().unwrap();
+    let target = builder[ext].name.clone();
+    let (inputs, outputs) = ports.get(&target).ok_or_else(|| {
+        super::ParseError::UndeclaredName(format!(
+            "{} {}: named ports require {} to be defined in the module",
+            builder.kind(),
+            builder.name(),
+            target
+        ))
+    })?;
+    let reorder = |names: &[String], args: &[ir::Value], ports: &[Option<String>]| {
+        if names.is_empty() {
+            return Ok(args.to_vec());
+        }
+        if let Some(name) = names
+            .iter()
+            .find(|&name| !ports.contains(&Some(name.clone())))
+        {
+            return Err(super::ParseError::UndeclaredName(format!(
+                "{} {}: {} has no port .{}",
+                builder.kind(),
+                builder.name(),
+                target,
+                name
+            )));
+        }
+        ports
+            .iter()
+            .map(
+                |port| match names.iter().position(|name| port.as_ref() == Some(name)) {
+                    Some(index) => Ok(args[index]),
+                    None => Err(super::ParseError::Malformed(format!(
+                        "{} {}: port .{} of {} is not connected",
+                        builder.kind(),
+                        builder.name(),
+                        port.as_deref().unwrap_or("?"),
+                        target
+                    ))),
+                },
+            )
+            .collect()
+    };
+    let data = &builder[named.inst];
+    let inputs = reorder(&named.inputs, data.input_args(), inputs)?;
+    let outputs = reorder(&named.outputs, data.output_args(), outputs)?;
+
+    let mut sig = Signature::new();
+    for &arg in &inputs {
+        sig.add_input(builder.value_type(arg));
+    }
+    for &arg in &outputs {
+        sig.add_output(builder.value_type(arg));
+    }
+    builder[ext].sig = sig;
+    builder[named.inst] = ir::InstData::Call {
+        opcode: Opcode::Inst,
+        unit: ext,
+        ins: inputs.len() as u16,
+        args: inputs.into_iter().chain(outputs).collect(),
+    };
+    Ok(())
+}
+
+/// Report an error if the target of a `drv` is not a signal.
+fn check_driven(builder: &UnitBuilder, context: &mut Context, name: Value, target: ir::Value) {
+    if !builder.value_type(target).is_signal() {
+        context.errors.push(super::ParseError::TypeMismatch(format!(
+            "{} {}: {} driven, but is not a signal",
+            builder.kind(),
+            builder.name(),
+            name
+        )));
+    }
+}
+
+/// Determine how deeply a type is nested.
+///
+/// Only the types built by `ParseState::nest` are passed in, so the recursion
+/// is bounded by the maximum depth.
+fn type_depth(ty: &Type) -> usize {
+    use crate::ty::TypeKind::*;
+    match **ty {
+        PointerType(ref ty) | SignalType(ref ty) | ArrayType(_, ref ty) => 1 + type_depth(ty),
+        StructType(ref tys) => 1 + tys.iter().map(type_depth).max().unwrap_or(0),
+        FuncType(ref args, ref ret) => {
+            1 + args.iter().chain(Some(ret)).map(type_depth).max().unwrap()
+        }
+        _ => 0,
+    }
+}
+
+/// Convert a `@name` or `%name` token into a unit name.
+pub fn unit_name(token: &str) -> UnitName {
+    let (first, tail) = (&token[0..1], &token[1..]);
+    let all_digits = tail.chars().all(|c| c.is_ascii_digit());
+    match first {
+        "@" => UnitName::global(unescape_name(tail)),
+        "%" if all_digits => UnitName::anonymous(tail.parse().unwrap()),
+        "%" => UnitName::local(unescape_name(tail)),
+        _ => unreachable!("regex should not match names starting with `{}`", first),
+    }
+}
+
+/// Strip the quotes from a quoted name such as `"foo$bar"`.
+///
+/// The name is returned without quotes if it contains no escape sequences,
+/// such that it matches the same name written without quotes. Otherwise it is
+/// returned as is, to be resolved by `unescape_name` once it is stored.
+pub fn strip_quotes(name: &str) -> &str {
+    if name.starts_with('"') && !name.contains('\\') {
+        &name[1..name.len() - 1]
+    } else {
+        name
+    }
+}
+
+/// Resolve a quoted name such as `"foo\"bar"` to the name it represents.
+///
+/// Names that are not quoted are returned unchanged.
+pub fn unescape_name(name: &str) -> Cow<'_, str> {
+    if !name.starts_with('"') {
+        return name.into();
+    }
+    let mut unescaped = String::with_capacity(name.len());
+    let mut chars = name[1..name.len() - 1].chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => unescaped.extend(chars.next()),
+            c => unescaped.push(c),
+        }
+    }
+    unescaped.into()
+}
+
+/// Determine the type of the value driven onto a signal of type `ty`.
+///
+/// Falls back to `ty` itself if it is not a signal, which is reported once the
+/// instruction is built.
+pub fn driven_type(ty: &Type) -> Type {
+    if ty.is_signal() {
+        ty.unwrap_signal().clone()
+    } else {
+        ty.clone()
+    }
+}
+
 impl std::fmt::Display for InstOrValue {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match self {
@@ -359,6 +1036,24 @@ impl std::fmt::Display for InstOrValue {
     }
 }
 
+/// A port connection of an instance, optionally with the port name.
+pub type Port<'a> = (Option<&'a str>, TypedValue<'a>);
+
+/// The names of the input and output ports of a unit.
+pub type PortNames = (Vec<Option<String>>, Vec<Option<String>>);
+
+/// An instance whose ports are connected by name.
+pub struct NamedPorts {
+    /// The unit containing the instance.
+    pub unit: UnitName,
+    /// The instance.
+    pub inst: ir::Inst,
+    /// The names of the connected input ports, in the order of the arguments.
+    pub inputs: Vec<String>,
+    /// The names of the connected output ports, in the order of the arguments.
+    pub outputs: Vec<String>,
+}
+
 /// A local name such as `%0` or `%foo`.
 #[derive(Copy, Clone, PartialEq, Eq, Hash)]
 pub enum LocalName<'a> {
@@ -366,6 +1061,13 @@ pub enum LocalName<'a> {
     Named(&'a str),
 }
 
+impl LocalName<'_> {
+    /// Check whether this is the `%_` placeholder, which discards a result.
+    pub fn is_discard(&self) -> bool {
+        *self == LocalName::Named("_")
+    }
+}
+
 impl<'a> From<&'a str> for LocalName<'a> {
     fn from(name: &'a str) -> Self {
         if name.chars().all(|c| c.is_digit(10)) {
@@ -386,7 +1088,15 @@ impl std::fmt::Display for LocalName<'_> {
 }
 
 /// A value without explicit type.
-pub struct Value<'a>(pub LocalName<'a>);
+#[derive(Copy, Clone, PartialEq, Eq, Hash)]
+pub enum Value<'a> {
+    /// A local value such as `%0` or `%foo`.
+    Local(LocalName<'a>),
+    /// A named constant such as `@foo`.
+    Const(&'a str),
+    /// A boolean literal `true` or `false`.
+    Bool(bool),
+}
 
 impl<'a> Value<'a> {
     /// Associate a type with this value.
@@ -397,19 +1107,101 @@ impl<'a> Value<'a> {
 
 impl std::fmt::Display for Value<'_> {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        write!(f, "{}", self.0)
+        match self {
+            Value::Local(name) => write!(f, "{}", name),
+            Value::Const(name) => write!(f, "@{}", name),
+            Value::Bool(value) => write!(f, "{}", value),
+        }
     }
 }
 
-impl Value<'_> {
-    fn build(self, _builder: &mut UnitBuilder, context: &mut Context) -> ir::Value {
-        match context.value_names.get(&self.0) {
-            Some(&v) => v,
-            None => panic!("value {} has not been declared", self),
+impl<'a> Value<'a> {
+    fn build(self, builder: &mut UnitBuilder, context: &mut Context<'a>) -> ir::Value {
+        match self {
+            Value::Local(name) if name.is_discard() => build_discard(None, builder, context),
+            Value::Local(name) => match context.value_names.get(&name) {
+                Some(&v) => v,
+                None => panic!("value {} has not been declared", self),
+            },
+            Value::Const(_) => build_const(self, builder, context),
+            Value::Bool(_) => build_bool(self, None, builder, context),
         }
     }
 }
 
+/// Materialize a boolean literal in a unit.
+///
+/// Reports an error if the literal is used where a type other than `i1` is
+/// expected, or where no type is known.
+fn build_bool<'a>(
+    value: Value<'a>,
+    ty: Option<Type>,
+    builder: &mut UnitBuilder,
+    context: &mut Context<'a>,
+) -> ir::Value {
+    match ty {
+        Some(ty) if ty == crate::int_ty(1) => build_const(value, builder, context),
+        ty => {
+            context.errors.push(super::ParseError::TypeMismatch(format!(
+                "{} {}: {} can only be used where an i1 is expected",
+                builder.kind(),
+                builder.name(),
+                value
+            )));
+            builder.add_placeholder(ty.unwrap_or_else(|| crate::int_ty(1)))
+        }
+    }
+}
+
+/// Report a use of the `%_` placeholder, which never names a value.
+fn build_discard(ty: Option<Type>, builder: &mut UnitBuilder, context: &mut Context) -> ir::Value {
+    context.errors.push(super::ParseError::Malformed(format!(
+        "{} {}: %_ discards a result and cannot be used as a value",
+        builder.kind(),
+        builder.name(),
+    )));
+    builder.add_placeholder(ty.unwrap_or_else(|| crate::int_ty(1)))
+}
+
+/// Materialize a named constant or boolean literal in a unit.
+///
+/// The constant is defined once at the beginning of the entry block, such that
+/// it dominates all uses.
+fn build_const<'a>(
+    literal: Value<'a>,
+    builder: &mut UnitBuilder,
+    context: &mut Context<'a>,
+) -> ir::Value {
+    if let Some(&value) = context.const_values.get(&literal) {
+        return value;
+    }
+    let constant = match literal {
+        Value::Const(name) => context.consts[&*unescape_name(name)].clone(),
+        Value::Bool(value) => IntValue::from_usize(1, value as usize).into(),
+        Value::Local(name) => unreachable!("local value {}", name),
+    };
+    let value = match constant {
+        crate::Value::Int(v) => builder.ins().const_int(v),
+        crate::Value::Time(v) => builder.ins().const_time(v),
+        crate::Value::Enum(v) => builder.ins().const_enum(v),
+        v => unreachable!("constant {}", v),
+    };
+    // Keep the constants at the beginning of the entry block in the order
+    // they are first used.
+    let inst = builder.value_inst(value);
+    let entry = builder.entry();
+    builder.remove_inst(inst);
+    match context.const_values.len() {
+        0 => builder.prepend_inst(inst, entry),
+        n => {
+            let last = builder.insts(entry).nth(n - 1).unwrap();
+            builder.insert_inst_after(inst, last);
+        }
+    }
+    context.const_values.insert(literal, value);
+    value
+}
+
 /// A value with explicit type.
 pub struct TypedValue<'a> {
     pub value: Value<'a>,
@@ -418,7 +1210,15 @@ pub struct TypedValue<'a> {
 
 impl<'a> TypedValue<'a> {
     fn build(self, builder: &mut UnitBuilder, context: &mut Context<'a>) -> ir::Value {
-        match context.value_names.get(&self.value.0).cloned() {
+        let name = match self.value {
+            Value::Local(name) if name.is_discard() => {
+                return build_discard(Some(self.ty), builder, context)
+            }
+            Value::Local(name) => name,
+            Value::Const(_) => return build_const(self.value, builder, context),
+            Value::Bool(_) => return build_bool(self.value, Some(self.ty), builder, context),
+        };
+        match context.value_names.get(&name).cloned() {
             Some(v) => {
                 // assert_eq!(builder.value_type(v), self.ty, "type mismatch");
                 // The above will be caught by the verifier in a more gentle way
@@ -426,7 +1226,7 @@ impl<'a> TypedValue<'a> {
             }
             None => {
                 let value = builder.add_placeholder(self.ty);
-                context.value_names.insert(self.value.0, value);
+                context.value_names.insert(name, value);
                 value
             }
         }
@@ -461,6 +1261,325 @@ impl<'a> Label<'a> {
     }
 }
 
+/// What a character of the input is part of.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Lexeme {
+    /// Anything that is not a comment.
+    Code,
+    /// A `; ...` comment, up to but excluding the line break.
+    LineComment,
+    /// A `/* ... */` comment, including the delimiters.
+    BlockComment,
+    /// A quoted name such as `%"a b"`, including the quotes.
+    Quoted,
+}
+
+/// An iterator over the characters of the input that tells comments apart
+/// from the surrounding code.
+///
+/// Block comments may span multiple lines and may be nested. A `/*` within a
+/// line comment, or a `;` within a block comment, has no special meaning.
+/// Neither has anything within the quotes of a quoted name, where `\"` and
+/// `\\` are escaped characters.
+struct Scanner<'a> {
+    chars: std::iter::Peekable<std::str::CharIndices<'a>>,
+    state: Lexeme,
+    /// The nesting depth of block comments.
+    depth: usize,
+    /// The offset of the outermost block comment opened last.
+    comment_start: usize,
+    /// Whether the next character is the second half of a `/*` or `*/`.
+    skip: bool,
+    /// Whether the next character is escaped by a `\\` in a quoted name.
+    escape: bool,
+}
+
+impl<'a> Scanner<'a> {
+    fn new(input: &'a str) -> Self {
+        Self {
+            chars: input.char_indices().peekable(),
+            state: Lexeme::Code,
+            depth: 0,
+            comment_start: 0,
+            skip: false,
+            escape: false,
+        }
+    }
+
+    /// Iterate over the characters of the input that are not in a comment or
+    /// a quoted name.
+    fn code(input: &'a str) -> impl Iterator<Item = (usize, char)> + 'a {
+        Scanner::new(input)
+            .filter(|&(_, _, lexeme)| lexeme == Lexeme::Code)
+            .map(|(i, c, _)| (i, c))
+    }
+
+    /// The offset of the block comment that is still open, if any.
+    fn unterminated_comment(&self) -> Option<usize> {
+        if self.depth > 0 {
+            Some(self.comment_start)
+        } else {
+            None
+        }
+    }
+}
+
+impl Iterator for Scanner<'_> {
+    type Item = (usize, char, Lexeme);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (i, c) = self.chars.next()?;
+        if self.skip {
+            let lexeme = self.state;
+            self.skip = false;
+            if self.depth == 0 {
+                self.state = Lexeme::Code;
+            }
+            return Some((i, c, lexeme));
+        }
+        if self.state == Lexeme::Quoted {
+            match c {
+                _ if self.escape => self.escape = false,
+                '\\' => self.escape = true,
+                '"' => self.state = Lexeme::Code,
+                _ => (),
+            }
+            return Some((i, c, Lexeme::Quoted));
+        }
+        let next = self.chars.peek().map(|&(_, c)| c);
+        match (self.state, c, next) {
+            (Lexeme::Code, ';', _) => self.state = Lexeme::LineComment,
+            (Lexeme::Code, '"', _) => self.state = Lexeme::Quoted,
+            (Lexeme::Code, '/', Some('*')) => {
+                self.state = Lexeme::BlockComment;
+                self.depth = 1;
+                self.comment_start = i;
+                self.skip = true;
+            }
+            (Lexeme::LineComment, '\n', _) => self.state = Lexeme::Code,
+            (Lexeme::BlockComment, '/', Some('*')) => {
+                self.depth += 1;
+                self.skip = true;
+            }
+            (Lexeme::BlockComment, '*', Some('/')) => {
+                self.depth -= 1;
+                self.skip = true;
+            }
+            _ => (),
+        }
+        Some((i, c, self.state))
+    }
+}
+
+/// Blank out all `/* ... */` block comments in the input.
+///
+/// Comments may span multiple lines and may be nested. They are replaced with
+/// spaces, keeping the line breaks, such that byte offsets into the input
+/// remain valid. A `/*` within a quoted name is kept as is.
+pub fn strip_block_comments(input: &str) -> Result<Cow<'_, str>, super::ParseError> {
+    if !input.contains("/*") {
+        return Ok(Cow::Borrowed(input));
+    }
+    let mut output = String::with_capacity(input.len());
+    let mut scanner = Scanner::new(input);
+    for (_, c, lexeme) in &mut scanner {
+        match (lexeme, c) {
+            (Lexeme::BlockComment, '\n') => output.push('\n'),
+            (Lexeme::BlockComment, _) => output.extend(std::iter::repeat(' ').take(c.len_utf8())),
+            _ => output.push(c),
+        }
+    }
+    if let Some(start) = scanner.unterminated_comment() {
+        return Err(super::ParseError::Malformed(format!(
+            "unterminated block comment at offset {}",
+            start
+        )));
+    }
+    Ok(Cow::Owned(output))
+}
+
+/// The text produced by `expand_repeats`.
+pub struct Expanded<'a> {
+    /// The input with all `repeat` blocks unrolled.
+    pub text: Cow<'a, str>,
+    /// The offset in the text at which each piece starts, along with the offset
+    /// in the input that the piece was copied from.
+    pieces: Vec<(usize, usize)>,
+}
+
+impl Expanded<'_> {
+    /// Map an offset in the expanded text back to the input.
+    ///
+    /// Offsets within an unrolled `repeat` block map to the body of the block,
+    /// regardless of the iteration.
+    pub fn source_offset(&self, offset: usize) -> usize {
+        match self.pieces.partition_point(|&(at, _)| at <= offset) {
+            0 => offset,
+            i => {
+                let (at, from) = self.pieces[i - 1];
+                from + (offset - at)
+            }
+        }
+    }
+
+    /// Take ownership of the expanded text.
+    pub fn into_owned(self) -> Expanded<'static> {
+        Expanded {
+            text: Cow::Owned(self.text.into_owned()),
+            pieces: self.pieces,
+        }
+    }
+
+    /// Append a piece of text copied from the given offset in the input.
+    fn push(&mut self, text: &str, from: usize) {
+        if !text.is_empty() {
+            self.pieces.push((self.text.len(), from));
+            self.text.to_mut().push_str(text);
+        }
+    }
+}
+
+/// Expand all `repeat N { ... }` blocks in the input.
+///
+/// The instructions within the braces are emitted `N` times, with every
+/// occurrence of `$i` replaced by the iteration index. Blocks may not be
+/// nested. The result remembers where each part of the text came from, such
+/// that errors can be reported at an offset into the input.
+pub fn expand_repeats(input: &str) -> Result<Expanded<'_>, super::ParseError> {
+    let mut output = Expanded {
+        text: Cow::Owned(String::new()),
+        pieces: vec![],
+    };
+    let mut rest = input;
+    let mut consumed = 0;
+    while let Some(offset) = find_repeat(rest) {
+        output.push(&rest[..offset], consumed);
+        let after = &rest[offset + "repeat".len()..];
+        let count_str = after.trim_start();
+        let digits = count_str
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(count_str.len());
+        let count: usize = count_str[..digits].parse().map_err(|_| {
+            super::ParseError::Malformed(format!(
+                "expected repetition count after `repeat` at offset {}",
+                consumed + offset
+            ))
+        })?;
+        let body_str = count_str[digits..].trim_start();
+        if !body_str.starts_with('{') {
+            return Err(super::ParseError::Malformed(format!(
+                "expected `{{` after `repeat {}` at offset {}",
+                count,
+                consumed + offset
+            )));
+        }
+        let body_len = match find_closing_brace(&body_str[1..]) {
+            Some(len) => len,
+            None => {
+                return Err(super::ParseError::Malformed(format!(
+                    "unterminated `repeat` block at offset {}",
+                    consumed + offset
+                )))
+            }
+        };
+        let body = &body_str[1..1 + body_len];
+        if find_repeat(body).is_some() {
+            return Err(super::ParseError::Malformed(format!(
+                "nested `repeat` blocks are not supported at offset {}",
+                consumed + offset
+            )));
+        }
+        let body_start = input.len() - body_str.len() + 1;
+        for i in 0..count {
+            let index = i.to_string();
+            let mut from = body_start;
+            for (j, part) in body.split("$i").enumerate() {
+                if j > 0 {
+                    output.push(&index, from);
+                    from += 2;
+                }
+                output.push(part, from);
+                from += part.len();
+            }
+            output.push("\n", body_start + body_len);
+        }
+        let next = body_start + body_len + 1;
+        rest = &input[next..];
+        consumed = next;
+    }
+    if consumed == 0 {
+        output.text = Cow::Borrowed(input);
+    } else {
+        output.push(rest, consumed);
+    }
+    Ok(output)
+}
+
+/// Find the offset of the next `repeat` keyword outside of comments and quoted
+/// names.
+fn find_repeat(input: &str) -> Option<usize> {
+    let mut prev = None;
+    for (i, c) in Scanner::code(input) {
+        if !prev.map(is_name_char).unwrap_or(false) && starts_with_keyword(&input[i..], "repeat") {
+            return Some(i);
+        }
+        prev = Some(c);
+    }
+    None
+}
+
+/// Find the offset of the `}` that closes an already opened brace.
+fn find_closing_brace(input: &str) -> Option<usize> {
+    let mut depth = 0;
+    for (i, c) in Scanner::code(input) {
+        match c {
+            '{' => depth += 1,
+            '}' if depth == 0 => return Some(i),
+            '}' => depth -= 1,
+            _ => (),
+        }
+    }
+    None
+}
+
+/// Find the offset of the next keyword that starts a unit, declaration, or
+/// type definition outside of comments, quoted names, and braces.
+pub fn find_item(input: &str) -> Option<usize> {
+    let mut depth = 0;
+    let mut prev = None;
+    for (i, c) in Scanner::code(input) {
+        match c {
+            '{' => depth += 1,
+            '}' if depth > 0 => depth -= 1,
+            _ if depth == 0 && !prev.map(is_name_char).unwrap_or(false) => {
+                let is_keyword = ["func", "proc", "entity", "declare", "type", "const"]
+                    .iter()
+                    .any(|kw| starts_with_keyword(&input[i..], kw));
+                if is_keyword {
+                    return Some(i);
+                }
+            }
+            _ => (),
+        }
+        prev = Some(c);
+    }
+    None
+}
+
+/// Check whether the input starts with a keyword followed by whitespace.
+fn starts_with_keyword(input: &str, keyword: &str) -> bool {
+    input.starts_with(keyword)
+        && input[keyword.len()..]
+            .chars()
+            .next()
+            .map(char::is_whitespace)
+            .unwrap_or(false)
+}
+
+fn is_name_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || "_.\\%@$".contains(c)
+}
+
 pub fn parse_time_triple(
     time: &str,
     delta: Option<&str>,
@@ -494,7 +1613,8 @@ pub fn parse_time_triple(
     };
     let scale = scale.unwrap_or(0);
 
-    // Split the time into integer and fractional parts.
+    // Split the time into integer and fractional parts. The lexer guarantees
+    // that there is at least one integer digit.
     let mut split = time.split('.');
     let int = split.next().unwrap();
     let frac = split.next();
@@ -520,7 +1640,7 @@ pub fn parse_time_triple(
     }
 
     // Convert the values to BigInt and combine them into a rational
-    // number.
+    // number, which also reduces the fraction.
     let numer = BigInt::parse_bytes(numer.as_bytes(), 10).unwrap();
     let denom = BigInt::parse_bytes(denom.as_bytes(), 10).unwrap();
     let v = BigRational::new(numer, denom);
@@ -532,4 +1652,4 @@ pub fn parse_time_triple(
     (v, delta, epsilon)
 }
 
-pub use super::grammar::{ModuleParser, TimeValueParser, TypeParser};
+pub use super::grammar::{ItemParser, ModuleParser, TimeValueParser, TypeParser};
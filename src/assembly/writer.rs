@@ -2,8 +2,14 @@
 
 //! Emitting LLHD IR assembly.
 
-use crate::ir::{prelude::*, UnitKind};
+use crate::{
+    ir::{prelude::*, UnitKind},
+    ty::{Type, TypeKind},
+    value::SiPrefix,
+};
+use itertools::Itertools;
 use std::{
+    borrow::Cow,
     collections::{HashMap, HashSet},
     io::{Result, Write},
     rc::Rc,
@@ -12,25 +18,121 @@ use std::{
 /// Temporary object to emit LLHD IR assembly.
 pub struct Writer<T> {
     sink: T,
+    type_names: HashMap<Type, String>,
+    sorted: bool,
+    array_style: ArrayStyle,
+    type_comments: bool,
+    time_unit: Option<SiPrefix>,
+    canonical: bool,
+}
+
+/// The notation used to emit array types.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArrayStyle {
+    /// Emit arrays as `[N x T]`.
+    Bracket,
+    /// Emit arrays as `<N x T>`.
+    Angle,
 }
 
 impl<T: Write> Writer<T> {
     /// Create a new assembly writer.
     pub fn new(sink: T) -> Self {
-        Self { sink }
+        Self {
+            sink,
+            type_names: Default::default(),
+            sorted: false,
+            array_style: ArrayStyle::Bracket,
+            type_comments: false,
+            time_unit: None,
+            canonical: false,
+        }
+    }
+
+    /// Create a new assembly writer that emits the canonical form of a module.
+    ///
+    /// Units and declarations are sorted by name, all local values and blocks
+    /// are numbered densely in the order in which they appear, types are
+    /// spelled out in full, and comments are omitted. Two modules that only
+    /// differ in these aspects produce the same output.
+    pub(crate) fn canonical(sink: T) -> Self {
+        Self {
+            sorted: true,
+            canonical: true,
+            ..Self::new(sink)
+        }
+    }
+
+    /// Emit units and declarations sorted by name.
+    ///
+    /// By default, units are emitted in the order in which they were added to
+    /// the module. Sorting them yields the same output for modules that only
+    /// differ in the order of their units.
+    pub fn set_sorted(&mut self, sorted: bool) {
+        self.sorted = sorted;
+    }
+
+    /// Choose the notation used to emit array types.
+    ///
+    /// By default, arrays are emitted as `[N x T]`.
+    pub fn set_array_style(&mut self, style: ArrayStyle) {
+        self.array_style = style;
+    }
+
+    /// Annotate instructions with the type of their result.
+    ///
+    /// Appends a comment such as `; -> i32` to each instruction that yields a
+    /// value. The comments are ignored when the output is parsed again.
+    pub fn set_type_comments(&mut self, type_comments: bool) {
+        self.type_comments = type_comments;
+    }
+
+    /// Emit time constants with a fixed SI prefix.
+    ///
+    /// By default, each time constant is emitted with the prefix that suits it
+    /// best. With a fixed prefix, a time such as `1ns` is emitted as `1000ps`
+    /// instead. Times that cannot be represented exactly with the prefix fall
+    /// back to the next finer prefix that can.
+    pub fn set_time_unit(&mut self, prefix: SiPrefix) {
+        self.time_unit = Some(prefix);
     }
 
     /// Emit assembly for a module.
+    ///
+    /// Named types are declared at the top of the output, and are referred to
+    /// by name wherever they occur in the units. Named constants are declared
+    /// after the types.
     pub fn write_module(&mut self, module: &Module) -> Result<()> {
         let mut separate = false;
-        for unit in module.units() {
+        let canonical = self.canonical;
+        for (name, ty) in module.types().filter(|_| !canonical) {
+            writeln!(
+                self.sink,
+                "type @{} = {}",
+                escape_name(name),
+                TypeWriter(ty, &Default::default(), self.array_style)
+            )?;
+            self.type_names.insert(ty.clone(), name.to_owned());
+            separate = true;
+        }
+        for (name, value) in module.consts() {
+            writeln!(self.sink, "const @{} = {}", escape_name(name), value)?;
+            separate = true;
+        }
+        let mut units: Vec<_> = module.units().collect();
+        let mut decls: Vec<_> = module.decls().collect();
+        if self.sorted {
+            units.sort_by_cached_key(|unit| unit.name().to_string());
+            decls.sort_by_cached_key(|&decl| module[decl].name.to_string());
+        }
+        for unit in units {
             if separate {
                 write!(self.sink, "\n")?;
             }
             separate = true;
             self.write_unit(unit)?;
         }
-        for decl in module.decls() {
+        for decl in decls {
             if separate {
                 write!(self.sink, "\n")?;
             }
@@ -51,11 +153,21 @@ impl<T: Write> Writer<T> {
                 write!(uw.writer.sink, ", ")?;
             }
             comma = true;
-            write!(uw.writer.sink, "{} ", data.sig().arg_type(arg))?;
+            let ty = data.sig().arg_type(arg);
+            write!(
+                uw.writer.sink,
+                "{} ",
+                TypeWriter(&ty, &uw.writer.type_names, uw.writer.array_style)
+            )?;
             uw.write_value_name(data.arg_value(arg))?;
         }
         if data.kind() == UnitKind::Function {
-            write!(uw.writer.sink, ") {} {{\n", data.sig().return_type())?;
+            let ty = data.sig().return_type();
+            write!(
+                uw.writer.sink,
+                ") {} {{\n",
+                TypeWriter(&ty, &uw.writer.type_names, uw.writer.array_style)
+            )?;
         } else {
             write!(uw.writer.sink, ") -> (")?;
             let mut comma = false;
@@ -64,7 +176,12 @@ impl<T: Write> Writer<T> {
                     write!(uw.writer.sink, ", ")?;
                 }
                 comma = true;
-                write!(uw.writer.sink, "{} ", data.sig().arg_type(arg))?;
+                let ty = data.sig().arg_type(arg);
+                write!(
+                    uw.writer.sink,
+                    "{} ",
+                    TypeWriter(&ty, &uw.writer.type_names, uw.writer.array_style)
+                )?;
                 uw.write_value_name(data.arg_value(arg))?;
             }
             write!(uw.writer.sink, ") {{\n")?;
@@ -78,8 +195,23 @@ impl<T: Write> Writer<T> {
                 if data[inst].opcode().is_terminator() && data.is_entity() {
                     continue;
                 }
+                let comments = data.comments(inst).filter(|_| !uw.writer.canonical);
+                for comment in comments.iter().flat_map(|c| &c.leading) {
+                    write!(uw.writer.sink, "    ;{}\n", comment)?;
+                }
                 write!(uw.writer.sink, "    ")?;
                 uw.write_inst(inst)?;
+                if uw.writer.type_comments && data.has_result(inst) {
+                    let ty = data.value_type(data.inst_result(inst));
+                    write!(
+                        uw.writer.sink,
+                        " ; -> {}",
+                        TypeWriter(&ty, &uw.writer.type_names, uw.writer.array_style)
+                    )?;
+                }
+                if let Some(comment) = comments.and_then(|c| c.trailing.as_ref()) {
+                    write!(uw.writer.sink, " ;{}", comment)?;
+                }
                 write!(uw.writer.sink, "\n")?;
             }
         }
@@ -89,7 +221,77 @@ impl<T: Write> Writer<T> {
 
     /// Emit assembly for a declaration.
     pub fn write_declaration(&mut self, sig: &Signature, name: &UnitName) -> Result<()> {
-        write!(self.sink, "declare {} {}\n", name, sig)?;
+        write!(
+            self.sink,
+            "declare {} {}\n",
+            name,
+            SignatureWriter(sig, self.array_style)
+        )?;
+        Ok(())
+    }
+}
+
+/// Temporary object to emit a type, referring to named types by name.
+struct TypeWriter<'a>(&'a Type, &'a HashMap<Type, String>, ArrayStyle);
+
+impl std::fmt::Display for TypeWriter<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let names = self.1;
+        let style = self.2;
+        if let Some(name) = names.get(self.0) {
+            return write!(f, "@{}", escape_name(name));
+        }
+        match **self.0 {
+            TypeKind::PointerType(ref ty) => write!(f, "{}*", TypeWriter(ty, names, style)),
+            TypeKind::SignalType(ref ty) => write!(f, "{}$", TypeWriter(ty, names, style)),
+            TypeKind::ArrayType(l, ref ty) => match style {
+                ArrayStyle::Bracket => write!(f, "[{} x {}]", l, TypeWriter(ty, names, style)),
+                ArrayStyle::Angle => write!(f, "<{} x {}>", l, TypeWriter(ty, names, style)),
+            },
+            TypeKind::StructType(ref tys) => write!(
+                f,
+                "{{{}}}",
+                tys.iter()
+                    .map(|ty| TypeWriter(ty, names, style))
+                    .format(", ")
+            ),
+            _ => write!(f, "{}", self.0),
+        }
+    }
+}
+
+/// Temporary object to emit a signature.
+struct SignatureWriter<'a>(&'a Signature, ArrayStyle);
+
+impl std::fmt::Display for SignatureWriter<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        use std::iter::{once, repeat};
+        let sig = self.0;
+        let names = Default::default();
+        write!(f, "(")?;
+        for (arg, sep) in sig.inputs().zip(once("").chain(repeat(", "))) {
+            write!(
+                f,
+                "{}{}",
+                sep,
+                TypeWriter(&sig.arg_type(arg), &names, self.1)
+            )?;
+        }
+        if sig.has_outputs() {
+            write!(f, ") -> (")?;
+            for (arg, sep) in sig.outputs().zip(once("").chain(repeat(", "))) {
+                write!(
+                    f,
+                    "{}{}",
+                    sep,
+                    TypeWriter(&sig.arg_type(arg), &names, self.1)
+                )?;
+            }
+        }
+        write!(f, ")")?;
+        if sig.has_return_type() {
+            write!(f, " {}", TypeWriter(&sig.return_type(), &names, self.1))?;
+        }
         Ok(())
     }
 }
@@ -113,7 +315,8 @@ impl<'a, T: Write> UnitWriter<'a, T> {
             value_names: Default::default(),
             block_names: Default::default(),
             name_indices: Default::default(),
-            names: Default::default(),
+            // `%_` discards a result when parsed, so never emit it as a name.
+            names: std::iter::once(Rc::new(String::from("_"))).collect(),
             tmp_index: 0,
         }
     }
@@ -127,7 +330,12 @@ impl<'a, T: Write> UnitWriter<'a, T> {
 
         // Check if the value has an explicit name set, or if we should just
         // generate a temporary name.
-        let name = self.uniquify_name(self.unit.get_name(value));
+        let name = if self.writer.canonical {
+            None
+        } else {
+            self.unit.get_name(value)
+        };
+        let name = self.uniquify_name(name);
 
         // Emit the name and associate it with the value for later reuse.
         write!(self.writer.sink, "%{}", name)?;
@@ -144,7 +352,12 @@ impl<'a, T: Write> UnitWriter<'a, T> {
 
         // Check if the block has an explicit name set, or if we should just
         // generate a temporary name.
-        let name = self.uniquify_name(self.unit.get_block_name(block));
+        let name = if self.writer.canonical {
+            None
+        } else {
+            self.unit.get_block_name(block)
+        };
+        let name = self.uniquify_name(name);
 
         // Emit the name and associate it with the block for later reuse.
         write!(self.writer.sink, "{}", name)?;
@@ -159,9 +372,12 @@ impl<'a, T: Write> UnitWriter<'a, T> {
     }
 
     /// Uniquify a value or block name.
+    ///
+    /// Returns the name as it is to be emitted, with requested names quoted as
+    /// needed and temporary names left bare.
     fn uniquify_name(&mut self, name: Option<&str>) -> Rc<String> {
         if let Some(requested_name) = name {
-            let requested_name = escape_name(requested_name);
+            let requested_name = Rc::new(requested_name.to_owned());
             let idx = self.name_indices.entry(requested_name.clone()).or_insert(0);
             loop {
                 let name = if *idx == 0 {
@@ -171,7 +387,7 @@ impl<'a, T: Write> UnitWriter<'a, T> {
                 };
                 *idx += 1;
                 if self.names.insert(name.clone()) {
-                    break name;
+                    break Rc::new(escape_name(&name).into_owned());
                 }
             }
         } else {
@@ -188,7 +404,12 @@ impl<'a, T: Write> UnitWriter<'a, T> {
     /// Emit the use of a value.
     pub fn write_value_use(&mut self, value: Value, with_type: bool) -> Result<()> {
         if with_type {
-            write!(self.writer.sink, "{} ", self.unit.value_type(value))?;
+            let ty = self.unit.value_type(value);
+            write!(
+                self.writer.sink,
+                "{} ",
+                TypeWriter(&ty, &self.writer.type_names, self.writer.array_style)
+            )?;
         }
         self.write_value_name(value)
     }
@@ -209,12 +430,38 @@ impl<'a, T: Write> UnitWriter<'a, T> {
                 unit.value_type(unit.inst_result(inst)),
                 data.get_const_int().unwrap().value
             )?,
-            Opcode::ConstTime => write!(
+            Opcode::ConstTime => {
+                let time = data.get_const_time().unwrap();
+                let time = match self.writer.time_unit {
+                    Some(prefix) => time.to_string_in(prefix),
+                    None => time.to_string(),
+                };
+                write!(self.writer.sink, "{} time {}", data.opcode(), time)?
+            }
+            Opcode::ConstEnum => write!(
                 self.writer.sink,
-                "{} time {}",
+                "{} {}",
                 data.opcode(),
-                data.get_const_time().unwrap()
+                data.get_const_enum().unwrap()
             )?,
+            Opcode::ConstUndef => {
+                let ty = unit.value_type(unit.inst_result(inst));
+                write!(
+                    self.writer.sink,
+                    "{} {} undef",
+                    data.opcode(),
+                    TypeWriter(&ty, &self.writer.type_names, self.writer.array_style)
+                )?
+            }
+            Opcode::ConstPoison => {
+                let ty = unit.value_type(unit.inst_result(inst));
+                write!(
+                    self.writer.sink,
+                    "{} {} poison",
+                    data.opcode(),
+                    TypeWriter(&ty, &self.writer.type_names, self.writer.array_style)
+                )?
+            }
             Opcode::ArrayUniform => {
                 write!(self.writer.sink, "[{} x ", data.imms()[0])?;
                 self.write_value_use(data.args()[0], true)?;
@@ -260,6 +507,10 @@ impl<'a, T: Write> UnitWriter<'a, T> {
             | Opcode::Udiv
             | Opcode::Umod
             | Opcode::Urem
+            | Opcode::Uaddo
+            | Opcode::Saddo
+            | Opcode::Usubo
+            | Opcode::Ssubo
             | Opcode::Eq
             | Opcode::Neq
             | Opcode::Slt
@@ -311,6 +562,20 @@ impl<'a, T: Write> UnitWriter<'a, T> {
                     self.write_value_use(arg, true)?;
                 }
             }
+            Opcode::Sel => {
+                let ty = unit.inst_type(inst);
+                write!(
+                    self.writer.sink,
+                    "{} {} ",
+                    data.opcode(),
+                    TypeWriter(&ty, &self.writer.type_names, self.writer.array_style)
+                )?;
+                self.write_value_use(data.args()[0], false)?;
+                write!(self.writer.sink, ", ")?;
+                self.write_value_use(data.args()[1], false)?;
+                write!(self.writer.sink, ", ")?;
+                self.write_value_use(data.args()[2], false)?;
+            }
             Opcode::Reg => {
                 write!(self.writer.sink, "{} ", data.opcode())?;
                 self.write_value_use(data.args()[0], true)?;
@@ -341,11 +606,12 @@ impl<'a, T: Write> UnitWriter<'a, T> {
                 }
             }
             Opcode::ExtField | Opcode::ExtSlice => {
+                let ty = unit.inst_type(inst);
                 write!(
                     self.writer.sink,
                     "{} {}",
                     data.opcode(),
-                    unit.inst_type(inst)
+                    TypeWriter(&ty, &self.writer.type_names, self.writer.array_style)
                 )?;
                 for &arg in data.args() {
                     write!(self.writer.sink, ", ")?;
@@ -356,15 +622,16 @@ impl<'a, T: Write> UnitWriter<'a, T> {
                 }
             }
             Opcode::Call => {
+                let ty = if unit.has_result(inst) {
+                    unit.value_type(unit.inst_result(inst))
+                } else {
+                    crate::void_ty()
+                };
                 write!(
                     self.writer.sink,
                     "{} {} {} (",
                     data.opcode(),
-                    if unit.has_result(inst) {
-                        unit.value_type(unit.inst_result(inst))
-                    } else {
-                        crate::void_ty()
-                    },
+                    TypeWriter(&ty, &self.writer.type_names, self.writer.array_style),
                     unit[data.get_ext_unit().unwrap()].name,
                 )?;
                 let mut comma = false;
@@ -377,6 +644,30 @@ impl<'a, T: Write> UnitWriter<'a, T> {
                 }
                 write!(self.writer.sink, ")")?;
             }
+            Opcode::CallIndirect => {
+                let ty = if unit.has_result(inst) {
+                    unit.value_type(unit.inst_result(inst))
+                } else {
+                    crate::void_ty()
+                };
+                write!(
+                    self.writer.sink,
+                    "{} {} ",
+                    data.opcode(),
+                    TypeWriter(&ty, &self.writer.type_names, self.writer.array_style),
+                )?;
+                self.write_value_use(data.args()[0], false)?;
+                write!(self.writer.sink, " (")?;
+                let mut comma = false;
+                for &arg in &data.args()[1..] {
+                    if comma {
+                        write!(self.writer.sink, ", ")?;
+                    }
+                    comma = true;
+                    self.write_value_use(arg, true)?;
+                }
+                write!(self.writer.sink, ")")?;
+            }
             Opcode::Inst => {
                 write!(
                     self.writer.sink,
@@ -405,11 +696,12 @@ impl<'a, T: Write> UnitWriter<'a, T> {
             }
             Opcode::Halt | Opcode::Ret => write!(self.writer.sink, "{}", data.opcode())?,
             Opcode::Phi => {
+                let ty = unit.value_type(unit.inst_result(inst));
                 write!(
                     self.writer.sink,
                     "{} {} ",
                     data.opcode(),
-                    unit.value_type(unit.inst_result(inst))
+                    TypeWriter(&ty, &self.writer.type_names, self.writer.array_style)
                 )?;
                 let mut comma = false;
                 for (&arg, &block) in data.args().iter().zip(data.blocks().iter()) {
@@ -464,15 +756,24 @@ fn is_acceptable_name_char(c: char) -> bool {
     c >= 'a' && c <= 'z' || c >= 'A' && c <= 'Z' || c >= '0' && c <= '9' || c == '_' || c == '.'
 }
 
-/// Escape the special characters in a name.
-fn escape_name(input: &str) -> Rc<String> {
-    let mut s = String::with_capacity(input.len());
+/// Quote a name if it contains characters that cannot appear in a bare name.
+///
+/// Quoted names are enclosed in `"`, and any `"` or `\` within them is escaped
+/// with a backslash, as in `%"foo$bar"`. Names starting with a digit are quoted
+/// as well, since `%1` would be read back as a temporary rather than a name.
+pub(crate) fn escape_name(input: &str) -> Cow<'_, str> {
+    let starts_with_digit = input.chars().next().map_or(false, |c| c.is_digit(10));
+    if !input.is_empty() && !starts_with_digit && input.chars().all(is_acceptable_name_char) {
+        return input.into();
+    }
+    let mut s = String::with_capacity(input.len() + 2);
+    s.push('"');
     for c in input.chars() {
-        if is_acceptable_name_char(c) {
-            s.push(c);
-        } else {
-            s.push_str(&format!("\\{:x}", c as u32));
+        if c == '"' || c == '\\' {
+            s.push('\\');
         }
+        s.push(c);
     }
-    Rc::new(s)
+    s.push('"');
+    s.into()
 }
@@ -138,14 +138,22 @@ fn main_inner() -> Result<(), String> {
         trace!("Running pass {}", pass);
         let t0 = time::precise_time_ns();
         let _changes = match pass {
+            "canon" => llhd::pass::Canonicalization::run_on_module(&ctx, &mut module),
+            "ces" => llhd::pass::CriticalEdgeSplitting::run_on_module(&ctx, &mut module),
             "cf" => llhd::pass::ConstFolding::run_on_module(&ctx, &mut module),
             "cfs" => llhd::pass::ControlFlowSimplification::run_on_module(&ctx, &mut module),
+            "coalesce" => llhd::pass::DriveCoalescing::run_on_module(&ctx, &mut module),
             "dce" => llhd::pass::DeadCodeElim::run_on_module(&ctx, &mut module),
             "deseq" => llhd::pass::Desequentialization::run_on_module(&ctx, &mut module),
             "ecm" => llhd::pass::EarlyCodeMotion::run_on_module(&ctx, &mut module),
+            "enumlower" => llhd::pass::EnumLowering::run_on_module(&ctx, &mut module),
             "gcse" => llhd::pass::GlobalCommonSubexprElim::run_on_module(&ctx, &mut module),
+            "inline" => llhd::pass::FunctionInlining::run_on_module(&ctx, &mut module),
             "insim" => llhd::pass::InstSimplification::run_on_module(&ctx, &mut module),
+            "licm" => llhd::pass::LoopInvariantCodeMotion::run_on_module(&ctx, &mut module),
             "proclower" => llhd::pass::ProcessLowering::run_on_module(&ctx, &mut module),
+            "renum" => llhd::pass::TempRenumbering::run_on_module(&ctx, &mut module),
+            "sccp" => llhd::pass::ConstPropagation::run_on_module(&ctx, &mut module),
             "tcm" => llhd::pass::TemporalCodeMotion::run_on_module(&ctx, &mut module),
             "vtpp" => llhd::pass::VarToPhiPromotion::run_on_module(&ctx, &mut module),
             "verify" => {
@@ -255,14 +263,21 @@ static HELP_PASSES: &str = "Exact order of passes to run
 This option specifies the exact order of passes to be executed. The admissible \
 passes are as follows:
 
+canon       Canonicalization
+ces         Critical Edge Splitting
 cf          Constant folding
 cfs         Control Flow Simplification
+coalesce    Drive Coalescing
 dce         Dead Code Elimination
 deseq       Desequentialization
 ecm         Early Code Motion
+enumlower   Enum Lowering
 gcse        Global Common Subexpression Elimination
+inline      Function Inlining
 insim       Instruction Simplification
+licm        Loop-Invariant Code Motion
 proclower   Process Lowering
+renum       Temporary Renumbering
 tcm         Temporal Code Motion
 vtpp        Var-to-Phi Promotion
 verify      Verify the IR
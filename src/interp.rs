@@ -0,0 +1,549 @@
+// Copyright (c) 2017-2020 Fabian Schuiki
+
+//! Function interpretation
+//!
+//! This module implements an interpreter that evaluates functions on constant
+//! arguments. This is mainly useful to test transformations of functions.
+
+use crate::{
+    ir::prelude::*,
+    value::{ArrayValue, IntValue, StructValue},
+};
+use std::collections::HashMap;
+
+/// An error that occurred during interpretation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InterpError {
+    /// The unit to be interpreted is not a function.
+    NotAFunction(String),
+    /// The number of arguments does not match the function signature.
+    ArgCount {
+        /// The number of arguments expected by the function.
+        expected: usize,
+        /// The number of arguments provided.
+        found: usize,
+    },
+    /// An argument does not match the type in the function signature.
+    ArgType(usize),
+    /// An instruction that is not allowed in a function was encountered.
+    IllegalInst(String),
+    /// An instruction that the interpreter does not support was encountered.
+    UnsupportedInst(String),
+    /// A division or modulo by zero occurred.
+    DivisionByZero(String),
+}
+
+impl std::fmt::Display for InterpError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            InterpError::NotAFunction(name) => write!(f, "{} is not a function", name),
+            InterpError::ArgCount { expected, found } => write!(
+                f,
+                "function expects {} arguments, but {} were provided",
+                expected, found
+            ),
+            InterpError::ArgType(idx) => write!(f, "argument {} has the wrong type", idx),
+            InterpError::IllegalInst(inst) => {
+                write!(f, "instruction `{}` not allowed in a function", inst)
+            }
+            InterpError::UnsupportedInst(inst) => {
+                write!(f, "instruction `{}` cannot be interpreted", inst)
+            }
+            InterpError::DivisionByZero(inst) => write!(f, "division by zero in `{}`", inst),
+        }
+    }
+}
+
+impl std::error::Error for InterpError {}
+
+/// A value slot during interpretation.
+///
+/// Pointers created by `var` instructions are tracked as indices into the
+/// interpreter's memory.
+#[derive(Debug, Clone)]
+enum Slot {
+    Value(crate::Value),
+    Pointer(usize),
+}
+
+/// Interpret a function on constant arguments.
+///
+/// Executes the function starting at its entry block and returns the value
+/// passed to the `ret` instruction, or `None` if the function returns without
+/// a value.
+pub fn interpret_function(
+    func: &Unit,
+    args: &[crate::Value],
+) -> Result<Option<crate::Value>, InterpError> {
+    if !func.is_function() {
+        return Err(InterpError::NotAFunction(func.name().to_string()));
+    }
+    let inputs: Vec<_> = func.input_args().collect();
+    if inputs.len() != args.len() {
+        return Err(InterpError::ArgCount {
+            expected: inputs.len(),
+            found: args.len(),
+        });
+    }
+    let mut interp = Interpreter::new(*func);
+    for (i, (&value, arg)) in inputs.iter().zip(args).enumerate() {
+        if func.value_type(value) != arg.ty() {
+            return Err(InterpError::ArgType(i));
+        }
+        interp.set(value, arg.clone());
+    }
+    interp.run()
+}
+
+/// The execution state of a single unit.
+///
+/// Also used by the simulator to evaluate the non-signal instructions of
+/// processes and entities.
+pub(crate) struct Interpreter<'a> {
+    unit: Unit<'a>,
+    values: HashMap<Value, Slot>,
+    memory: Vec<crate::Value>,
+}
+
+impl<'a> Interpreter<'a> {
+    /// Create a new interpreter for a unit.
+    pub(crate) fn new(unit: Unit<'a>) -> Self {
+        Self {
+            unit,
+            values: HashMap::new(),
+            memory: vec![],
+        }
+    }
+
+    /// Assign a constant to a value.
+    pub(crate) fn set(&mut self, value: Value, constant: crate::Value) {
+        self.values.insert(value, Slot::Value(constant));
+    }
+
+    /// Evaluate the phi nodes at the beginning of a block simultaneously.
+    pub(crate) fn enter_block(
+        &mut self,
+        bb: Block,
+        prev_bb: Option<Block>,
+    ) -> Result<(), InterpError> {
+        let unit = self.unit;
+        let mut phis = vec![];
+        for inst in unit.insts(bb) {
+            let data = &unit[inst];
+            if data.opcode() != Opcode::Phi {
+                continue;
+            }
+            let from = data
+                .blocks()
+                .iter()
+                .position(|&b| Some(b) == prev_bb)
+                .ok_or_else(|| InterpError::UnsupportedInst(inst.dump(&unit).to_string()))?;
+            phis.push((unit.inst_result(inst), self.slot(data.args()[from])));
+        }
+        self.values.extend(phis);
+        Ok(())
+    }
+    /// Execute the function until it returns.
+    fn run(&mut self) -> Result<Option<crate::Value>, InterpError> {
+        let unit = self.unit;
+        let mut prev_bb = None;
+        let mut bb = unit.entry();
+        'blocks: loop {
+            trace!("Interpreting {}", bb.dump(&unit));
+            self.enter_block(bb, prev_bb)?;
+
+            // Execute the remaining instructions.
+            for inst in unit.insts(bb) {
+                let data = &unit[inst];
+                match data.opcode() {
+                    Opcode::Phi => continue,
+                    Opcode::Br => {
+                        prev_bb = Some(bb);
+                        bb = data.blocks()[0];
+                        continue 'blocks;
+                    }
+                    Opcode::BrCond => {
+                        let cond = self.int(data.args()[0]);
+                        prev_bb = Some(bb);
+                        bb = data.blocks()[if cond.is_zero() { 0 } else { 1 }];
+                        continue 'blocks;
+                    }
+                    Opcode::Ret => return Ok(None),
+                    Opcode::RetValue => return Ok(Some(self.value(data.args()[0]))),
+                    _ => (),
+                }
+                self.step(inst)?;
+            }
+
+            // Blocks without a terminator are rejected by the verifier.
+            return Err(InterpError::UnsupportedInst(format!(
+                "fall off the end of {}",
+                bb.dump(&unit)
+            )));
+        }
+    }
+
+    /// Execute a single non-terminator instruction and record its result.
+    pub(crate) fn step(&mut self, inst: Inst) -> Result<(), InterpError> {
+        if let Some(slot) = self.execute(inst)? {
+            self.values.insert(self.unit.inst_result(inst), slot);
+        }
+        Ok(())
+    }
+
+    /// Execute a single non-terminator instruction.
+    fn execute(&mut self, inst: Inst) -> Result<Option<Slot>, InterpError> {
+        let unit = self.unit;
+        let data = &unit[inst];
+        let value = match data.opcode() {
+            Opcode::ConstInt => data.get_const_int().unwrap().clone().into(),
+            Opcode::ConstTime => data.get_const_time().unwrap().clone().into(),
+            Opcode::ConstEnum => data.get_const_enum().unwrap().clone().into(),
+            Opcode::ConstUndef | Opcode::ConstPoison => crate::Value::zero(&unit.inst_type(inst)),
+            Opcode::Alias => return Ok(Some(self.slot(data.args()[0]))),
+            Opcode::ArrayUniform => {
+                ArrayValue::new_uniform(data.imms()[0], self.value(data.args()[0])).into()
+            }
+            Opcode::Array => {
+                ArrayValue::new(data.args().iter().map(|&a| self.value(a)).collect()).into()
+            }
+            Opcode::Struct => {
+                StructValue::new(data.args().iter().map(|&a| self.value(a)).collect()).into()
+            }
+            Opcode::Not | Opcode::Neg => {
+                IntValue::unary_op(data.opcode(), &self.int(data.args()[0])).into()
+            }
+            Opcode::Add
+            | Opcode::Sub
+            | Opcode::And
+            | Opcode::Or
+            | Opcode::Xor
+            | Opcode::Smul
+            | Opcode::Umul => {
+                let lhs = self.int(data.args()[0]);
+                let rhs = self.int(data.args()[1]);
+                IntValue::binary_op(data.opcode(), &lhs, &rhs).into()
+            }
+            Opcode::Uaddo | Opcode::Saddo | Opcode::Usubo | Opcode::Ssubo => {
+                let lhs = self.int(data.args()[0]);
+                let rhs = self.int(data.args()[1]);
+                let (result, overflow) =
+                    IntValue::try_overflow_op(data.opcode(), &lhs, &rhs).unwrap();
+                StructValue::new(vec![result.into(), overflow.into()]).into()
+            }
+            Opcode::Sdiv
+            | Opcode::Smod
+            | Opcode::Srem
+            | Opcode::Udiv
+            | Opcode::Umod
+            | Opcode::Urem => {
+                let lhs = self.int(data.args()[0]);
+                let rhs = self.int(data.args()[1]);
+                if rhs.is_zero() {
+                    return Err(InterpError::DivisionByZero(inst.dump(&unit).to_string()));
+                }
+                IntValue::binary_op(data.opcode(), &lhs, &rhs).into()
+            }
+            Opcode::Eq
+            | Opcode::Neq
+            | Opcode::Slt
+            | Opcode::Sgt
+            | Opcode::Sle
+            | Opcode::Sge
+            | Opcode::Ult
+            | Opcode::Ugt
+            | Opcode::Ule
+            | Opcode::Uge => {
+                let lhs = self.value(data.args()[0]);
+                let rhs = self.value(data.args()[1]);
+                match (data.opcode(), lhs.get_int(), rhs.get_int()) {
+                    (_, Some(lhs), Some(rhs)) => IntValue::compare_op(data.opcode(), lhs, rhs),
+                    (Opcode::Eq, ..) => IntValue::from_usize(1, (lhs == rhs) as usize),
+                    (Opcode::Neq, ..) => IntValue::from_usize(1, (lhs != rhs) as usize),
+                    _ => return Err(InterpError::UnsupportedInst(inst.dump(&unit).to_string())),
+                }
+                .into()
+            }
+            Opcode::Shl | Opcode::Shr => {
+                let base = self.value(data.args()[0]);
+                let hidden = self.value(data.args()[1]);
+                let amount = self.int(data.args()[2]).to_usize();
+                match (base.get_int(), hidden.get_int()) {
+                    (Some(base), Some(hidden)) => {
+                        shift(base, hidden, amount, data.opcode() == Opcode::Shl).into()
+                    }
+                    _ => return Err(InterpError::UnsupportedInst(inst.dump(&unit).to_string())),
+                }
+            }
+            Opcode::Mux => {
+                let array = self.value(data.args()[0]);
+                let sel = match self.value(data.args()[1]) {
+                    crate::Value::Enum(sel) => sel.value,
+                    sel => sel.unwrap_int().to_usize(),
+                };
+                array.unwrap_array().extract_field(sel)
+            }
+            Opcode::Sel => {
+                let cond = self.int(data.args()[0]);
+                let choice = if cond.is_zero() { 2 } else { 1 };
+                self.value(data.args()[choice])
+            }
+            Opcode::ExtField => {
+                let target = self.value(data.args()[0]);
+                match target {
+                    crate::Value::Array(v) => v.extract_field(data.imms()[0]),
+                    crate::Value::Struct(v) => v.extract_field(data.imms()[0]),
+                    _ => return Err(InterpError::UnsupportedInst(inst.dump(&unit).to_string())),
+                }
+            }
+            Opcode::ExtSlice => {
+                let target = self.value(data.args()[0]);
+                let (off, len) = (data.imms()[0], data.imms()[1]);
+                match target {
+                    crate::Value::Int(v) => v.extract_slice(off, len).into(),
+                    crate::Value::Array(v) => v.extract_slice(off, len).into(),
+                    _ => return Err(InterpError::UnsupportedInst(inst.dump(&unit).to_string())),
+                }
+            }
+            Opcode::InsField => {
+                let target = self.value(data.args()[0]);
+                let value = self.value(data.args()[1]);
+                match target {
+                    crate::Value::Array(mut v) => {
+                        v.insert_field(data.imms()[0], value);
+                        v.into()
+                    }
+                    crate::Value::Struct(mut v) => {
+                        v.insert_field(data.imms()[0], value);
+                        v.into()
+                    }
+                    _ => return Err(InterpError::UnsupportedInst(inst.dump(&unit).to_string())),
+                }
+            }
+            Opcode::InsSlice => {
+                let target = self.value(data.args()[0]);
+                let value = self.value(data.args()[1]);
+                let (off, len) = (data.imms()[0], data.imms()[1]);
+                match (target, value) {
+                    (crate::Value::Int(mut v), crate::Value::Int(w)) => {
+                        v.insert_slice(off, len, &w);
+                        v.into()
+                    }
+                    (crate::Value::Array(mut v), crate::Value::Array(w)) => {
+                        v.insert_slice(off, len, &w);
+                        v.into()
+                    }
+                    _ => return Err(InterpError::UnsupportedInst(inst.dump(&unit).to_string())),
+                }
+            }
+            Opcode::Var => {
+                let init = self.value(data.args()[0]);
+                self.memory.push(init);
+                return Ok(Some(Slot::Pointer(self.memory.len() - 1)));
+            }
+            Opcode::Ld => {
+                let ptr = self.pointer(data.args()[0]);
+                self.memory[ptr].clone()
+            }
+            Opcode::St => {
+                let ptr = self.pointer(data.args()[0]);
+                self.memory[ptr] = self.value(data.args()[1]);
+                return Ok(None);
+            }
+            Opcode::Sig
+            | Opcode::Prb
+            | Opcode::Drv
+            | Opcode::DrvCond
+            | Opcode::Con
+            | Opcode::Del
+            | Opcode::Reg
+            | Opcode::Inst
+            | Opcode::Halt
+            | Opcode::Wait
+            | Opcode::WaitTime => {
+                return Err(InterpError::IllegalInst(inst.dump(&unit).to_string()))
+            }
+            _ => return Err(InterpError::UnsupportedInst(inst.dump(&unit).to_string())),
+        };
+        Ok(Some(Slot::Value(value)))
+    }
+
+    /// Look up the slot assigned to a value.
+    fn slot(&self, value: Value) -> Slot {
+        match self.values.get(&value) {
+            Some(slot) => slot.clone(),
+            None => panic!("{} used before definition", value.dump(&self.unit)),
+        }
+    }
+
+    /// Look up the constant assigned to a value.
+    pub(crate) fn value(&self, value: Value) -> crate::Value {
+        match self.slot(value) {
+            Slot::Value(v) => v,
+            Slot::Pointer(_) => panic!("{} is a pointer", value.dump(&self.unit)),
+        }
+    }
+
+    /// Look up the integer constant assigned to a value.
+    pub(crate) fn int(&self, value: Value) -> IntValue {
+        self.value(value).unwrap_int().clone()
+    }
+
+    /// Look up the memory location assigned to a pointer value.
+    fn pointer(&self, value: Value) -> usize {
+        match self.slot(value) {
+            Slot::Pointer(p) => p,
+            Slot::Value(_) => panic!("{} is not a pointer", value.dump(&self.unit)),
+        }
+    }
+}
+
+/// Shift `base` by `amount` bits, filling in the bits from `hidden`.
+fn shift(base: &IntValue, hidden: &IntValue, amount: usize, left: bool) -> IntValue {
+    let amount = std::cmp::min(amount, hidden.width);
+    let width = base.width + hidden.width;
+    let mut concat = IntValue::zero(width);
+    if left {
+        concat.insert_slice(0, hidden.width, hidden);
+        concat.insert_slice(hidden.width, base.width, base);
+        concat.extract_slice(hidden.width - amount, base.width)
+    } else {
+        concat.insert_slice(0, base.width, base);
+        concat.insert_slice(base.width, hidden.width, hidden);
+        concat.extract_slice(amount, base.width)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assembly::parse_module;
+
+    fn int(width: usize, value: usize) -> crate::Value {
+        IntValue::from_usize(width, value).into()
+    }
+
+    #[test]
+    fn arithmetic() {
+        let module = parse_module(
+            "
+            func @foo (i32 %a, i32 %b) i32 {
+            %entry:
+                %0 = add i32 %a, %b
+                %1 = const i32 3
+                %2 = umul i32 %0, %1
+                %3 = sub i32 %2, %a
+                ret i32 %3
+            }
+            ",
+        )
+        .unwrap();
+        let func = module.units().next().unwrap();
+        assert_eq!(
+            interpret_function(&func, &[int(32, 4), int(32, 5)]),
+            Ok(Some(int(32, 23)))
+        );
+    }
+
+    #[test]
+    fn loops_and_memory() {
+        let module = parse_module(
+            "
+            func @sum (i8 %n) i8 {
+            %entry:
+                %zero = const i8 0
+                %one = const i8 1
+                %acc = var i8 %zero
+                br %loop
+            %loop:
+                %i = phi i8 [%zero, %entry], [%in, %body]
+                %done = eq i8 %i, %n
+                br %done, %body, %exit
+            %body:
+                %in = add i8 %i, %one
+                %a = ld i8* %acc
+                %an = add i8 %a, %in
+                st i8* %acc, %an
+                br %loop
+            %exit:
+                %r = ld i8* %acc
+                ret i8 %r
+            }
+            ",
+        )
+        .unwrap();
+        let func = module.units().next().unwrap();
+        assert_eq!(
+            interpret_function(&func, &[int(8, 4)]),
+            Ok(Some(int(8, 10)))
+        );
+    }
+
+    #[test]
+    fn division_by_zero() {
+        let module = parse_module(
+            "
+            func @foo (i32 %a) i32 {
+            %entry:
+                %0 = const i32 0
+                %1 = udiv i32 %a, %0
+                ret i32 %1
+            }
+            ",
+        )
+        .unwrap();
+        let func = module.units().next().unwrap();
+        assert_eq!(
+            interpret_function(&func, &[int(32, 4)]),
+            Err(InterpError::DivisionByZero(
+                "%1 = udiv i32 %a, %0".to_owned()
+            ))
+        );
+    }
+
+    #[test]
+    fn wrong_arguments() {
+        let module = parse_module(
+            "
+            func @foo (i32 %a) void {
+            %entry:
+                ret
+            }
+            ",
+        )
+        .unwrap();
+        let func = module.units().next().unwrap();
+        assert_eq!(
+            interpret_function(&func, &[]),
+            Err(InterpError::ArgCount {
+                expected: 1,
+                found: 0
+            })
+        );
+        assert_eq!(
+            interpret_function(&func, &[int(8, 0)]),
+            Err(InterpError::ArgType(0))
+        );
+        assert_eq!(interpret_function(&func, &[int(32, 0)]), Ok(None));
+    }
+
+    #[test]
+    fn illegal_instructions() {
+        // The parser rejects signals in functions, so build the unit by hand.
+        let mut sig = Signature::new();
+        sig.set_return_type(crate::int_ty(32));
+        let mut data = UnitData::new(UnitKind::Function, UnitName::global("foo"), sig);
+        let mut builder = UnitBuilder::new_anonymous(&mut data);
+        let entry = builder.block();
+        builder.append_to(entry);
+        let zero = builder.ins().name("zero").const_int((32, 0));
+        let signal = builder.ins().name("s").sig(zero);
+        let value = builder.ins().prb(signal);
+        builder.ins().ret_value(value);
+        let func = Unit::new_anonymous(&data);
+        assert_eq!(
+            interpret_function(&func, &[]),
+            Err(InterpError::IllegalInst("%s = sig i32$ %zero".to_owned()))
+        );
+    }
+}
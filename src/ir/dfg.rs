@@ -7,7 +7,7 @@
 
 use crate::{
     impl_table_indexing,
-    ir::{Arg, Block, ExtUnit, ExtUnitData, Inst, InstData, Value, ValueData},
+    ir::{Arg, Block, Comments, ExtUnit, ExtUnitData, Inst, InstData, Span, Value, ValueData},
     table::{PrimaryTable2, SecondaryTable},
 };
 use std::collections::{HashMap, HashSet};
@@ -35,6 +35,10 @@ pub(super) struct DataFlowGraph {
     pub anonymous_hints: HashMap<Value, u32>,
     /// The location hints assigned to instructions.
     pub location_hints: HashMap<Inst, usize>,
+    /// The source spans assigned to instructions.
+    pub span_hints: HashMap<Inst, Span>,
+    /// The source comments attached to instructions.
+    pub comments: HashMap<Inst, Comments>,
     /// The value use lookup table.
     pub value_uses: HashMap<Value, HashSet<Inst>>,
     /// The block use lookup table.
@@ -0,0 +1,133 @@
+// Copyright (c) 2017-2020 Fabian Schuiki
+
+//! Emission of GraphViz DOT graphs.
+//!
+//! This module implements rendering of the control flow of functions,
+//! processes, and entities as GraphViz DOT graphs for debugging.
+
+use crate::ir::prelude::*;
+use std::fmt::{Result, Write};
+
+impl Unit<'_> {
+    /// Render the control flow graph of the unit as a GraphViz DOT digraph.
+    ///
+    /// Each block becomes a node listing its instructions, and each control
+    /// flow edge between blocks becomes an edge. The edges of conditional
+    /// branches are labeled `true` and `false`.
+    pub fn to_dot(self) -> String {
+        let mut out = String::new();
+        writeln!(out, "digraph {} {{", quote(&self.name().to_string())).unwrap();
+        write_unit_body(&mut out, self, "", "    ").unwrap();
+        writeln!(out, "}}").unwrap();
+        out
+    }
+}
+
+impl Module {
+    /// Render the control flow graph of all units as a GraphViz DOT digraph.
+    ///
+    /// Each unit is emitted as a separate cluster. See `Unit::to_dot`.
+    pub fn to_dot(&self) -> String {
+        let mut out = String::new();
+        writeln!(out, "digraph module {{").unwrap();
+        for unit in self.units() {
+            let prefix = format!("{}_", unit.id());
+            writeln!(out, "    subgraph cluster_{} {{", unit.id()).unwrap();
+            writeln!(
+                out,
+                "        label = {};",
+                quote(&format!("{} {}", unit.kind(), unit.name()))
+            )
+            .unwrap();
+            write_unit_body(&mut out, unit, &prefix, "        ").unwrap();
+            writeln!(out, "    }}").unwrap();
+        }
+        writeln!(out, "}}").unwrap();
+        out
+    }
+}
+
+/// Emit the nodes and edges of a unit.
+fn write_unit_body(out: &mut String, unit: Unit, prefix: &str, indent: &str) -> Result {
+    writeln!(out, "{}node [shape=box, fontname=monospace];", indent)?;
+    for bb in unit.blocks() {
+        let mut label = String::new();
+        if !unit.is_entity() {
+            write!(label, "{}:\\l", escape(&bb.dump(&unit).to_string()))?;
+        }
+        for inst in unit.insts(bb) {
+            // Skip the implicit `halt` at the end of entities.
+            if unit.is_entity() && unit[inst].opcode() == Opcode::Halt {
+                continue;
+            }
+            write!(label, "    {}\\l", escape(&inst.dump(&unit).to_string()))?;
+        }
+        writeln!(out, "{}{}{} [label=\"{}\"];", indent, prefix, bb, label)?;
+    }
+    for bb in unit.blocks() {
+        let term = match unit.last_inst(bb) {
+            Some(term) => term,
+            None => continue,
+        };
+        let data = &unit[term];
+        match data.opcode() {
+            Opcode::BrCond => {
+                let labels = ["false", "true"];
+                for (to, label) in data.blocks().iter().zip(&labels) {
+                    writeln!(
+                        out,
+                        "{}{}{} -> {}{} [label=\"{}\"];",
+                        indent, prefix, bb, prefix, to, label
+                    )?;
+                }
+            }
+            _ => {
+                for to in data.blocks() {
+                    writeln!(out, "{}{}{} -> {}{};", indent, prefix, bb, prefix, to)?;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Escape a string for use within a quoted DOT label.
+fn escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// Quote a string as a DOT identifier.
+fn quote(s: &str) -> String {
+    format!("\"{}\"", escape(s))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::assembly::parse_module;
+
+    #[test]
+    fn two_blocks() {
+        let module = parse_module(
+            "
+            func @foo (i1 %c) void {
+            %entry:
+                br %c, %entry, %exit
+            %exit:
+                ret
+            }
+            ",
+        )
+        .unwrap();
+        let unit = module.units().next().unwrap();
+        let dot = unit.to_dot();
+        assert!(dot.starts_with("digraph \"@foo\" {"));
+        assert!(dot.contains("%entry:\\l"));
+        assert!(dot.contains("%exit:\\l"));
+        assert!(dot.contains("bb0 -> bb0 [label=\"false\"];"));
+        assert!(dot.contains("bb0 -> bb1 [label=\"true\"];"));
+
+        let dot = module.to_dot();
+        assert!(dot.contains("label = \"func @foo\";"));
+        assert!(dot.contains("u0_bb0 -> u0_bb1 [label=\"true\"];"));
+    }
+}
@@ -8,12 +8,17 @@
 use crate::{
     ir::{Block, ExtUnit, Inst, Unit, UnitBuilder, Value},
     ty::{array_ty, int_ty, pointer_ty, signal_ty, struct_ty, void_ty, Type},
-    value::{IntValue, TimeValue},
+    value::{EnumValue, IntValue, TimeValue},
 };
 use bitflags::bitflags;
 use std::borrow::Cow;
 
 /// A temporary object used to construct a single instruction.
+///
+/// Obtained through `UnitBuilder::ins()`. The instruction is inserted at the
+/// builder's current insertion point, as set by `append_to`, `insert_after`,
+/// and friends. Methods for instructions that produce a value return the
+/// result `Value`, all others return the `Inst`.
 pub struct InstBuilder<'a, 'b> {
     builder: &'b mut UnitBuilder<'a>,
     name: Option<String>,
@@ -46,6 +51,7 @@ impl<'a, 'b> InstBuilder<'a, 'b> {
         match ty.as_ref() {
             TimeType => self.const_time(TimeValue::zero()),
             IntType(w) => self.const_int(IntValue::zero(*w)),
+            EnumType(n) => self.const_enum(EnumValue::zero(*n)),
             ArrayType(l, ty) => {
                 let name = self.name.take();
                 let elem = self.const_zero(ty);
@@ -84,6 +90,41 @@ impl<'a, 'b> InstBuilder<'a, 'b> {
         self.inst_result(inst)
     }
 
+    pub fn const_enum(&mut self, value: impl Into<EnumValue>) -> Value {
+        let value = value.into();
+        let ty = value.ty();
+        let data = InstData::ConstEnum {
+            opcode: Opcode::ConstEnum,
+            imm: value,
+        };
+        let inst = self.build(data, ty);
+        self.inst_result(inst)
+    }
+
+    /// Construct an undefined value of a type.
+    ///
+    /// The value may take on any value of its type. It is useful to describe
+    /// don't-care inputs.
+    pub fn const_undef(&mut self, ty: Type) -> Value {
+        let data = InstData::Nullary {
+            opcode: Opcode::ConstUndef,
+        };
+        let inst = self.build(data, ty);
+        self.inst_result(inst)
+    }
+
+    /// Construct a poison value of a type.
+    ///
+    /// Poison marks the result of an operation with undefined behavior. Unlike
+    /// `undef`, it propagates through most operations that consume it.
+    pub fn const_poison(&mut self, ty: Type) -> Value {
+        let data = InstData::Nullary {
+            opcode: Opcode::ConstPoison,
+        };
+        let inst = self.build(data, ty);
+        self.inst_result(inst)
+    }
+
     pub fn alias(&mut self, x: Value) -> Value {
         let ty = self.value_type(x);
         let inst = self.build_unary(Opcode::Alias, ty, x);
@@ -157,6 +198,28 @@ impl<'a, 'b> InstBuilder<'a, 'b> {
         self.inst_result(inst)
     }
 
+    /// Construct an unsigned addition that also yields an overflow flag.
+    ///
+    /// The result is a `{iN, i1}` struct of the wrapped sum and the flag.
+    pub fn uaddo(&mut self, x: Value, y: Value) -> Value {
+        self.build_overflow(Opcode::Uaddo, x, y)
+    }
+
+    /// Construct a signed addition that also yields an overflow flag.
+    pub fn saddo(&mut self, x: Value, y: Value) -> Value {
+        self.build_overflow(Opcode::Saddo, x, y)
+    }
+
+    /// Construct an unsigned subtraction that also yields an overflow flag.
+    pub fn usubo(&mut self, x: Value, y: Value) -> Value {
+        self.build_overflow(Opcode::Usubo, x, y)
+    }
+
+    /// Construct a signed subtraction that also yields an overflow flag.
+    pub fn ssubo(&mut self, x: Value, y: Value) -> Value {
+        self.build_overflow(Opcode::Ssubo, x, y)
+    }
+
     pub fn and(&mut self, x: Value, y: Value) -> Value {
         let ty = self.value_type(x);
         let inst = self.build_binary(Opcode::And, ty, x, y);
@@ -293,6 +356,12 @@ impl<'a, 'b> InstBuilder<'a, 'b> {
         self.inst_result(inst)
     }
 
+    pub fn sel(&mut self, cond: Value, x: Value, y: Value) -> Value {
+        let ty = self.value_type(x);
+        let inst = self.build_ternary(Opcode::Sel, ty, cond, x, y);
+        self.inst_result(inst)
+    }
+
     pub fn reg(&mut self, x: Value, data: Vec<RegTrigger>) -> Inst {
         let mut args = vec![x];
         let mut modes = vec![];
@@ -400,6 +469,18 @@ impl<'a, 'b> InstBuilder<'a, 'b> {
         self.build(data, ty)
     }
 
+    /// Call a function through a value of function type.
+    pub fn call_indirect(&mut self, callee: Value, mut args: Vec<Value>) -> Inst {
+        let ty = self.value_type(callee);
+        let ty = ty.unwrap_func().1.clone();
+        args.insert(0, callee);
+        let data = InstData::Aggregate {
+            opcode: Opcode::CallIndirect,
+            args,
+        };
+        self.build(data, ty)
+    }
+
     pub fn inst(&mut self, unit: ExtUnit, mut inputs: Vec<Value>, outputs: Vec<Value>) -> Inst {
         let ins = inputs.len() as u16;
         inputs.extend(outputs);
@@ -412,6 +493,28 @@ impl<'a, 'b> InstBuilder<'a, 'b> {
         self.build(data, void_ty())
     }
 
+    /// Instantiate an entity and create signals for its outputs.
+    ///
+    /// This is a convenience function that creates a zero-initialized signal
+    /// for each output of `unit`, followed by the `inst` instruction that
+    /// connects them. Returns the signals in the order of the outputs. If a
+    /// name is assigned, it is given to each of the signals.
+    pub fn instantiate(&mut self, unit: ExtUnit, inputs: Vec<Value>) -> Vec<Value> {
+        let ext_sig = self.builder.extern_sig(unit);
+        let tys: Vec<_> = ext_sig.outputs().map(|arg| ext_sig.arg_type(arg)).collect();
+        let name = self.name.take();
+        let outputs: Vec<_> = tys
+            .iter()
+            .map(|ty| {
+                let init = self.const_zero(ty.unwrap_signal());
+                self.name = name.clone();
+                self.sig(init)
+            })
+            .collect();
+        self.inst(unit, inputs, outputs.clone());
+        outputs
+    }
+
     pub fn sig(&mut self, x: Value) -> Value {
         let ty = self.value_type(x);
         let ty = if ty.is_signal() { ty } else { signal_ty(ty) };
@@ -538,6 +641,13 @@ impl<'a, 'b> InstBuilder<'a, 'b> {
         self.build(data, ty)
     }
 
+    /// `a = opcode type x, y`, yielding `{type, i1}` with the overflow flag
+    fn build_overflow(&mut self, opcode: Opcode, x: Value, y: Value) -> Value {
+        let ty = struct_ty(vec![self.value_type(x), int_ty(1)]);
+        let inst = self.build_binary(opcode, ty, x, y);
+        self.inst_result(inst)
+    }
+
     /// `a = opcode type x, y, z`
     fn build_ternary(&mut self, opcode: Opcode, ty: Type, x: Value, y: Value, z: Value) -> Inst {
         let data = InstData::Ternary {
@@ -614,6 +724,8 @@ pub enum InstData {
     ConstInt { opcode: Opcode, imm: IntValue },
     /// `a = const time imm`
     ConstTime { opcode: Opcode, imm: TimeValue },
+    /// `a = const nN imm`
+    ConstEnum { opcode: Opcode, imm: EnumValue },
     /// `opcode imm, type x`
     Array {
         opcode: Opcode,
@@ -679,6 +791,7 @@ impl InstData {
         match *self {
             InstData::ConstInt { opcode, .. } => opcode,
             InstData::ConstTime { opcode, .. } => opcode,
+            InstData::ConstEnum { opcode, .. } => opcode,
             InstData::Array { opcode, .. } => opcode,
             InstData::Aggregate { opcode, .. } => opcode,
             InstData::Nullary { opcode, .. } => opcode,
@@ -701,6 +814,7 @@ impl InstData {
         match self {
             InstData::ConstInt { .. } => &[],
             InstData::ConstTime { .. } => &[],
+            InstData::ConstEnum { .. } => &[],
             InstData::Array { args, .. } => args,
             InstData::Aggregate { args, .. } => args,
             InstData::Nullary { .. } => &[],
@@ -734,6 +848,7 @@ impl InstData {
         match self {
             InstData::ConstInt { .. } => &mut [],
             InstData::ConstTime { .. } => &mut [],
+            InstData::ConstEnum { .. } => &mut [],
             InstData::Array { args, .. } => args,
             InstData::Aggregate { args, .. } => args,
             InstData::Nullary { .. } => &mut [],
@@ -766,6 +881,7 @@ impl InstData {
         match self {
             InstData::ConstInt { .. } => &[],
             InstData::ConstTime { .. } => &[],
+            InstData::ConstEnum { .. } => &[],
             InstData::Array { imms, .. } => imms,
             InstData::Aggregate { .. } => &[],
             InstData::Nullary { .. } => &[],
@@ -868,6 +984,7 @@ impl InstData {
         match self {
             InstData::ConstInt { .. } => &[],
             InstData::ConstTime { .. } => &[],
+            InstData::ConstEnum { .. } => &[],
             InstData::Array { .. } => &[],
             InstData::Aggregate { .. } => &[],
             InstData::Nullary { .. } => &[],
@@ -891,6 +1008,7 @@ impl InstData {
         match self {
             InstData::ConstInt { .. } => &mut [],
             InstData::ConstTime { .. } => &mut [],
+            InstData::ConstEnum { .. } => &mut [],
             InstData::Array { .. } => &mut [],
             InstData::Aggregate { .. } => &mut [],
             InstData::Nullary { .. } => &mut [],
@@ -975,6 +1093,14 @@ impl InstData {
         }
     }
 
+    /// Return the const enum constructed by this instruction.
+    pub fn get_const_enum(&self) -> Option<&EnumValue> {
+        match self {
+            InstData::ConstEnum { imm, .. } => Some(imm),
+            _ => None,
+        }
+    }
+
     /// Return the external unit being called or instantiated by this
     /// instruction.
     pub fn get_ext_unit(&self) -> Option<ExtUnit> {
@@ -1052,6 +1178,9 @@ pub struct RegTrigger {
 pub enum Opcode {
     ConstInt,
     ConstTime,
+    ConstEnum,
+    ConstUndef,
+    ConstPoison,
     Alias,
     ArrayUniform,
     Array,
@@ -1073,6 +1202,10 @@ pub enum Opcode {
     Udiv,
     Umod,
     Urem,
+    Uaddo,
+    Saddo,
+    Usubo,
+    Ssubo,
 
     Eq,
     Neq,
@@ -1088,6 +1221,7 @@ pub enum Opcode {
     Shl,
     Shr,
     Mux,
+    Sel,
     Reg,
     InsField,
     InsSlice,
@@ -1097,6 +1231,7 @@ pub enum Opcode {
     Del,
 
     Call,
+    CallIndirect,
     Inst,
 
     Sig,
@@ -1126,6 +1261,9 @@ impl std::fmt::Display for Opcode {
             match *self {
                 Opcode::ConstInt => "const",
                 Opcode::ConstTime => "const",
+                Opcode::ConstEnum => "const",
+                Opcode::ConstUndef => "const",
+                Opcode::ConstPoison => "const",
                 Opcode::Alias => "alias",
                 Opcode::ArrayUniform => "array",
                 Opcode::Array => "array",
@@ -1145,6 +1283,10 @@ impl std::fmt::Display for Opcode {
                 Opcode::Udiv => "udiv",
                 Opcode::Umod => "umod",
                 Opcode::Urem => "urem",
+                Opcode::Uaddo => "uaddo",
+                Opcode::Saddo => "saddo",
+                Opcode::Usubo => "usubo",
+                Opcode::Ssubo => "ssubo",
                 Opcode::Eq => "eq",
                 Opcode::Neq => "neq",
                 Opcode::Slt => "slt",
@@ -1158,6 +1300,7 @@ impl std::fmt::Display for Opcode {
                 Opcode::Shl => "shl",
                 Opcode::Shr => "shr",
                 Opcode::Mux => "mux",
+                Opcode::Sel => "sel",
                 Opcode::Reg => "reg",
                 Opcode::InsField => "insf",
                 Opcode::InsSlice => "inss",
@@ -1166,6 +1309,7 @@ impl std::fmt::Display for Opcode {
                 Opcode::Con => "con",
                 Opcode::Del => "del",
                 Opcode::Call => "call",
+                Opcode::CallIndirect => "call",
                 Opcode::Inst => "inst",
                 Opcode::Sig => "sig",
                 Opcode::Drv => "drv",
@@ -1203,6 +1347,10 @@ impl Opcode {
             Opcode::Del => UnitFlags::ENTITY,
             Opcode::Reg => UnitFlags::ENTITY,
             Opcode::Inst => UnitFlags::ENTITY,
+            Opcode::Sig => UnitFlags::PROCESS | UnitFlags::ENTITY,
+            Opcode::Prb => UnitFlags::PROCESS | UnitFlags::ENTITY,
+            Opcode::Drv => UnitFlags::PROCESS | UnitFlags::ENTITY,
+            Opcode::DrvCond => UnitFlags::PROCESS | UnitFlags::ENTITY,
             _ => UnitFlags::ALL,
         }
     }
@@ -1227,6 +1375,9 @@ impl Opcode {
         match self {
             Opcode::ConstInt => true,
             Opcode::ConstTime => true,
+            Opcode::ConstEnum => true,
+            Opcode::ConstUndef => true,
+            Opcode::ConstPoison => true,
             _ => false,
         }
     }
@@ -1253,6 +1404,24 @@ impl Opcode {
         }
     }
 
+    /// Check if this instruction produces a value.
+    ///
+    /// Calls produce a value unless the callee returns `void`; use
+    /// `Unit::get_inst_type` to account for that.
+    pub fn produces_value(self) -> bool {
+        !self.is_terminator()
+            && !matches!(
+                self,
+                Opcode::Con
+                    | Opcode::Del
+                    | Opcode::Inst
+                    | Opcode::Drv
+                    | Opcode::DrvCond
+                    | Opcode::Reg
+                    | Opcode::St
+            )
+    }
+
     /// Check if this is a return instruction.
     pub fn is_return(self) -> bool {
         match self {
@@ -1268,6 +1437,23 @@ impl Opcode {
             _ => false,
         }
     }
+
+    /// Check if the two operands of this instruction can be swapped.
+    pub fn is_commutative(self) -> bool {
+        match self {
+            Opcode::Add
+            | Opcode::Umul
+            | Opcode::Smul
+            | Opcode::And
+            | Opcode::Or
+            | Opcode::Xor
+            | Opcode::Uaddo
+            | Opcode::Saddo
+            | Opcode::Eq
+            | Opcode::Neq => true,
+            _ => false,
+        }
+    }
 }
 
 impl Inst {
@@ -1367,6 +1553,13 @@ impl std::fmt::Display for InstDumper<'_> {
             match data {
                 InstData::ConstInt { imm, .. } => write!(f, " {}", imm.value)?,
                 InstData::ConstTime { imm, .. } => write!(f, " {}", imm)?,
+                InstData::ConstEnum { imm, .. } => write!(f, " {}", imm.value)?,
+                InstData::Nullary {
+                    opcode: Opcode::ConstUndef,
+                } => write!(f, " undef")?,
+                InstData::Nullary {
+                    opcode: Opcode::ConstPoison,
+                } => write!(f, " poison")?,
                 InstData::Array { imms, .. } => write!(f, ", {}", imms[0])?,
                 _ => (),
             }
@@ -1384,3 +1577,246 @@ fn with_unpacked_sigptr(ty: Type, f: impl FnOnce(Type) -> Type) -> Type {
         f(ty)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        assembly::write_module_string,
+        ir::{Module, Opcode, Signature, UnitBuilder, UnitData, UnitKind, UnitName},
+        ty::{int_ty, signal_ty, void_ty},
+    };
+
+    #[test]
+    fn build_function() {
+        let mut sig = Signature::new();
+        let a = sig.add_input(int_ty(32));
+        let b = sig.add_input(int_ty(32));
+        sig.set_return_type(int_ty(32));
+        let mut data = UnitData::new(UnitKind::Function, UnitName::global("max"), sig);
+        let mut builder = UnitBuilder::new_anonymous(&mut data);
+        let a = builder.arg_value(a);
+        let b = builder.arg_value(b);
+        builder.set_name(a, "a".to_owned());
+        builder.set_name(b, "b".to_owned());
+
+        let entry = builder.named_block("entry");
+        let take_a = builder.named_block("take_a");
+        let take_b = builder.named_block("take_b");
+        builder.append_to(entry);
+        let cond = builder.ins().name("lt").ult(a, b);
+        builder.ins().br_cond(cond, take_a, take_b);
+        builder.append_to(take_a);
+        builder.ins().ret_value(a);
+        builder.append_to(take_b);
+        builder.ins().ret_value(b);
+
+        let mut module = Module::new();
+        module.add_unit(data);
+        module.verify();
+        assert_eq!(
+            write_module_string(&module),
+            "func @max (i32 %a, i32 %b) i32 {\n\
+             entry:\n    \
+                 %lt = ult i32 %a, %b\n    \
+                 br %lt, %take_a, %take_b\n\
+             take_a:\n    \
+                 ret i32 %a\n\
+             take_b:\n    \
+                 ret i32 %b\n\
+             }\n"
+        );
+    }
+
+    #[test]
+    fn instantiate_entity() {
+        let mut sub_sig = Signature::new();
+        sub_sig.add_input(signal_ty(int_ty(1)));
+        sub_sig.add_output(signal_ty(int_ty(8)));
+        sub_sig.add_output(signal_ty(int_ty(32)));
+
+        let mut sig = Signature::new();
+        let clk = sig.add_input(signal_ty(int_ty(1)));
+        let mut data = UnitData::new(UnitKind::Entity, UnitName::global("top"), sig);
+        let mut builder = UnitBuilder::new_anonymous(&mut data);
+        let clk = builder.arg_value(clk);
+        builder.set_name(clk, "clk".to_owned());
+        let sub = builder.add_extern(UnitName::global("sub"), sub_sig.clone());
+        builder.insert_at_beginning();
+        let outputs = builder.ins().instantiate(sub, vec![clk]);
+        let tys: Vec<_> = outputs.iter().map(|&v| builder.value_type(v)).collect();
+        assert_eq!(tys, vec![signal_ty(int_ty(8)), signal_ty(int_ty(32))]);
+        let mut module = Module::new();
+        module.add_unit(data);
+        assert_eq!(
+            write_module_string(&module),
+            "entity @top (i1$ %clk) -> () {\n    \
+                 %0 = const i8 0\n    \
+                 %1 = sig i8 %0\n    \
+                 %2 = const i32 0\n    \
+                 %3 = sig i32 %2\n    \
+                 inst @sub (i1$ %clk) -> (i8$ %1, i32$ %3)\n\
+             }\n"
+        );
+
+        // A name is given to the output signals.
+        let mut sig = Signature::new();
+        let clk = sig.add_input(signal_ty(int_ty(1)));
+        let mut data = UnitData::new(UnitKind::Entity, UnitName::global("top"), sig);
+        let mut builder = UnitBuilder::new_anonymous(&mut data);
+        let clk = builder.arg_value(clk);
+        builder.set_name(clk, "clk".to_owned());
+        let sub = builder.add_extern(UnitName::global("sub"), sub_sig);
+        builder.insert_at_beginning();
+        builder.ins().name("q").instantiate(sub, vec![clk]);
+        let mut module = Module::new();
+        module.add_unit(data);
+        assert_eq!(
+            write_module_string(&module),
+            "entity @top (i1$ %clk) -> () {\n    \
+                 %0 = const i8 0\n    \
+                 %q = sig i8 %0\n    \
+                 %1 = const i32 0\n    \
+                 %q1 = sig i32 %1\n    \
+                 inst @sub (i1$ %clk) -> (i8$ %q, i32$ %q1)\n\
+             }\n"
+        );
+    }
+
+    #[test]
+    fn replace_operands() {
+        let mut sig = Signature::new();
+        let a = sig.add_input(int_ty(32));
+        let b = sig.add_input(int_ty(32));
+        let c = sig.add_input(int_ty(1));
+        sig.set_return_type(void_ty());
+        let mut data = UnitData::new(UnitKind::Function, UnitName::global("foo"), sig);
+        let mut builder = UnitBuilder::new_anonymous(&mut data);
+        let a = builder.arg_value(a);
+        let b = builder.arg_value(b);
+        let c = builder.arg_value(c);
+
+        let mut ext_sig = Signature::new();
+        ext_sig.add_input(int_ty(32));
+        ext_sig.add_input(int_ty(32));
+        ext_sig.set_return_type(void_ty());
+        let ext = builder.add_extern(UnitName::global("bar"), ext_sig);
+
+        let entry = builder.block();
+        let exit = builder.block();
+        builder.append_to(entry);
+        let sum = builder.ins().add(a, a);
+        let add = builder.value_inst(sum);
+        let call = builder.ins().call(ext, vec![a, sum]);
+        let br = builder.ins().br_cond(c, exit, exit);
+        builder.append_to(exit);
+        builder.ins().ret();
+
+        assert_eq!(builder[add].args(), &[a, a]);
+        assert_eq!(builder.replace_value_within_inst(a, b, add), 2);
+        assert_eq!(builder[add].args(), &[b, b]);
+        assert_eq!(builder.replace_value_within_inst(a, b, call), 1);
+        assert_eq!(builder[call].args(), &[b, sum]);
+        assert_eq!(builder.replace_use(c, a), 1);
+        assert_eq!(builder[br].args(), &[a]);
+        assert_eq!(builder[br].blocks(), &[exit, exit]);
+        assert!(!builder.uses(b).is_empty());
+        assert_eq!(builder.replace_use(b, a), 3);
+    }
+
+    #[test]
+    fn result_types() {
+        let module = crate::assembly::parse_module(
+            "declare @g () void
+            declare @h (i32) i32
+
+            func @f (i32 %a, i32* %p, (i32) i32 %fp, (i32) void %fv) i32 {
+            %entry:
+                %b = add i32 %a, %a
+                %c = eq i32 %a, %b
+                %d = ld i32* %p
+                st i32* %p, %d
+                call void @g ()
+                %e = call i32 @h (i32 %a)
+                %f = call i32 %fp (i32 %a)
+                call void %fv (i32 %a)
+                br %c, %entry, %exit
+            %exit:
+                ret i32 %b
+            }
+
+            proc @q (i8$ %s) -> () {
+            %entry:
+                %t = const time 1ns
+                %v = prb i8$ %s
+                drv i8$ %s, %v, %t
+                wait %entry for %t
+            }
+
+            entity @r (i1$ %clk, i8 %d) -> (i8$ %s) {
+                %clkp = prb i1$ %clk
+                reg i8$ %s, [%d, rise %clkp]
+            }",
+        )
+        .unwrap();
+        let mut seen = vec![];
+        for unit in module.units() {
+            for inst in unit.all_insts() {
+                let opcode = unit[inst].opcode();
+                let ty = unit.get_inst_type(inst);
+                // Calls produce a value only if the callee returns one.
+                let returns = match opcode {
+                    Opcode::Call => {
+                        let sig = unit.extern_sig(unit[inst].get_ext_unit().unwrap());
+                        sig.has_return_type() && !sig.return_type().is_void()
+                    }
+                    Opcode::CallIndirect => {
+                        let callee = unit.value_type(unit[inst].args()[0]);
+                        !callee.unwrap_func().1.is_void()
+                    }
+                    _ => true,
+                };
+                assert_eq!(
+                    opcode.produces_value() && returns,
+                    ty.is_some(),
+                    "{}",
+                    opcode
+                );
+                let args = unit
+                    .inst_arg_types(inst)
+                    .iter()
+                    .map(|ty| ty.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                seen.push(format!(
+                    "{} {}: {}",
+                    opcode,
+                    ty.map(|ty| ty.to_string())
+                        .unwrap_or_else(|| "-".to_owned()),
+                    args
+                ));
+            }
+        }
+        assert_eq!(
+            seen,
+            vec![
+                "add i32: i32, i32",
+                "eq i1: i32, i32",
+                "ld i32: i32*",
+                "st -: i32*, i32",
+                "call -: ",
+                "call i32: i32",
+                "call i32: (i32) i32, i32",
+                "call -: (i32) void, i32",
+                "br -: i1",
+                "ret -: i32",
+                "const time: ",
+                "prb i8: i8$",
+                "drv -: i8$, i8, time",
+                "wait -: time",
+                "prb i1: i1$",
+                "reg -: i8$, i8, i1",
+                "halt -: ",
+            ]
+        );
+    }
+}
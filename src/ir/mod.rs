@@ -9,6 +9,7 @@ use crate::{impl_table_key, ty::Type};
 
 mod cfg;
 mod dfg;
+mod dot;
 mod inst;
 mod layout;
 mod module;
@@ -92,6 +93,19 @@ impl ValueData {
     }
 }
 
+/// The kind of definition a value originates from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ValueKind {
+    /// The value is an argument of the `Function`, `Process`, or `Entity`.
+    Arg(Arg),
+    /// The value is the result of a constant instruction.
+    Const(Inst),
+    /// The value is the result of any other instruction.
+    Inst(Inst),
+    /// The value is a placeholder that has not been replaced yet.
+    Placeholder,
+}
+
 impl Default for ValueData {
     fn default() -> ValueData {
         ValueData::Invalid
@@ -126,6 +140,25 @@ impl Default for ExtUnitData {
     }
 }
 
+/// A range of bytes in the input file an object was parsed from.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Span {
+    /// The byte offset of the first character.
+    pub start: usize,
+    /// The byte offset one past the last character.
+    pub end: usize,
+}
+
+/// The comments attached to an instruction in the input file.
+#[derive(Debug, Serialize, Deserialize, Clone, Default, PartialEq, Eq)]
+pub struct Comments {
+    /// The comment lines preceding the instruction, without the leading `;`.
+    pub leading: Vec<String>,
+    /// The comment on the same line as the instruction, without the leading
+    /// `;`.
+    pub trailing: Option<String>,
+}
+
 /// Any one of the table keys in this module.
 #[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
 pub enum AnyObject {
@@ -9,12 +9,17 @@
 
 use crate::{
     impl_table_key,
-    ir::{ExtUnit, Signature, Unit, UnitBuilder, UnitData, UnitName},
+    ir::{
+        AnyObject, Block, ExtUnit, Inst, InstData, Opcode, Signature, Span, Unit, UnitBuilder,
+        UnitData, UnitKind, UnitName, Value, ValueData, ValueKind,
+    },
     table::{PrimaryTable, TableKey},
-    verifier::Verifier,
+    ty::{array_ty, entity_ty, func_ty, pointer_ty, signal_ty, struct_ty, Type, TypeKind},
+    value::{ArrayValue, EnumValue, IntValue, StructValue},
+    verifier::{Check, TypeError, Verifier, VerifyError, VerifyReport},
 };
 use rayon::prelude::*;
-use std::collections::{BTreeSet, HashMap};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 
 /// A module.
 ///
@@ -30,8 +35,13 @@ pub struct Module {
     pub(crate) decls: PrimaryTable<DeclId, DeclData>,
     /// The order of declarations in the module.
     decl_order: BTreeSet<DeclId>,
+    /// The named types declared in this module.
+    types: BTreeMap<String, Type>,
+    /// The named constants declared in this module.
+    consts: BTreeMap<String, crate::Value>,
     /// The local link table. Maps an external unit declared within a unit to a
     /// unit in the module.
+    #[serde(skip)]
     link_table: Option<HashMap<(UnitId, ExtUnit), LinkedUnit>>,
     /// The location of units in the input file. If the module was read from a
     /// file, this table *may* contain additional hints on the byte offsets
@@ -47,16 +57,71 @@ impl Module {
             unit_order: BTreeSet::new(),
             decls: PrimaryTable::new(),
             decl_order: BTreeSet::new(),
+            types: BTreeMap::new(),
+            consts: BTreeMap::new(),
             link_table: None,
             location_hints: Default::default(),
         }
     }
 
+    /// Emit the module in a canonical textual form.
+    ///
+    /// The output is assembly that can be parsed again, but with units and
+    /// declarations sorted by name, all local values and blocks numbered
+    /// densely in order of appearance, named types spelled out in full, and
+    /// comments omitted. Two modules that only differ in these aspects yield
+    /// the same string, which makes the output suitable for comparisons.
+    pub fn to_canonical_string(&self) -> String {
+        let mut asm = vec![];
+        crate::assembly::Writer::canonical(&mut asm)
+            .write_module(self)
+            .unwrap();
+        String::from_utf8(asm).expect("writer should emit proper utf8")
+    }
+
     /// Dump the module in human-readable form.
     pub fn dump(&self) -> ModuleDumper {
         ModuleDumper(self)
     }
 
+    /// Serialize the module as JSON.
+    ///
+    /// Values, blocks, and instructions are represented by their ids, such
+    /// that references survive a round trip through `from_json`. The link
+    /// table is not serialized; call `link()` after deserialization. Requires
+    /// the `serde` feature.
+    #[cfg(feature = "serde")]
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("module should be serializable as JSON")
+    }
+
+    /// Deserialize a module from JSON produced by `to_json`. Requires the
+    /// `serde` feature.
+    #[cfg(feature = "serde")]
+    pub fn from_json(input: impl AsRef<str>) -> Result<Self, String> {
+        serde_json::from_str(input.as_ref()).map_err(|e| format!("{}", e))
+    }
+
+    /// Parse a module from assembly read from `input`.
+    ///
+    /// Errors that occur while reading are reported as `failed to read
+    /// input: ...`, to set them apart from errors in the assembly itself.
+    pub fn from_reader(mut input: impl std::io::Read) -> Result<Self, String> {
+        let mut contents = String::new();
+        input
+            .read_to_string(&mut contents)
+            .map_err(|e| format!("failed to read input: {}", e))?;
+        crate::assembly::parse_module(contents).map_err(|e| e.to_string())
+    }
+
+    /// Parse a module from the assembly file at `path`.
+    pub fn from_file(path: impl AsRef<std::path::Path>) -> Result<Self, String> {
+        let path = path.as_ref();
+        let file = std::fs::File::open(path)
+            .map_err(|e| format!("failed to read input: {}: {}", path.display(), e))?;
+        Self::from_reader(file)
+    }
+
     /// Add a unit to the module.
     pub fn add_unit(&mut self, data: UnitData) -> UnitId {
         let unit = self.units.add(data);
@@ -71,6 +136,163 @@ impl Module {
         self.unit_order.remove(&unit);
     }
 
+    /// Find the unit defined under a name.
+    pub fn lookup_unit(&self, name: &UnitName) -> Option<UnitId> {
+        self.units()
+            .find(|unit| unit.name() == name)
+            .map(|unit| unit.id())
+    }
+
+    /// Remove the unit defined under a name from the module.
+    ///
+    /// Returns true if a unit was removed. References to the unit from other
+    /// units are left in place and fail to link unless a declaration or
+    /// another definition of the same name is added.
+    pub fn remove_unit_named(&mut self, name: &UnitName) -> bool {
+        match self.lookup_unit(name) {
+            Some(unit) => {
+                self.remove_unit(unit);
+                self.link_table = None;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Rename a unit and update all calls and instances that refer to it.
+    ///
+    /// Fails without modifying the module if no unit is defined under the old
+    /// name, or if a unit is already defined under the new name.
+    pub fn rename_unit(&mut self, old: &UnitName, new: UnitName) -> Result<(), RenameError> {
+        let unit = match self.lookup_unit(old) {
+            Some(unit) => unit,
+            None => return Err(RenameError::NotFound(old.clone())),
+        };
+        if self.lookup_unit(&new).is_some() {
+            return Err(RenameError::Exists(new));
+        }
+        self.units[unit].name = new.clone();
+        for data in self.units.values_mut() {
+            for ext_unit in data.dfg.ext_units.values_mut() {
+                if &ext_unit.name == old {
+                    ext_unit.name = new.clone();
+                }
+            }
+        }
+        self.link_table = None;
+        Ok(())
+    }
+
+    /// Rewrite all types in the module.
+    ///
+    /// Applies `f` to the types of all values and arguments, the signatures of
+    /// units, declarations, and external units, and the named types and
+    /// constants. Types nested within other types, such as the `i8` in `i8$`,
+    /// are mapped first, and `f` is then applied to the rebuilt outer type.
+    ///
+    /// Integer and enum constants are recreated with the same value in their
+    /// new type, without sign extension. Enum constants may also become integer
+    /// constants. Aggregate constants are recreated element by element. The
+    /// lengths in uniform arrays and slice instructions are updated to match
+    /// the new types. Other immediates, such as slice offsets, are kept as they
+    /// are.
+    ///
+    /// Fails without modifying the module if a constant does not fit its new
+    /// type.
+    pub fn map_types(&mut self, f: impl Fn(&Type) -> Type) -> Result<(), String> {
+        let mut cache = HashMap::new();
+        let mut map = |ty: &Type| map_type(ty, &f, &mut cache);
+
+        // Convert the constants first, such that the module is left untouched
+        // if one of them does not fit.
+        let mut consts = BTreeMap::new();
+        for (name, value) in &self.consts {
+            consts.insert(name.clone(), convert_const(value, &map(&value.ty()))?);
+        }
+        let mut const_insts = vec![];
+        for unit in self.units() {
+            for inst in unit.all_insts() {
+                if !unit[inst].opcode().is_const() {
+                    continue;
+                }
+                let value = match unit.get_const(unit.inst_result(inst)) {
+                    Some(value) => value,
+                    None => continue,
+                };
+                let value = convert_const(&value, &map(&unit.inst_type(inst)))?;
+                let data = match value {
+                    crate::Value::Int(imm) => InstData::ConstInt {
+                        opcode: Opcode::ConstInt,
+                        imm,
+                    },
+                    crate::Value::Enum(imm) => InstData::ConstEnum {
+                        opcode: Opcode::ConstEnum,
+                        imm,
+                    },
+                    crate::Value::Time(imm) => InstData::ConstTime {
+                        opcode: Opcode::ConstTime,
+                        imm,
+                    },
+                    _ => continue,
+                };
+                const_insts.push((unit.id(), inst, data));
+            }
+        }
+        self.consts = consts;
+        for (id, inst, data) in const_insts {
+            self.units[id].dfg.insts[inst] = data;
+        }
+
+        for ty in self.types.values_mut() {
+            *ty = map(ty);
+        }
+        for decl in self.decls.values_mut() {
+            decl.sig.map_types(&mut map);
+        }
+        let ids: Vec<_> = self.units().map(|unit| unit.id()).collect();
+        for id in ids {
+            let data = &mut self.units[id];
+            data.sig.map_types(&mut map);
+            for ext_unit in data.dfg.ext_units.values_mut() {
+                ext_unit.sig.map_types(&mut map);
+            }
+            for value in data.dfg.values.values_mut() {
+                match value {
+                    ValueData::Inst { ty, .. }
+                    | ValueData::Arg { ty, .. }
+                    | ValueData::Placeholder { ty } => *ty = map(ty),
+                    ValueData::Invalid => (),
+                }
+            }
+
+            // Update the lengths that are implied by the types.
+            let unit = Unit::new(id, &self.units[id]);
+            let mut lengths = vec![];
+            for inst in unit.all_insts() {
+                let data = &unit[inst];
+                let length = match data.opcode() {
+                    Opcode::ArrayUniform => unit.inst_type(inst).len(),
+                    Opcode::ExtSlice => match *unit.inst_type(inst) {
+                        TypeKind::PointerType(ref ty) | TypeKind::SignalType(ref ty) => ty.len(),
+                        ref ty => ty.len(),
+                    },
+                    Opcode::InsSlice => unit.value_type(data.args()[1]).len(),
+                    _ => continue,
+                };
+                lengths.push((inst, length));
+            }
+            for (inst, length) in lengths {
+                match &mut self.units[id].dfg.insts[inst] {
+                    InstData::Array { imms, .. } => imms[0] = length,
+                    InstData::InsExt { imms, .. } => imms[1] = length,
+                    _ => unreachable!(),
+                }
+            }
+        }
+        self.link_table = None;
+        Ok(())
+    }
+
     /// Declare an external unit.
     pub fn declare(&mut self, name: UnitName, sig: Signature) -> DeclId {
         self.add_decl(DeclData {
@@ -94,6 +316,43 @@ impl Module {
         self.decl_order.remove(&decl);
     }
 
+    /// Declare a named type, such as `type @pixel = {i32, i32, i32}`.
+    ///
+    /// Replaces any previous type declared under the same name.
+    pub fn add_type(&mut self, name: impl Into<String>, ty: Type) {
+        self.types.insert(name.into(), ty);
+    }
+
+    /// Get the type declared under a name.
+    pub fn get_type(&self, name: &str) -> Option<&Type> {
+        self.types.get(name)
+    }
+
+    /// Return an iterator over the named types in this module.
+    pub fn types<'a>(&'a self) -> impl Iterator<Item = (&'a str, &'a Type)> + 'a {
+        self.types.iter().map(|(name, ty)| (name.as_str(), ty))
+    }
+
+    /// Declare a named constant, such as `const @max = i32 255`.
+    ///
+    /// Replaces any previous constant declared under the same name. Only
+    /// integer, time, and enum values can be declared as constants.
+    pub fn add_const(&mut self, name: impl Into<String>, value: crate::Value) {
+        self.consts.insert(name.into(), value);
+    }
+
+    /// Get the constant declared under a name.
+    pub fn get_const(&self, name: &str) -> Option<&crate::Value> {
+        self.consts.get(name)
+    }
+
+    /// Return an iterator over the named constants in this module.
+    pub fn consts<'a>(&'a self) -> impl Iterator<Item = (&'a str, &'a crate::Value)> + 'a {
+        self.consts
+            .iter()
+            .map(|(name, value)| (name.as_str(), value))
+    }
+
     /// Return an iterator over the units in this module.
     pub fn units<'a>(&'a self) -> impl Iterator<Item = Unit<'a>> + 'a {
         self.unit_order.iter().map(move |&id| self.unit(id))
@@ -135,6 +394,75 @@ impl Module {
         self.units().filter(|unit| unit.is_entity())
     }
 
+    /// Return a mutable iterator over the functions in this module.
+    pub fn functions_mut<'a>(&'a mut self) -> impl Iterator<Item = UnitBuilder<'a>> + 'a {
+        self.units_mut().filter(|unit| unit.is_function())
+    }
+
+    /// Return a mutable iterator over the processes in this module.
+    pub fn processes_mut<'a>(&'a mut self) -> impl Iterator<Item = UnitBuilder<'a>> + 'a {
+        self.units_mut().filter(|unit| unit.is_process())
+    }
+
+    /// Return a mutable iterator over the entities in this module.
+    pub fn entities_mut<'a>(&'a mut self) -> impl Iterator<Item = UnitBuilder<'a>> + 'a {
+        self.units_mut().filter(|unit| unit.is_entity())
+    }
+
+    /// Return an iterator over all instructions in this module.
+    ///
+    /// Yields the instructions of each unit in layout order, together with the
+    /// unit they belong to.
+    pub fn all_insts<'a>(&'a self) -> impl Iterator<Item = (UnitId, Inst)> + 'a {
+        self.units()
+            .flat_map(|unit| unit.all_insts().map(move |inst| (unit.id(), inst)))
+    }
+
+    /// Return the name of a value, instruction, block, or argument in a unit.
+    ///
+    /// See `Unit::name_of` for details.
+    pub fn name_of(&self, unit: UnitId, obj: impl Into<AnyObject>) -> Option<&str> {
+        Unit::new(unit, &self[unit]).name_of(obj)
+    }
+
+    /// Set the name of a value, instruction, block, or argument in a unit.
+    ///
+    /// See `UnitBuilder::set_name_of` for details.
+    pub fn set_name_of(&mut self, unit: UnitId, obj: impl Into<AnyObject>, name: String) {
+        self.unit_mut(unit).set_name_of(obj, name)
+    }
+
+    /// Collect statistics about the units and instructions in the module.
+    pub fn statistics(&self) -> ModuleStats {
+        let mut stats = ModuleStats::default();
+        for unit in self.units() {
+            match unit.kind() {
+                UnitKind::Function => stats.functions += 1,
+                UnitKind::Process => stats.processes += 1,
+                UnitKind::Entity => stats.entities += 1,
+            }
+            stats.max_blocks = stats.max_blocks.max(unit.blocks().count());
+            for arg in unit.args() {
+                stats.max_int_width = stats
+                    .max_int_width
+                    .max(max_int_width(&unit.value_type(arg)));
+            }
+            for inst in unit.all_insts() {
+                // The terminator of an entity is implicit.
+                if unit.is_entity() && unit[inst].opcode().is_terminator() {
+                    continue;
+                }
+                stats.insts += 1;
+                *stats.opcodes.entry(unit[inst].opcode()).or_insert(0) += 1;
+                if let Some(value) = unit.get_inst_result(inst) {
+                    let width = max_int_width(&unit.value_type(value));
+                    stats.max_int_width = stats.max_int_width.max(width);
+                }
+            }
+        }
+        stats
+    }
+
     /// Return an iterator over the external unit declarations in this module.
     pub fn decls<'a>(&'a self) -> impl Iterator<Item = DeclId> + 'a {
         self.decl_order.iter().cloned()
@@ -175,6 +503,29 @@ impl Module {
         self.symbols().filter(|&(name, ..)| name.is_global())
     }
 
+    /// Return the names referenced by instructions in the module, but not
+    /// defined by any of its units.
+    ///
+    /// These are the symbols another module has to provide when linking,
+    /// including those that are only declared in this module. Each name is
+    /// listed once, in the order it is first referenced.
+    pub fn external_symbols(&self) -> Vec<UnitName> {
+        let defined: HashSet<&UnitName> = self.units().map(|unit| unit.name()).collect();
+        let mut seen = HashSet::new();
+        let mut names = vec![];
+        for unit in self.units() {
+            for inst in unit.all_insts() {
+                if let Some(ext) = unit[inst].get_ext_unit() {
+                    let name = &unit[ext].name;
+                    if !defined.contains(name) && seen.insert(name.clone()) {
+                        names.push(name.clone());
+                    }
+                }
+            }
+        }
+        names
+    }
+
     /// Check whether the module is internally linked.
     ///
     /// Adding or modifying a unit invalidates the linkage within the module.
@@ -236,6 +587,72 @@ impl Module {
         self.link_table = Some(linked);
     }
 
+    /// Move the units and declarations of another module into this module.
+    ///
+    /// Declarations in either module that are defined by a unit in the other
+    /// module are dropped, such that references to them resolve to the actual
+    /// definition once the module is linked again. Named types and constants
+    /// declared in both modules are kept once. Fails without modifying the
+    /// module if both modules define a unit with the same name, or declare a
+    /// named type or constant differently.
+    pub fn merge(&mut self, mut other: Module) -> Result<(), LinkError> {
+        let defined: HashSet<UnitName> = self.units().map(|u| u.name().clone()).collect();
+        let duplicates: Vec<UnitName> = other
+            .units()
+            .map(|u| u.name().clone())
+            .filter(|name| defined.contains(name))
+            .collect();
+        if !duplicates.is_empty() {
+            return Err(LinkError::DuplicateUnits(duplicates));
+        }
+        let conflicts: Vec<String> = other
+            .types()
+            .filter(|&(name, ty)| self.get_type(name).map(|t| t != ty).unwrap_or(false))
+            .map(|(name, _)| name.to_string())
+            .collect();
+        if !conflicts.is_empty() {
+            return Err(LinkError::ConflictingTypes(conflicts));
+        }
+        let conflicts: Vec<String> = other
+            .consts()
+            .filter(|&(name, value)| self.get_const(name).map(|v| v != value).unwrap_or(false))
+            .map(|(name, _)| name.to_string())
+            .collect();
+        if !conflicts.is_empty() {
+            return Err(LinkError::ConflictingConsts(conflicts));
+        }
+
+        // Drop our declarations that the other module defines.
+        let incoming: HashSet<UnitName> = other.units().map(|u| u.name().clone()).collect();
+        let resolved: Vec<DeclId> = self
+            .decls()
+            .filter(|&decl| incoming.contains(&self[decl].name))
+            .collect();
+        for decl in resolved {
+            self.remove_decl(decl);
+        }
+
+        // Move over the units and the remaining declarations.
+        let declared: HashSet<UnitName> = self.decls().map(|d| self[d].name.clone()).collect();
+        for unit in std::mem::take(&mut other.unit_order) {
+            let data = other.units.storage.remove(&unit.index()).unwrap();
+            self.add_unit(data);
+        }
+        for decl in std::mem::take(&mut other.decl_order) {
+            let data = other.decls.storage.remove(&decl.index()).unwrap();
+            if !defined.contains(&data.name) && !declared.contains(&data.name) {
+                self.add_decl(data);
+            }
+        }
+        for (name, ty) in other.types {
+            self.types.entry(name).or_insert(ty);
+        }
+        for (name, value) in other.consts {
+            self.consts.entry(name).or_insert(value);
+        }
+        Ok(())
+    }
+
     /// Panic if the module is not well-formed.
     pub fn verify(&self) {
         let mut verifier = Verifier::new();
@@ -254,6 +671,43 @@ impl Module {
         }
     }
 
+    /// Check that the operand and result types of all instructions line up.
+    ///
+    /// In contrast to `verify()`, this does not panic, and only reports
+    /// mismatching types.
+    pub fn typecheck(&self) -> Result<(), Vec<TypeError>> {
+        let mut verifier = Verifier::types_only();
+        verifier.verify_module(self);
+        verifier
+            .finish()
+            .map_err(|errs| errs.0.into_iter().map(TypeError::from).collect())
+    }
+
+    /// Check that the module is well-formed.
+    ///
+    /// In contrast to `verify()`, this does not panic, but collects all errors
+    /// found by checking the block terminators, the types of all instructions,
+    /// the instructions allowed in each kind of unit, and that all values are
+    /// defined before their use.
+    pub fn try_verify(&self) -> Result<(), Vec<VerifyError>> {
+        let mut verifier = Verifier::new();
+        verifier.verify_module(self);
+        verifier.finish_typed()
+    }
+
+    /// Run all available checks on the module and report the findings.
+    ///
+    /// In contrast to `verify()`, this does not panic but returns a report of
+    /// all errors and warnings, grouped by unit and severity.
+    pub fn verify_all(&self) -> VerifyReport {
+        self.verify_with(&crate::verifier::default_checks())
+    }
+
+    /// Run a custom set of checks on the module and report the findings.
+    pub fn verify_with(&self, checks: &[&dyn Check]) -> VerifyReport {
+        VerifyReport::run(self, checks)
+    }
+
     /// Lookup what an external unit links to.
     ///
     /// The module must be linked for this to work.
@@ -278,6 +732,188 @@ impl Module {
     pub fn location_hint(&self, mod_unit: UnitId) -> Option<usize> {
         self.location_hints.get(&mod_unit).cloned()
     }
+
+    /// Get the span of an instruction within a unit.
+    ///
+    /// Returns the range of bytes the instruction occupies in the input file,
+    /// or None if there is no hint for the instruction.
+    pub fn span_of(&self, mod_unit: UnitId, inst: Inst) -> Option<Span> {
+        self.unit(mod_unit).span_hint(inst)
+    }
+
+    /// Determine what kind of definition a value in a unit originates from.
+    ///
+    /// Values are local to the unit they are defined in, which is therefore
+    /// always `mod_unit`.
+    pub fn classify(&self, mod_unit: UnitId, value: Value) -> ValueKind {
+        self.unit(mod_unit).value_kind(value)
+    }
+
+    /// Check whether two modules are structurally identical.
+    ///
+    /// Units and declarations are matched up by name. Two units are equal if
+    /// their blocks contain the same instructions with the same types and
+    /// operands, regardless of how values and blocks are numbered or named.
+    /// The named types and constants of the modules must be equal as well.
+    pub fn structural_eq(&self, other: &Module) -> bool {
+        self.structural_key() == other.structural_key()
+    }
+
+    /// Compute a hash of the structure of the module.
+    ///
+    /// Modules that are `structural_eq` have the same hash. The hash uses
+    /// FNV-1a, which does not depend on a random seed or on the version of the
+    /// standard library, such that it is stable across runs on the same
+    /// platform.
+    pub fn structural_hash(&self) -> u64 {
+        use std::hash::{Hash, Hasher};
+        let mut hasher = FnvHasher::default();
+        self.structural_key().hash(&mut hasher);
+        hasher.finish()
+    }
+
+    /// Compute a representation of the module that is independent of value
+    /// and block numbering, and of the order of units.
+    fn structural_key(&self) -> ModuleKey {
+        let mut units: Vec<_> = self.units().map(UnitKey::new).collect();
+        units.sort_by_cached_key(|key| key.name.to_string());
+        let mut decls: Vec<_> = self
+            .decls()
+            .map(|decl| (self[decl].name.clone(), SigKey::new(&self[decl].sig)))
+            .collect();
+        decls.sort_by_cached_key(|(name, _)| name.to_string());
+        ModuleKey {
+            units,
+            decls,
+            types: self.types.clone(),
+            consts: self.consts.clone(),
+        }
+    }
+}
+
+/// A 64 bit FNV-1a hasher, as used by `Module::structural_hash`.
+struct FnvHasher(u64);
+
+impl Default for FnvHasher {
+    fn default() -> Self {
+        FnvHasher(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl std::hash::Hasher for FnvHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 = (self.0 ^ byte as u64).wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
+
+/// The structure of a module, as compared by `Module::structural_eq`.
+#[derive(PartialEq, Eq, Hash)]
+struct ModuleKey {
+    units: Vec<UnitKey>,
+    decls: Vec<(UnitName, SigKey)>,
+    types: BTreeMap<String, Type>,
+    consts: BTreeMap<String, crate::Value>,
+}
+
+/// The structure of a unit, with values and blocks renumbered in the order
+/// they are defined.
+#[derive(PartialEq, Eq, Hash)]
+struct UnitKey {
+    kind: UnitKind,
+    name: UnitName,
+    sig: SigKey,
+    blocks: Vec<Vec<InstKey>>,
+}
+
+/// The structure of an instruction. Calls and instantiations carry the name
+/// and signature of the unit they refer to.
+#[derive(PartialEq, Eq, Hash)]
+struct InstKey {
+    data: InstData,
+    ty: Option<Type>,
+    callee: Option<(UnitName, SigKey)>,
+}
+
+/// The input types, output types, and return type of a signature.
+#[derive(PartialEq, Eq, Hash)]
+struct SigKey(Vec<Type>, Vec<Type>, Option<Type>);
+
+impl SigKey {
+    fn new(sig: &Signature) -> Self {
+        SigKey(
+            sig.inputs().map(|arg| sig.arg_type(arg)).collect(),
+            sig.outputs().map(|arg| sig.arg_type(arg)).collect(),
+            if sig.has_return_type() {
+                Some(sig.return_type())
+            } else {
+                None
+            },
+        )
+    }
+}
+
+impl UnitKey {
+    fn new(unit: Unit) -> Self {
+        let mut values = HashMap::new();
+        let mut blocks = HashMap::new();
+        for arg in unit.args() {
+            values.insert(arg, Value::new(values.len()));
+        }
+        for bb in unit.blocks() {
+            blocks.insert(bb, Block::new(blocks.len()));
+            for inst in unit.insts(bb) {
+                if let Some(value) = unit.get_inst_result(inst) {
+                    values.insert(value, Value::new(values.len()));
+                }
+            }
+        }
+
+        let map_inst = |inst: Inst| {
+            let mut data = unit[inst].clone();
+            #[allow(deprecated)]
+            for arg in data.args_mut() {
+                *arg = values.get(arg).cloned().unwrap_or_else(Value::invalid);
+            }
+            #[allow(deprecated)]
+            for bb in data.blocks_mut() {
+                *bb = blocks.get(bb).cloned().unwrap_or_else(Block::invalid);
+            }
+            // Calls and instantiations are the only instructions that refer
+            // to other units.
+            let callee = match &mut data {
+                InstData::Call { unit: ext, .. } => {
+                    let callee = (
+                        unit.extern_name(*ext).clone(),
+                        SigKey::new(unit.extern_sig(*ext)),
+                    );
+                    *ext = ExtUnit::invalid();
+                    Some(callee)
+                }
+                _ => None,
+            };
+            InstKey {
+                data,
+                ty: unit.get_inst_result(inst).map(|v| unit.value_type(v)),
+                callee,
+            }
+        };
+
+        UnitKey {
+            kind: unit.kind(),
+            name: unit.name().clone(),
+            sig: SigKey::new(unit.sig()),
+            blocks: unit
+                .blocks()
+                .map(|bb| unit.insts(bb).map(&map_inst).collect())
+                .collect(),
+        }
+    }
 }
 
 impl std::ops::Index<UnitId> for Module {
@@ -365,6 +1001,145 @@ pub enum LinkedUnit {
     Decl(DeclId),
 }
 
+/// An error that occurred while merging modules.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LinkError {
+    /// The names of the units defined in both modules.
+    DuplicateUnits(Vec<UnitName>),
+    /// The names of the types declared differently in both modules.
+    ConflictingTypes(Vec<String>),
+    /// The names of the constants declared differently in both modules.
+    ConflictingConsts(Vec<String>),
+}
+
+impl std::fmt::Display for LinkError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let lines: Vec<String> = match self {
+            LinkError::DuplicateUnits(names) => names
+                .iter()
+                .map(|name| format!("unit {} defined multiple times", name))
+                .collect(),
+            LinkError::ConflictingTypes(names) => names
+                .iter()
+                .map(|name| format!("type @{} declared differently", name))
+                .collect(),
+            LinkError::ConflictingConsts(names) => names
+                .iter()
+                .map(|name| format!("constant @{} declared differently", name))
+                .collect(),
+        };
+        write!(f, "{}", lines.join("\n"))
+    }
+}
+
+impl std::error::Error for LinkError {}
+
+/// Statistics about a module, as returned by `Module::statistics`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ModuleStats {
+    /// The number of functions.
+    pub functions: usize,
+    /// The number of processes.
+    pub processes: usize,
+    /// The number of entities.
+    pub entities: usize,
+    /// The total number of instructions, without the implicit terminators of
+    /// entities.
+    pub insts: usize,
+    /// The number of instructions with each opcode.
+    pub opcodes: HashMap<Opcode, usize>,
+    /// The largest number of blocks in a unit.
+    pub max_blocks: usize,
+    /// The width of the widest integer type used by any argument or value,
+    /// including integers nested in other types.
+    pub max_int_width: usize,
+}
+
+/// Determine the width of the widest integer within a type.
+fn max_int_width(ty: &Type) -> usize {
+    match **ty {
+        TypeKind::IntType(width) => width,
+        TypeKind::PointerType(ref ty) | TypeKind::SignalType(ref ty) => max_int_width(ty),
+        TypeKind::ArrayType(_, ref ty) => max_int_width(ty),
+        TypeKind::StructType(ref fields) => fields.iter().map(max_int_width).max().unwrap_or(0),
+        _ => 0,
+    }
+}
+
+/// An error that occurred while renaming a unit.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RenameError {
+    /// No unit is defined under the old name.
+    NotFound(UnitName),
+    /// A unit is already defined under the new name.
+    Exists(UnitName),
+}
+
+impl std::fmt::Display for RenameError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            RenameError::NotFound(name) => write!(f, "unit {} not found", name),
+            RenameError::Exists(name) => write!(f, "unit {} defined multiple times", name),
+        }
+    }
+}
+
+impl std::error::Error for RenameError {}
+
+/// Apply a function to a type and the types nested within it.
+fn map_type(ty: &Type, f: &dyn Fn(&Type) -> Type, cache: &mut HashMap<Type, Type>) -> Type {
+    if let Some(mapped) = cache.get(ty) {
+        return mapped.clone();
+    }
+    let mut map = |ty: &Type| map_type(ty, f, cache);
+    let inner = match **ty {
+        TypeKind::PointerType(ref ty) => pointer_ty(map(ty)),
+        TypeKind::SignalType(ref ty) => signal_ty(map(ty)),
+        TypeKind::ArrayType(len, ref ty) => array_ty(len, map(ty)),
+        TypeKind::StructType(ref fields) => struct_ty(fields.iter().map(map).collect()),
+        TypeKind::FuncType(ref args, ref ret) => {
+            let args = args.iter().map(&mut map).collect();
+            func_ty(args, map(ret))
+        }
+        TypeKind::EntityType(ref ins, ref outs) => {
+            let ins = ins.iter().map(&mut map).collect();
+            entity_ty(ins, outs.iter().map(map).collect())
+        }
+        _ => ty.clone(),
+    };
+    let mapped = f(&inner);
+    cache.insert(ty.clone(), mapped.clone());
+    mapped
+}
+
+/// Recreate a constant value in a new type.
+fn convert_const(value: &crate::Value, ty: &Type) -> Result<crate::Value, String> {
+    let converted = match (value, &**ty) {
+        (crate::Value::Int(v), TypeKind::IntType(width)) if v.value.bits() <= *width => {
+            Some(IntValue::from_unsigned(*width, v.value.clone()).into())
+        }
+        (crate::Value::Enum(v), TypeKind::EnumType(states)) if v.value < *states => {
+            Some(EnumValue::new(*states, v.value).into())
+        }
+        (crate::Value::Enum(v), TypeKind::IntType(width))
+            if (usize::BITS - v.value.leading_zeros()) as usize <= *width =>
+        {
+            Some(IntValue::from_usize(*width, v.value).into())
+        }
+        (crate::Value::Array(v), TypeKind::ArrayType(len, elem)) if v.0.len() == *len => {
+            let values = v.0.iter().map(|v| convert_const(v, elem));
+            Some(ArrayValue::new(values.collect::<Result<_, _>>()?).into())
+        }
+        (crate::Value::Struct(v), TypeKind::StructType(fields)) if v.0.len() == fields.len() => {
+            let values = v.0.iter().zip(fields).map(|(v, ty)| convert_const(v, ty));
+            Some(StructValue::new(values.collect::<Result<_, _>>()?).into())
+        }
+        _ if value.ty() == *ty => Some(value.clone()),
+        _ => None,
+    };
+    converted.ok_or_else(|| format!("constant {} does not fit {}", value, ty))
+}
+
 impl LinkedUnit {
     /// Check whether the linked unit is a definition.
     pub fn is_def(&self) -> bool {
@@ -382,3 +1157,1050 @@ impl LinkedUnit {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assembly::{parse_module, parse_module_unchecked, write_module_string};
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn json_round_trip() {
+        let module = parse_module(
+            "
+            declare @bar (i32) void
+
+            func @foo (i32 %a) i32 {
+            %entry:
+                call void @bar (i32 %a)
+                %t = const time 1ns 2d
+                %c = [2 x i32 %a]
+                br %next
+            %next:
+                %d = extf i32, [2 x i32] %c, 1
+                ret i32 %d
+            }
+
+            entity @top (i32$ %x) -> () {
+                %xp = prb i32$ %x
+            }
+            ",
+        )
+        .unwrap();
+        let json = module.to_json();
+        let mut back = Module::from_json(&json).unwrap();
+        back.link();
+        back.verify();
+        assert_eq!(write_module_string(&back), write_module_string(&module));
+        assert!(Module::from_json("{").is_err());
+    }
+
+    #[test]
+    fn all_insts() {
+        let module = parse_module(
+            "
+            func @foo (i32 %a) i32 {
+            %entry:
+                %b = not i32 %a
+                ret i32 %b
+            }
+
+            entity @top (i32$ %x) -> () {
+                %xp = prb i32$ %x
+            }
+            ",
+        )
+        .unwrap();
+        let foo = module.units().next().unwrap().id();
+        let insts: Vec<_> = module.all_insts().collect();
+        assert_eq!(insts.len(), 4);
+        assert_eq!(insts.iter().filter(|&&(unit, _)| unit == foo).count(), 2);
+    }
+
+    #[test]
+    fn merge() {
+        let mut module = parse_module(
+            "
+            declare @bar (i32) i32
+
+            func @foo (i32 %a) i32 {
+            %entry:
+                %b = call i32 @bar (i32 %a)
+                ret i32 %a
+            }
+            ",
+        )
+        .unwrap();
+        let other = parse_module(
+            "
+            declare @foo (i32) i32
+
+            func @bar (i32 %a) i32 {
+            %entry:
+                ret i32 %a
+            }
+            ",
+        )
+        .unwrap();
+        module.merge(other).unwrap();
+        assert_eq!(module.units().count(), 2);
+        assert_eq!(module.decls().count(), 0);
+        module.link();
+        module.verify();
+        let foo = module.units().next().unwrap();
+        let (ext_unit, _) = foo.extern_units().next().unwrap();
+        let bar = module.units().nth(1).unwrap().id();
+        assert_eq!(
+            module.lookup_ext_unit(ext_unit, foo.id()),
+            Some(LinkedUnit::Def(bar))
+        );
+    }
+
+    #[test]
+    fn merge_duplicate() {
+        let source = "
+            func @foo () void {
+            %entry:
+                ret
+            }
+        ";
+        let mut module = parse_module(source).unwrap();
+        let err = module.merge(parse_module(source).unwrap()).unwrap_err();
+        assert_eq!(
+            err,
+            LinkError::DuplicateUnits(vec![UnitName::global("foo")])
+        );
+        assert_eq!(err.to_string(), "unit @foo defined multiple times");
+        assert_eq!(module.units().count(), 1);
+    }
+
+    #[test]
+    fn merge_types() {
+        let mut module = parse_module("type @t = {i8, i8}\ntype @u = {i1}\n").unwrap();
+        let same = parse_module("type @t = {i8, i8}\n").unwrap();
+        module.merge(same).unwrap();
+        assert_eq!(module.types().count(), 2);
+
+        let other = parse_module("type @t = {i32}\ntype @v = {i1}\n").unwrap();
+        let err = module.merge(other).unwrap_err();
+        assert_eq!(err, LinkError::ConflictingTypes(vec!["t".to_string()]));
+        assert_eq!(err.to_string(), "type @t declared differently");
+        assert_eq!(
+            module.get_type("t"),
+            Some(&crate::ty::struct_ty(vec![crate::ty::int_ty(8); 2]))
+        );
+        assert!(module.get_type("v").is_none());
+    }
+
+    #[test]
+    fn merge_consts() {
+        let mut module = parse_module("const @a = i8 1\nconst @b = i8 2\n").unwrap();
+        let same = parse_module("const @a = i8 1\n").unwrap();
+        module.merge(same).unwrap();
+        assert_eq!(module.consts().count(), 2);
+
+        let other = parse_module("const @a = i16 1\nconst @c = i8 3\n").unwrap();
+        let err = module.merge(other).unwrap_err();
+        assert_eq!(err, LinkError::ConflictingConsts(vec!["a".to_string()]));
+        assert_eq!(err.to_string(), "constant @a declared differently");
+        assert!(module.get_const("c").is_none());
+    }
+
+    #[test]
+    fn typecheck() {
+        let module = parse_module(
+            "
+            func @foo (i8 %x, i8 %y) i8 {
+            %entry:
+                %z = add i8 %x, %y
+                ret i8 %z
+            }
+            ",
+        )
+        .unwrap();
+        assert_eq!(module.typecheck(), Ok(()));
+
+        let module = parse_module_unchecked(
+            "
+            func @foo (i8 %x, i16 %y16) i8 {
+            %entry:
+                %z = add i8 %x, %y16
+                ret i8 %z
+            }
+            ",
+        )
+        .unwrap();
+        let errs = module.typecheck().unwrap_err();
+        assert_eq!(errs.len(), 1);
+        assert_eq!(errs[0].unit, "func @foo");
+        assert_eq!(errs[0].inst, "%z = add i8 %x, %y16");
+        assert_eq!(
+            errs[0].message,
+            "argument %y16 must be of type i8 (but is i16)"
+        );
+
+        // Calls are checked against the callee, not the types at the call.
+        let module = parse_module_unchecked(
+            "
+            declare @bar (i32) void
+
+            func @foo (i8 %x) void {
+            %entry:
+                call void @bar (i8 %x)
+                ret
+            }
+            ",
+        )
+        .unwrap();
+        let errs = module.typecheck().unwrap_err();
+        assert_eq!(errs.len(), 1);
+        assert_eq!(errs[0].unit, "func @foo");
+        assert_eq!(errs[0].inst, "call @bar (%x)");
+        assert_eq!(
+            errs[0].message,
+            "argument %x must be of type i32 (but is i8)"
+        );
+    }
+
+    /// A module which fails several structural checks at once.
+    const MALFORMED: &str = "
+            func @noterm (i32 %a) void {
+            %entry:
+                %b = add i32 %a, %a
+            }
+
+            func @types (i8 %x, i16 %y) void {
+            %entry:
+                %z = add i8 %x, %y
+                ret
+            }
+
+            proc @con (i1$ %x, i1$ %y) -> () {
+            %entry:
+                con i1$ %x, %y
+                halt
+            }
+
+            func @order (i32 %a, i1 %c) void {
+            %entry:
+                br %c, %then, %else
+            %then:
+                %b = add i32 %a, %a
+                br %else
+            %else:
+                %d = add i32 %a, %b
+                ret
+            }
+    ";
+
+    #[test]
+    fn verify_all_structure() {
+        let module = parse_module_unchecked(MALFORMED).unwrap();
+        let report = module.verify_all();
+        let errors = |unit| -> Vec<String> {
+            report
+                .unit(unit)
+                .unwrap()
+                .errors()
+                .map(|e| e.to_string())
+                .collect()
+        };
+        assert_eq!(report.num_errors(), 4);
+        assert_eq!(
+            errors("func @noterm"),
+            vec!["error [integrity]: %entry: last instruction `%b = add i32 %a, %a` must be a terminator"]
+        );
+        assert_eq!(
+            errors("func @types"),
+            vec!["error [integrity]: %z = add i8 %x, %y: argument %y must be of type i8 (but is i16)"]
+        );
+        assert_eq!(
+            errors("proc @con"),
+            vec!["error [integrity]: con %x, %y: con may not appear in this unit"]
+        );
+        assert_eq!(
+            errors("func @order"),
+            vec!["error [integrity]: %b = add i32 %a, %a: does not dominate use in `%d = add i32 %a, %b`"]
+        );
+        assert!(
+            parse_module_unchecked("func @foo () void {\n%entry:\n    ret\n}")
+                .unwrap()
+                .verify_all()
+                .is_ok()
+        );
+    }
+
+    #[test]
+    fn try_verify() {
+        let module = parse_module_unchecked(MALFORMED).unwrap();
+        let errs = module.try_verify().unwrap_err();
+        assert_eq!(
+            errs[0],
+            VerifyError::Terminator {
+                unit: "func @noterm".to_string(),
+                object: "%entry".to_string(),
+                message: "last instruction `%b = add i32 %a, %a` must be a terminator".to_string(),
+            }
+        );
+        assert!(matches!(&errs[1], VerifyError::Type(err) if err.unit == "func @types"));
+        assert_eq!(
+            errs[2],
+            VerifyError::NotAllowed {
+                unit: "proc @con".to_string(),
+                inst: "con %x, %y".to_string(),
+                opcode: Opcode::Con,
+            }
+        );
+        assert_eq!(
+            errs[3],
+            VerifyError::UseBeforeDef {
+                unit: "func @order".to_string(),
+                def: "%b = add i32 %a, %a".to_string(),
+                inst: "%d = add i32 %a, %b".to_string(),
+            }
+        );
+        assert_eq!(errs.len(), 4);
+        assert_eq!(
+            errs[2].to_string(),
+            "proc @con: con %x, %y: con may not appear in this unit"
+        );
+        assert!(
+            parse_module_unchecked("func @foo () void {\n%entry:\n    ret\n}")
+                .unwrap()
+                .try_verify()
+                .is_ok()
+        );
+
+        let mut module = Module::new();
+        let mut sig = Signature::new();
+        sig.set_return_type(crate::void_ty());
+        module.add_unit(UnitData::new(
+            UnitKind::Function,
+            UnitName::global("empty"),
+            sig,
+        ));
+        let errs = module.try_verify().unwrap_err();
+        assert_eq!(
+            errs,
+            vec![VerifyError::NoEntry {
+                unit: "func @empty".to_string()
+            }]
+        );
+        assert_eq!(
+            errs[0].to_string(),
+            "func @empty: layout has no entry block"
+        );
+    }
+
+    #[test]
+    fn units_by_kind() {
+        let mut module = parse_module(
+            "
+            func @f1 () void {
+            %entry:
+                ret
+            }
+            proc @p1 () -> () {
+            %entry:
+                halt
+            }
+            entity @e1 () -> () {}
+            func @f2 () void {
+            %entry:
+                ret
+            }
+            entity @e2 () -> () {}
+            entity @e3 () -> () {}
+            ",
+        )
+        .unwrap();
+        assert_eq!(module.functions().count(), 2);
+        assert_eq!(module.processes().count(), 1);
+        assert_eq!(module.entities().count(), 3);
+        assert_eq!(module.functions_mut().count(), 2);
+        assert_eq!(module.processes_mut().count(), 1);
+        assert!(module.entities_mut().all(|unit| unit.is_entity()));
+        assert_eq!(module.entities_mut().count(), 3);
+    }
+
+    #[test]
+    fn inst_spans() {
+        let input =
+            "func @foo (i32 %a) i32 {\n%entry:\n    %b = add i32 %a, %a\n    ret i32 %b\n}\n";
+        let module = parse_module(input).unwrap();
+        let id = module.units().next().unwrap().id();
+        let unit = module.unit(id);
+        let spans: Vec<_> = unit
+            .all_insts()
+            .map(|inst| module.span_of(id, inst).unwrap())
+            .collect();
+        assert_eq!(spans.len(), 2);
+        assert_eq!(&input[spans[0].start..spans[0].end], "%b = add i32 %a, %a");
+        assert_eq!(&input[spans[1].start..spans[1].end], "ret i32 %b");
+        assert!(spans[0].end <= spans[1].start);
+    }
+
+    #[test]
+    fn remove_and_rename_units() {
+        let mut module = parse_module(
+            "
+            func @unused () void {
+            %entry:
+                ret
+            }
+            func @f (i32 %a) i32 {
+            %entry:
+                ret i32 %a
+            }
+            func @g (i32 %a) i32 {
+            %entry:
+                %b = call i32 @f (i32 %a)
+                %c = call i32 @f (i32 %b)
+                ret i32 %c
+            }
+            ",
+        )
+        .unwrap();
+        let name = |s: &str| UnitName::global(s);
+
+        assert!(module.remove_unit_named(&name("unused")));
+        assert!(!module.remove_unit_named(&name("unused")));
+        assert_eq!(module.units().count(), 2);
+
+        assert_eq!(
+            module.rename_unit(&name("f"), name("g")),
+            Err(RenameError::Exists(name("g")))
+        );
+        assert_eq!(
+            module.rename_unit(&name("unused"), name("h")),
+            Err(RenameError::NotFound(name("unused")))
+        );
+        module.rename_unit(&name("f"), name("h")).unwrap();
+        assert!(!module.is_linked());
+        module.link();
+        module.verify();
+        let g = module.lookup_unit(&name("g")).unwrap();
+        let h = module.lookup_unit(&name("h")).unwrap();
+        let calls: Vec<_> = module
+            .unit(g)
+            .all_insts()
+            .filter(|&inst| module.unit(g)[inst].opcode() == crate::ir::Opcode::Call)
+            .collect();
+        assert_eq!(calls.len(), 2);
+        for inst in calls {
+            let ext_unit = module.unit(g)[inst].get_ext_unit().unwrap();
+            assert_eq!(
+                module.lookup_ext_unit(ext_unit, g),
+                Some(LinkedUnit::Def(h))
+            );
+        }
+    }
+
+    #[test]
+    fn structural_eq() {
+        let parse = |input: &str| parse_module(input).unwrap();
+        let a = parse(
+            "
+            declare @g (i32) void
+            func @f (i32 %0) i32 {
+            %0:
+                %1 = add i32 %0, %0
+                call void @g (i32 %1)
+                br %1
+            %1:
+                ret i32 %1
+            }
+            ",
+        );
+        let b = parse(
+            "
+            func @f (i32 %x) i32 {
+            %entry:
+                %y = add i32 %x, %x
+                call void @g (i32 %y)
+                br %exit
+            %exit:
+                ret i32 %y
+            }
+            declare @g (i32) void
+            ",
+        );
+        let c = parse(
+            "
+            declare @g (i32) void
+            func @f (i32 %x) i32 {
+            %entry:
+                %y = add i32 %x, %x
+                call void @g (i32 %x)
+                br %exit
+            %exit:
+                ret i32 %y
+            }
+            ",
+        );
+        assert!(a.structural_eq(&b));
+        assert_eq!(a.structural_hash(), b.structural_hash());
+        assert!(!a.structural_eq(&c));
+        assert_ne!(a.structural_hash(), c.structural_hash());
+
+        // Modules that only differ in a named constant are not equal.
+        let d = parse("const @k = i32 1\n");
+        let e = parse("const @k = i32 2\n");
+        assert!(d.structural_eq(&parse("const @k = i32 1\n")));
+        assert!(!d.structural_eq(&e));
+        assert_ne!(d.structural_hash(), e.structural_hash());
+
+        // Reference values of FNV-1a.
+        use std::hash::Hasher;
+        let fnv = |bytes: &[u8]| {
+            let mut hasher = FnvHasher::default();
+            hasher.write(bytes);
+            hasher.finish()
+        };
+        assert_eq!(fnv(b""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(fnv(b"a"), 0xaf63_dc4c_8601_ec8c);
+        assert_eq!(fnv(b"foobar"), 0x8594_4171_f739_67e8);
+    }
+
+    #[test]
+    fn classify_values() {
+        let module = parse_module(
+            "
+            func @foo (i32 %a) i32 {
+            %entry:
+                %one = const i32 1
+                %b = add i32 %a, %one
+                ret i32 %b
+            }
+            ",
+        )
+        .unwrap();
+        let id = module.lookup_unit(&UnitName::global("foo")).unwrap();
+        let unit = module.unit(id);
+        let arg = unit.input_arg(0);
+        let insts: Vec<_> = unit.all_insts().collect();
+        let one = unit.inst_result(insts[0]);
+        let b = unit.inst_result(insts[1]);
+        assert_eq!(
+            module.classify(id, arg),
+            ValueKind::Arg(unit.sig().inputs().next().unwrap())
+        );
+        assert_eq!(module.classify(id, one), ValueKind::Const(insts[0]));
+        assert_eq!(module.classify(id, b), ValueKind::Inst(insts[1]));
+    }
+
+    #[test]
+    fn from_reader() {
+        let input = b"func @foo (i32 %a) i32 {\n%entry:\n    ret i32 %a\n}\n";
+        let module = Module::from_reader(std::io::Cursor::new(&input[..])).unwrap();
+        assert_eq!(module.units().count(), 1);
+
+        let err = Module::from_reader(std::io::Cursor::new(&b"\xff\xfe"[..]))
+            .err()
+            .unwrap();
+        assert!(err.starts_with("failed to read input:"), "{}", err);
+        let err = Module::from_reader(std::io::Cursor::new(&b"func @foo {"[..]))
+            .err()
+            .unwrap();
+        assert!(!err.starts_with("failed to read input:"), "{}", err);
+        let err = Module::from_file("/nonexistent/foo.llhd").err().unwrap();
+        assert!(
+            err.starts_with("failed to read input: /nonexistent/foo.llhd:"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn external_symbols() {
+        let module = parse_module_unchecked(
+            "declare @decl (i32) i32
+
+            func @foo (i32 %a) i32 {
+            %entry:
+                %b = call i32 @other (i32 %a)
+                %c = call i32 @decl (i32 %b)
+                %d = call i32 @bar (i32 %c)
+                %e = call i32 @other (i32 %d)
+                ret i32 %e
+            }
+
+            func @bar (i32 %a) i32 {
+            %entry:
+                ret i32 %a
+            }
+
+            entity @top (i32$ %a) -> () {
+                inst @sub (i32$ %a) -> ()
+            }",
+        )
+        .unwrap();
+        assert_eq!(
+            module.external_symbols(),
+            vec![
+                UnitName::global("other"),
+                UnitName::global("decl"),
+                UnitName::global("sub"),
+            ]
+        );
+    }
+
+    #[test]
+    fn block_insts() {
+        let module = parse_module(
+            "
+            func @foo (i32 %a) i32 {
+            %entry:
+                %x = add i32 %a, %a
+                %y = umul i32 %x, %a
+                br %next
+            %next:
+                ret i32 %y
+            }",
+        )
+        .unwrap();
+        let unit = module.units().next().unwrap();
+        let blocks: Vec<_> = unit.blocks().collect();
+        let dump = |inst: Inst| inst.dump(&unit).to_string();
+        let insts: Vec<_> = unit.insts(blocks[0]).map(dump).collect();
+        assert_eq!(
+            insts,
+            vec!["%x = add i32 %a, %a", "%y = umul i32 %x, %a", "br %next"]
+        );
+        assert_eq!(unit.first_inst(blocks[0]).map(dump), Some(insts[0].clone()));
+        assert_eq!(unit.last_inst(blocks[0]).map(dump), Some(insts[2].clone()));
+        assert_eq!(dump(unit.terminator(blocks[0])), "br %next");
+        assert_eq!(unit.first_inst(blocks[1]), unit.last_inst(blocks[1]));
+        assert_eq!(dump(unit.terminator(blocks[1])), "ret %y");
+    }
+
+    #[test]
+    fn replace_all_uses() {
+        let mut module = parse_module(
+            "
+            func @foo (i32 %a, i32 %b) i32 {
+            %entry:
+                %x = add i32 %a, %a
+                %y = umul i32 %x, %b
+                br %next
+            %next:
+                %z = sub i32 %y, %a
+                ret i32 %z
+            }",
+        )
+        .unwrap();
+        let id = module.units().next().unwrap().id();
+        let mut unit = module.unit_mut(id);
+        let a = unit.input_arg(0);
+        let b = unit.input_arg(1);
+        assert_eq!(unit.replace_use(a, b), 3);
+        assert_eq!(unit.replace_use(a, b), 0);
+        assert_eq!(
+            write_module_string(&module),
+            "func @foo (i32 %a, i32 %b) i32 {
+entry:
+    %x = add i32 %b, %b
+    %y = umul i32 %x, %b
+    br %next
+next:
+    %z = sub i32 %y, %b
+    ret i32 %z
+}
+"
+        );
+    }
+
+    #[test]
+    fn map_types() {
+        let source = "
+            func @foo (i8 %a) i8 {
+            %entry:
+                %c = const i8 200
+                %x = add i8 %a, %c
+                %lo = exts i4, i8 %x, 0, 4
+                %y = inss i8 %x, i4 %lo, 4, 4
+                %v = [4 x i8 %y]
+                ret i8 %y
+            }
+        ";
+        let double = |ty: &Type| match **ty {
+            TypeKind::IntType(width) => crate::ty::int_ty(width * 2),
+            _ => ty.clone(),
+        };
+        let mut module = parse_module(source).unwrap();
+        module.map_types(double).unwrap();
+        module.verify();
+        assert_eq!(
+            write_module_string(&module),
+            "func @foo (i16 %a) i16 {
+entry:
+    %c = const i16 200
+    %x = add i16 %a, %c
+    %lo = exts i8, i16 %x, 0, 8
+    %y = inss i16 %x, i8 %lo, 4, 8
+    %v = [4 x i16 %y]
+    ret i16 %y
+}
+"
+        );
+
+        let halve = |ty: &Type| match **ty {
+            TypeKind::IntType(width) => crate::ty::int_ty(width / 2),
+            _ => ty.clone(),
+        };
+        let mut module = parse_module(source).unwrap();
+        assert_eq!(
+            module.map_types(halve),
+            Err("constant i8 200 does not fit i4".to_string())
+        );
+        assert_eq!(
+            write_module_string(&module),
+            write_module_string(&parse_module(source).unwrap())
+        );
+    }
+
+    #[test]
+    fn name_of() {
+        let mut module = parse_module(
+            "
+            func @foo (i32 %a) i32 {
+            %entry:
+                %0 = add i32 %a, %a
+                ret i32 %0
+            }
+            ",
+        )
+        .unwrap();
+        let id = module.units().next().unwrap().id();
+        let unit = module.unit(id);
+        let arg = unit.sig().args().next().unwrap();
+        let bb = unit.entry();
+        let add = unit.first_inst(bb).unwrap();
+        let ret = unit.last_inst(bb).unwrap();
+        assert_eq!(module.name_of(id, arg), Some("a"));
+        assert_eq!(module.name_of(id, add), None);
+        assert_eq!(module.name_of(id, ret), None);
+        assert_eq!(module.name_of(id, bb), Some("entry"));
+
+        module.set_name_of(id, arg, "x".to_string());
+        module.set_name_of(id, add, "y".to_string());
+        module.set_name_of(id, bb, "body".to_string());
+        let unit = module.unit(id);
+        assert_eq!(module.name_of(id, unit.arg_value(arg)), Some("x"));
+        assert_eq!(module.name_of(id, unit.inst_result(add)), Some("y"));
+        assert_eq!(
+            write_module_string(&module),
+            "func @foo (i32 %x) i32 {
+body:
+    %y = add i32 %x, %x
+    ret i32 %y
+}
+"
+        );
+    }
+
+    #[test]
+    fn clone_with_remap() {
+        let mut module = parse_module(
+            "
+            func @foo (i32 %a) i32 {
+            %entry:
+                %b = add i32 %a, %a
+                br %next
+            %next:
+                %c = add i32 %b, %a
+                ret i32 %c
+            }
+            ",
+        )
+        .unwrap();
+        let unit = module.units().next().unwrap();
+        let (mut data, values, blocks) = unit.clone_with_remap();
+        let clone = Unit::new_anonymous(&data);
+
+        // Branch targets and operands refer to the clone.
+        let next = blocks[&unit.blocks().nth(1).unwrap()];
+        assert_eq!(clone.blocks().nth(1), Some(next));
+        assert_eq!(clone[clone.terminator(clone.entry())].blocks(), &[next]);
+        let add = clone.first_inst(next).unwrap();
+        let b = unit.inst_result(unit.first_inst(unit.entry()).unwrap());
+        assert_eq!(clone[add].args(), &[values[&b], values[&unit.input_arg(0)]]);
+        assert_eq!(
+            clone.get_value_inst(values[&b]),
+            clone.first_inst(clone.entry())
+        );
+        assert_eq!(clone.input_arg(0), values[&unit.input_arg(0)]);
+
+        data.name = UnitName::global("bar");
+        module.add_unit(data);
+        module.verify();
+        assert_eq!(
+            write_module_string(&module),
+            "func @foo (i32 %a) i32 {
+entry:
+    %b = add i32 %a, %a
+    br %next
+next:
+    %c = add i32 %b, %a
+    ret i32 %c
+}
+
+func @bar (i32 %a) i32 {
+entry:
+    %b = add i32 %a, %a
+    br %next
+next:
+    %c = add i32 %b, %a
+    ret i32 %c
+}
+"
+        );
+
+        // Phi nodes may refer to values defined later in the loop, and calls
+        // and instances refer to the external units of the clone.
+        let source = "entity @sub (i32$ %a) -> (i32$ %b) {
+}
+
+func @loop (i32 %n) i32 {
+entry:
+    %zero = const i32 0
+    br %head
+head:
+    %i = phi i32 [%zero, %entry], [%j, %head]
+    %j = call i32 @step (i32 %i)
+    %done = eq i32 %j, %n
+    br %done, %head, %exit
+exit:
+    ret i32 %j
+}
+
+entity @top (i32$ %x) -> (i32$ %y) {
+    inst @sub (i32$ %x) -> (i32$ %y)
+}
+";
+        let declaration = "
+declare @step (i32) i32
+";
+        let mut module = parse_module(format!("{}{}", source, declaration)).unwrap();
+        let ids: Vec<_> = module.units().skip(1).map(|unit| unit.id()).collect();
+        for id in ids {
+            let unit = module.unit(id);
+            let (mut data, _, _) = unit.clone_with_remap();
+            let clone = Unit::new_anonymous(&data);
+            for (inst, new_inst) in unit.all_insts().zip(clone.all_insts()) {
+                assert!(clone[new_inst]
+                    .args()
+                    .iter()
+                    .all(|&arg| !clone.is_placeholder(arg)));
+                if let Some(ext) = unit[inst].get_ext_unit() {
+                    let new_ext = clone[new_inst].get_ext_unit().unwrap();
+                    assert_eq!(clone.extern_name(new_ext), unit.extern_name(ext));
+                    assert_eq!(clone.extern_sig(new_ext), unit.extern_sig(ext));
+                }
+            }
+            data.name = UnitName::global(format!("{}_copy", unit.name().get_name().unwrap()));
+            module.add_unit(data);
+        }
+        module.verify();
+        let copies = source
+            .split("\n\n")
+            .skip(1)
+            .map(|unit| unit.replacen(" (", "_copy (", 1))
+            .collect::<Vec<_>>()
+            .join("\n\n");
+        assert_eq!(
+            write_module_string(&module),
+            format!("{}\n{}{}", source, copies, declaration)
+        );
+    }
+
+    #[test]
+    fn topologically_sort() {
+        let source = "entity @foo (i8$ %a) -> (i8$ %x) {
+    %ap = prb i8$ %a
+    %t = const time 0s 1e
+    %one = const i8 1
+    %y = add i8 %ap, %one
+    %z = add i8 %y, %ap
+    drv i8$ %x, %z, %t
+}
+";
+        let mut module = parse_module(source).unwrap();
+        let id = module.units().next().unwrap().id();
+        let mut unit = module.unit_mut(id);
+        let bb = unit.entry();
+        let insts: Vec<_> = unit.insts(bb).collect();
+        for &inst in insts[..3].iter().rev() {
+            unit.remove_inst(inst);
+            unit.append_inst(inst, bb);
+        }
+        unit.remove_inst(insts[5]);
+        unit.prepend_inst(insts[5], bb);
+        unit.topologically_sort().unwrap();
+        assert_eq!(
+            write_module_string(&module),
+            "entity @foo (i8$ %a) -> (i8$ %x) {
+    %one = const i8 1
+    %t = const time 0s 1e
+    %ap = prb i8$ %a
+    %y = add i8 %ap, %one
+    %z = add i8 %y, %ap
+    drv i8$ %x, %z, %t
+}
+"
+        );
+
+        // Make %y depend on %z, which closes a loop.
+        let mut unit = module.unit_mut(id);
+        let one = unit.inst_result(insts[2]);
+        let z = unit.inst_result(insts[4]);
+        unit.replace_value_within_inst(one, z, insts[3]);
+        let err = unit.topologically_sort().unwrap_err();
+        assert_eq!(err.insts, vec![insts[3], insts[4]]);
+        assert_eq!(
+            err.to_string(),
+            format!(
+                "combinational loop among instructions {}, {}",
+                insts[3], insts[4]
+            )
+        );
+    }
+
+    #[test]
+    fn canonical_string() {
+        let a = parse_module(
+            "
+            type @pair = {i32, i32}
+
+            func @foo (i32 %a) i32 {
+            %entry:
+                ; Double the input.
+                %x = add i32 %a, %a
+                br %done
+            %done:
+                ret i32 %x
+            }
+
+            func @id (@pair %p) @pair {
+            %entry:
+                ret @pair %p
+            }
+
+            entity @bar (i32$ %in) -> () {
+                %v = prb i32$ %in
+                %r = call i32 @foo (i32 %v)
+            }
+            ",
+        )
+        .unwrap();
+        let b = parse_module(
+            "
+            func @id ({i32, i32} %q) {i32, i32} {
+            %0:
+                ret {i32, i32} %q
+            }
+
+            entity @bar (i32$ %0) -> () {
+                %1 = prb i32$ %0
+                %2 = call i32 @foo (i32 %1)
+            }
+
+            func @foo (i32 %b) i32 {
+            %start:
+                %y = add i32 %b, %b
+                br %exit
+            %exit:
+                ret i32 %y
+            }
+            ",
+        )
+        .unwrap();
+        assert_eq!(a.to_canonical_string(), b.to_canonical_string());
+        assert_eq!(
+            a.to_canonical_string(),
+            "entity @bar (i32$ %0) -> () {
+    %1 = prb i32$ %0
+    %2 = call i32 @foo (i32 %1)
+}
+
+func @foo (i32 %0) i32 {
+1:
+    %2 = add i32 %0, %0
+    br %3
+3:
+    ret i32 %2
+}
+
+func @id ({i32, i32} %0) {i32, i32} {
+1:
+    ret {i32, i32} %0
+}
+"
+        );
+        let c = parse_module(
+            "
+            func @foo (i32 %a) i32 {
+            %entry:
+                %x = add i32 %a, %a
+                ret i32 %a
+            }
+            ",
+        )
+        .unwrap();
+        assert_ne!(a.to_canonical_string(), c.to_canonical_string());
+    }
+
+    #[test]
+    fn statistics() {
+        let module = parse_module(
+            "
+            func @foo (i32 %a) i32 {
+            %entry:
+                %b = add i32 %a, %a
+                %c = add i32 %b, %a
+                br %next
+            %next:
+                ret i32 %c
+            }
+
+            proc @bar () -> ({i1, i64}$ %x) {
+            %entry:
+                halt
+            }
+
+            entity @baz (i8$ %a) -> (i8$ %b) {
+                %t = const time 0s 1e
+                %ap = prb i8$ %a
+                drv i8$ %b, %ap, %t
+            }
+            ",
+        )
+        .unwrap();
+        let stats = module.statistics();
+        assert_eq!(
+            (stats.functions, stats.processes, stats.entities),
+            (1, 1, 1)
+        );
+        assert_eq!(stats.insts, 8);
+        assert_eq!(stats.max_blocks, 2);
+        assert_eq!(stats.max_int_width, 64);
+        let mut opcodes: Vec<_> = stats
+            .opcodes
+            .iter()
+            .map(|(opcode, &count)| (opcode.to_string(), count))
+            .collect();
+        opcodes.sort();
+        assert_eq!(
+            opcodes,
+            vec![
+                ("add".to_string(), 2),
+                ("br".to_string(), 1),
+                ("const".to_string(), 1),
+                ("drv".to_string(), 1),
+                ("halt".to_string(), 1),
+                ("prb".to_string(), 1),
+                ("ret".to_string(), 1),
+            ]
+        );
+    }
+}
@@ -61,6 +61,16 @@ impl Signature {
         arg
     }
 
+    /// Replace the types of the arguments and the return type.
+    pub(crate) fn map_types(&mut self, mut f: impl FnMut(&Type) -> Type) {
+        for arg in self.args.values_mut() {
+            arg.ty = f(&arg.ty);
+        }
+        if let Some(retty) = &mut self.retty {
+            *retty = f(retty);
+        }
+    }
+
     /// Set the return type of the signature.
     pub fn set_return_type(&mut self, ty: Type) {
         self.retty = Some(ty);
@@ -3,17 +3,20 @@
 // #![deny(missing_docs)]
 
 use crate::{
-    analysis::{DominatorTree, PredecessorTable, TemporalRegionGraph},
+    analysis::{DominatorTree, Liveness, PredecessorTable, TemporalRegionGraph},
+    assembly::escape_name,
     ir::{
-        layout::BlockNode, prelude::*, BlockData, ControlFlowGraph, DataFlowGraph, ExtUnit,
-        ExtUnitData, FunctionLayout, InstBuilder, InstData, UnitId, ValueData,
+        layout::BlockNode, prelude::*, AnyObject, BlockData, Comments, ControlFlowGraph,
+        DataFlowGraph, ExtUnit, ExtUnitData, FunctionLayout, InstBuilder, InstData, Span, UnitId,
+        ValueData, ValueKind,
     },
     table::TableKey,
+    ty::TypeContext,
     verifier::Verifier,
     void_ty, Type,
 };
 use std::{
-    collections::HashSet,
+    collections::{BTreeSet, HashMap, HashSet},
     ops::{Deref, Index, IndexMut},
 };
 
@@ -78,14 +81,14 @@ impl std::fmt::Display for UnitName {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         match self {
             UnitName::Anonymous(id) => write!(f, "%{}", id),
-            UnitName::Local(n) => write!(f, "%{}", n),
-            UnitName::Global(n) => write!(f, "@{}", n),
+            UnitName::Local(n) => write!(f, "%{}", escape_name(n)),
+            UnitName::Global(n) => write!(f, "@{}", escape_name(n)),
         }
     }
 }
 
 /// The three different units that may appear in LLHD IR.
-#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum UnitKind {
     /// A `Function`.
     Function,
@@ -281,6 +284,109 @@ impl<'a> Unit<'a> {
     pub fn extern_units(self) -> impl Iterator<Item = (ExtUnit, &'a ExtUnitData)> + 'a {
         self.data.dfg.ext_units.iter()
     }
+
+    /// Create a copy of the unit with fresh values and blocks.
+    ///
+    /// Rebuilds the blocks and instructions of the unit in layout order, along
+    /// with their names, hints, and comments. All operands and branch targets
+    /// are rewritten to refer to the copy. Returns the copy together with maps
+    /// from the values and blocks of this unit to their counterparts, such
+    /// that references held outside the unit can be fixed up.
+    pub fn clone_with_remap(self) -> (UnitData, HashMap<Value, Value>, HashMap<Block, Block>) {
+        let mut data = UnitData::new(self.kind(), self.name().clone(), self.sig().clone());
+        let mut values: HashMap<Value, Value> = HashMap::new();
+        let mut blocks: HashMap<Block, Block> = HashMap::new();
+        {
+            let mut unit = UnitBuilder::new_anonymous(&mut data);
+            values.extend(self.args().zip(unit.args()));
+            let ext_units: HashMap<ExtUnit, ExtUnit> = self
+                .extern_units()
+                .map(|(ext, data)| (ext, unit.add_extern(data.name.clone(), data.sig.clone())))
+                .collect();
+
+            // Create the blocks. Entities already have their single block.
+            for bb in self.blocks() {
+                let nb = if self.is_entity() {
+                    unit.entry()
+                } else {
+                    unit.block()
+                };
+                if let Some(name) = self.get_block_name(bb) {
+                    unit.set_block_name(nb, name.to_owned());
+                }
+                if let Some(hint) = self.get_anonymous_block_hint(bb) {
+                    unit.set_anonymous_block_hint(nb, hint);
+                }
+                blocks.insert(bb, nb);
+            }
+
+            // Copy the instructions, using placeholders for values that are
+            // used before they are defined.
+            let mut placeholders: HashMap<Value, Value> = HashMap::new();
+            for bb in self.blocks() {
+                let nb = blocks[&bb];
+                for inst in self.insts(bb) {
+                    if self.is_entity() && self[inst].opcode().is_terminator() {
+                        continue;
+                    }
+                    let mut inst_data = self[inst].clone();
+                    #[allow(deprecated)]
+                    for arg in inst_data.args_mut() {
+                        *arg = match values.get(arg) {
+                            Some(&v) => v,
+                            None => *placeholders
+                                .entry(*arg)
+                                .or_insert_with(|| unit.add_placeholder(self.value_type(*arg))),
+                        };
+                    }
+                    #[allow(deprecated)]
+                    for block in inst_data.blocks_mut() {
+                        *block = blocks[block];
+                    }
+                    if let InstData::Call { unit: ext, .. } = &mut inst_data {
+                        *ext = ext_units[ext];
+                    }
+                    if self.is_entity() {
+                        let term = unit.terminator(nb);
+                        unit.insert_before(term);
+                    } else {
+                        unit.append_to(nb);
+                    }
+                    let result = self.get_inst_result(inst);
+                    let ty = result.map(|v| self.value_type(v)).unwrap_or_else(void_ty);
+                    let new_inst = unit.build_inst(inst_data, ty);
+                    if let Some(loc) = self.location_hint(inst) {
+                        unit.set_location_hint(new_inst, loc);
+                    }
+                    if let Some(span) = self.span_hint(inst) {
+                        unit.set_span_hint(new_inst, span);
+                    }
+                    if let Some(comments) = self.comments(inst) {
+                        unit.set_comments(new_inst, comments.clone());
+                    }
+                    if let Some(value) = result {
+                        let new = unit.inst_result(new_inst);
+                        values.insert(value, new);
+                        if let Some(ph) = placeholders.remove(&value) {
+                            unit.replace_use(ph, new);
+                            unit.remove_placeholder(ph);
+                        }
+                    }
+                }
+            }
+
+            // Carry over the names of the values.
+            for (&value, &new) in &values {
+                if let Some(name) = self.get_name(value) {
+                    unit.set_name(new, name.to_owned());
+                }
+                if let Some(hint) = self.get_anonymous_hint(value) {
+                    unit.set_anonymous_hint(new, hint);
+                }
+            }
+        }
+        (data, values, blocks)
+    }
 }
 
 /// # Analyses
@@ -319,6 +425,11 @@ impl<'a> Unit<'a> {
         #[allow(deprecated)]
         DominatorTree::new(&self, pt)
     }
+
+    /// Compute the values live at the beginning and end of each block.
+    pub fn liveness(self) -> Liveness {
+        Liveness::new(&self, &self.predtbl())
+    }
 }
 
 /// # Control Flow Graph
@@ -380,6 +491,24 @@ impl<'a> Unit<'a> {
         }
     }
 
+    /// Returns the type of an instruction's result, if it produces one.
+    pub fn get_inst_type(self, inst: Inst) -> Option<Type> {
+        self.get_inst_result(inst).map(|v| self.value_type(v))
+    }
+
+    /// Returns the types of an instruction's value operands.
+    ///
+    /// Operands that are absent, such as the gate of a `reg` trigger without
+    /// one, are skipped.
+    pub fn inst_arg_types(self, inst: Inst) -> Vec<Type> {
+        self[inst]
+            .args()
+            .iter()
+            .filter(|arg| !arg.is_invalid())
+            .map(|&arg| self.value_type(arg))
+            .collect()
+    }
+
     /// Return the argument that produces `value`.
     pub fn get_value_arg(self, value: Value) -> Option<Arg> {
         match self[value] {
@@ -412,6 +541,19 @@ impl<'a> Unit<'a> {
         }
     }
 
+    /// Determine what kind of definition a value originates from.
+    pub fn value_kind(self, value: Value) -> ValueKind {
+        match self[value] {
+            ValueData::Inst { inst, .. } if self[inst].opcode().is_const() => {
+                ValueKind::Const(inst)
+            }
+            ValueData::Inst { inst, .. } => ValueKind::Inst(inst),
+            ValueData::Arg { arg, .. } => ValueKind::Arg(arg),
+            ValueData::Placeholder { .. } => ValueKind::Placeholder,
+            ValueData::Invalid => panic!("invalid value {}", value),
+        }
+    }
+
     /// Return the name of a value.
     pub fn get_name(self, value: Value) -> Option<&'a str> {
         self.data.dfg.names.get(&value).map(AsRef::as_ref)
@@ -422,6 +564,19 @@ impl<'a> Unit<'a> {
         self.data.dfg.anonymous_hints.get(&value).cloned()
     }
 
+    /// Return the name of a value, instruction, block, or argument.
+    ///
+    /// Instructions are named after their result, and arguments after their
+    /// value. Returns `None` for instructions without a result.
+    pub fn name_of(self, obj: impl Into<AnyObject>) -> Option<&'a str> {
+        match obj.into() {
+            AnyObject::Value(value) => self.get_name(value),
+            AnyObject::Inst(inst) => self.get_name(self.get_inst_result(inst)?),
+            AnyObject::Block(bb) => self.get_block_name(bb),
+            AnyObject::Arg(arg) => self.get_name(self.arg_value(arg)),
+        }
+    }
+
     /// Iterate over all uses of a value.
     pub fn uses(self, value: Value) -> &'a HashSet<Inst> {
         &self.data.dfg.value_uses[&value]
@@ -447,6 +602,7 @@ impl<'a> Unit<'a> {
         match self[inst].opcode() {
             Opcode::ConstInt => self.get_const_int(value).cloned().map(Into::into),
             Opcode::ConstTime => self.get_const_time(value).cloned().map(Into::into),
+            Opcode::ConstEnum => self.get_const_enum(value).cloned().map(Into::into),
             Opcode::Array | Opcode::ArrayUniform => self.get_const_array(value).map(Into::into),
             Opcode::Struct => self.get_const_struct(value).map(Into::into),
             _ => None,
@@ -473,6 +629,16 @@ impl<'a> Unit<'a> {
         self.data.dfg[inst].get_const_int()
     }
 
+    /// Resolve a constant enum value.
+    ///
+    /// Returns `None` if the value is not constant. Note that this *does not*
+    /// perform constant folding. Rather, the value must resolve to an
+    /// instruction which produces a constant value.
+    pub fn get_const_enum(self, value: Value) -> Option<&'a crate::EnumValue> {
+        let inst = self.get_value_inst(value)?;
+        self.data.dfg[inst].get_const_enum()
+    }
+
     /// Resolve a constant array value.
     ///
     /// Returns `None` if the value is not constant. Note that this *does not*
@@ -525,6 +691,22 @@ impl<'a> Unit<'a> {
         self.data.dfg.location_hints.get(&inst).cloned()
     }
 
+    /// Get the span hint associated with an instruction.
+    ///
+    /// Returns the range of bytes the instruction occupies in the input file,
+    /// or None if there is no hint for the instruction.
+    pub fn span_hint(self, inst: Inst) -> Option<Span> {
+        self.data.dfg.span_hints.get(&inst).cloned()
+    }
+
+    /// Get the source comments attached to an instruction.
+    ///
+    /// Returns None if the instruction has no comments, which is always the
+    /// case unless the module was parsed with comments preserved.
+    pub fn comments(self, inst: Inst) -> Option<&'a Comments> {
+        self.data.dfg.comments.get(&inst)
+    }
+
     /// Get the block ID bound.
     ///
     /// This function is useful for creating dense vectors to associate data
@@ -606,12 +788,14 @@ impl<'a> Unit<'a> {
         self.data.layout.inst_map.contains_key(&inst)
     }
 
-    /// Get the first instruction in the layout.
+    /// Get the first instruction in a block, or `None` if it is empty.
     pub fn first_inst(self, bb: Block) -> Option<Inst> {
         self.data.layout.bbs[bb].layout.first_inst()
     }
 
-    /// Get the last instruction in the layout.
+    /// Get the last instruction in a block, or `None` if it is empty.
+    ///
+    /// In a well-formed unit this is the block's terminator.
     pub fn last_inst(self, bb: Block) -> Option<Inst> {
         self.data.layout.bbs[bb].layout.last_inst()
     }
@@ -628,9 +812,10 @@ impl<'a> Unit<'a> {
         self.data.layout.bbs[bb].layout.next_inst(inst)
     }
 
-    /// Get the terminator instruction in the layout.
+    /// Get the terminator instruction of a block.
     ///
-    /// The fallible alternative is `last_inst(bb)`.
+    /// Panics if the block is empty. The fallible alternative is
+    /// `last_inst(bb)`.
     pub fn terminator(self, bb: Block) -> Inst {
         match self.last_inst(bb) {
             Some(term) => term,
@@ -1004,6 +1189,25 @@ impl<'a> UnitBuilder<'a> {
         self.data.dfg.names.remove(&value)
     }
 
+    /// Set the name of a value, instruction, block, or argument.
+    ///
+    /// Instructions are named after their result, and arguments after their
+    /// value. Panics if an instruction has no result.
+    pub fn set_name_of(&mut self, obj: impl Into<AnyObject>, name: String) {
+        match obj.into() {
+            AnyObject::Value(value) => self.set_name(value, name),
+            AnyObject::Inst(inst) => match self.get_inst_result(inst) {
+                Some(value) => self.set_name(value, name),
+                None => panic!("{} has no result to be named", inst.dump(self)),
+            },
+            AnyObject::Block(bb) => self.set_block_name(bb, name),
+            AnyObject::Arg(arg) => {
+                let value = self.arg_value(arg);
+                self.set_name(value, name)
+            }
+        }
+    }
+
     /// Set the anonymous name hint of a value.
     pub fn set_anonymous_hint(&mut self, value: Value, hint: u32) {
         self.data.dfg.anonymous_hints.insert(value, hint);
@@ -1016,6 +1220,9 @@ impl<'a> UnitBuilder<'a> {
 
     /// Replace all uses of a value with another.
     ///
+    /// Only the operands of instructions are replaced. The instruction or
+    /// argument that defines `from` remains, as do block operands.
+    ///
     /// Returns how many uses were replaced.
     pub fn replace_use(&mut self, from: Value, to: Value) -> usize {
         let mut count = 0;
@@ -1048,6 +1255,12 @@ impl<'a> UnitBuilder<'a> {
         count
     }
 
+    /// Swap the first two arguments of an instruction.
+    pub fn swap_args(&mut self, inst: Inst) {
+        #[allow(deprecated)]
+        self[inst].args_mut().swap(0, 1);
+    }
+
     /// Replace all uses of a block with another.
     ///
     /// Returns how many blocks were replaced.
@@ -1128,6 +1341,33 @@ impl<'a> UnitBuilder<'a> {
     pub fn set_location_hint(&mut self, inst: Inst, loc: usize) {
         self.data.dfg.location_hints.insert(inst, loc);
     }
+
+    /// Add a span hint to an instruction.
+    ///
+    /// Annotates the range of bytes an instruction occupies in the input file.
+    pub fn set_span_hint(&mut self, inst: Inst, span: Span) {
+        self.data.dfg.span_hints.insert(inst, span);
+    }
+
+    /// Replace the type of every value with the equal type in `cx`, such that
+    /// equal types share their storage.
+    pub(crate) fn intern_types(&mut self, cx: &mut TypeContext) {
+        for value in self.data.dfg.values.values_mut() {
+            match value {
+                ValueData::Inst { ty, .. }
+                | ValueData::Arg { ty, .. }
+                | ValueData::Placeholder { ty } => *ty = cx.intern(ty.clone()),
+                ValueData::Invalid => (),
+            }
+        }
+    }
+
+    /// Attach source comments to an instruction.
+    ///
+    /// The writer emits the comments along with the instruction.
+    pub fn set_comments(&mut self, inst: Inst, comments: Comments) {
+        self.data.dfg.comments.insert(inst, comments);
+    }
 }
 
 /// # Basic Block Layout
@@ -1329,8 +1569,103 @@ impl<'a> UnitBuilder<'a> {
         self.data.layout.bbs[bb].layout.remove_inst(inst);
         self.data.layout.inst_map.remove(&inst);
     }
+
+    /// Reorder the instructions of an entity such that every value is defined
+    /// before it is used.
+    ///
+    /// Instructions are kept in their current order as far as their operands
+    /// allow, such that sorting an already sorted entity leaves it unchanged.
+    /// Signals do not need to be sorted by the data flowing through them, since
+    /// probes and drives do not depend on each other. Fails if the operands of
+    /// the instructions form a cycle, in which case the entity is left
+    /// unchanged.
+    ///
+    /// Panics if the unit is not an entity.
+    pub fn topologically_sort(&mut self) -> Result<(), CombLoopError> {
+        assert!(self.is_entity(), "only entities can be sorted");
+        let bb = self.entry();
+        let insts: Vec<Inst> = self.insts(bb).collect();
+        let indices: HashMap<Inst, usize> =
+            insts.iter().enumerate().map(|(i, &x)| (x, i)).collect();
+
+        // Count the unsorted operands of each instruction.
+        let mut pending = vec![0; insts.len()];
+        let mut users = vec![vec![]; insts.len()];
+        for (i, &inst) in insts.iter().enumerate() {
+            for &arg in self[inst].args() {
+                if let Some(def) = self.get_value_inst(arg).and_then(|def| indices.get(&def)) {
+                    pending[i] += 1;
+                    users[*def].push(i);
+                }
+            }
+        }
+
+        // Repeatedly place the earliest instruction whose operands have all
+        // been placed.
+        let mut ready: BTreeSet<usize> = (0..insts.len()).filter(|&i| pending[i] == 0).collect();
+        let mut order = Vec::with_capacity(insts.len());
+        while let Some(i) = ready.iter().next().cloned() {
+            ready.remove(&i);
+            order.push(i);
+            for &user in &users[i] {
+                pending[user] -= 1;
+                if pending[user] == 0 {
+                    ready.insert(user);
+                }
+            }
+        }
+
+        // Report the instructions that are part of a cycle, and not merely
+        // depend on one.
+        if order.len() < insts.len() {
+            let mut remaining: Vec<usize> = (0..insts.len()).filter(|&i| pending[i] > 0).collect();
+            loop {
+                let before = remaining.len();
+                let feeds = |i: usize, remaining: &[usize]| {
+                    users[i].iter().any(|user| remaining.contains(user))
+                };
+                remaining = remaining
+                    .iter()
+                    .cloned()
+                    .filter(|&i| feeds(i, &remaining))
+                    .collect();
+                if remaining.len() == before {
+                    break;
+                }
+            }
+            return Err(CombLoopError {
+                insts: remaining.into_iter().map(|i| insts[i]).collect(),
+            });
+        }
+
+        for i in order {
+            self.remove_inst(insts[i]);
+            self.append_inst(insts[i], bb);
+        }
+        Ok(())
+    }
+}
+
+/// An error returned when the instructions of an entity cannot be sorted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CombLoopError {
+    /// The instructions whose operands form one or more cycles, in layout
+    /// order.
+    pub insts: Vec<Inst>,
 }
 
+impl std::fmt::Display for CombLoopError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "combinational loop among instructions")?;
+        for (i, inst) in self.insts.iter().enumerate() {
+            write!(f, "{} {}", if i > 0 { "," } else { "" }, inst)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for CombLoopError {}
+
 // Allow builders to be borrowed as the unit being built.
 
 impl<'a> std::borrow::Borrow<Unit<'a>> for UnitBuilder<'a> {
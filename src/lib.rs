@@ -13,9 +13,11 @@ extern crate serde;
 #[macro_use]
 pub mod assembly;
 pub mod analysis;
+pub mod interp;
 pub mod ir;
 pub mod opt;
 pub mod pass;
+pub mod sim;
 pub mod table;
 pub mod ty;
 pub mod value;
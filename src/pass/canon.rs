@@ -0,0 +1,37 @@
+// Copyright (c) 2017-2020 Fabian Schuiki
+
+//! Canonicalization
+
+use crate::ir::prelude::*;
+use crate::opt::prelude::*;
+
+/// Canonicalization
+///
+/// This pass brings instructions into a canonical form, such that equivalent
+/// instructions look identical to later passes like GCSE. The operands of
+/// commutative instructions are ordered such that constants come last, and
+/// otherwise by value.
+pub struct Canonicalization;
+
+impl Pass for Canonicalization {
+    fn run_on_inst(_ctx: &PassContext, inst: Inst, unit: &mut UnitBuilder) -> bool {
+        if !unit[inst].opcode().is_commutative() {
+            return false;
+        }
+        let args = unit[inst].args();
+        let key = |value| (is_const(unit, value), value);
+        if key(args[0]) <= key(args[1]) {
+            return false;
+        }
+        debug!("Swap operands of {}", inst.dump(unit));
+        unit.swap_args(inst);
+        true
+    }
+}
+
+/// Check whether a value is produced by a constant instruction.
+fn is_const(unit: &Unit, value: Value) -> bool {
+    unit.get_value_inst(value)
+        .map(|inst| unit[inst].opcode().is_const())
+        .unwrap_or(false)
+}
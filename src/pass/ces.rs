@@ -0,0 +1,71 @@
+// Copyright (c) 2017-2020 Fabian Schuiki
+
+//! Critical Edge Splitting
+
+use crate::{analysis::PredecessorTable, ir::prelude::*, opt::prelude::*};
+use std::collections::HashSet;
+
+/// Critical Edge Splitting
+///
+/// This pass inserts an empty block on every edge in the control flow graph
+/// that leads from a block with multiple successors to a block with multiple
+/// predecessors. This gives code motion and phi placement a location that is
+/// executed if and only if the edge is taken.
+pub struct CriticalEdgeSplitting;
+
+impl Pass for CriticalEdgeSplitting {
+    fn run_on_cfg(_ctx: &PassContext, unit: &mut UnitBuilder) -> bool {
+        if unit.is_entity() {
+            return false;
+        }
+        info!("CES [{}]", unit.name());
+        let pt = unit.predtbl();
+        split_critical_edges(unit, &pt)
+    }
+}
+
+/// Split the critical edges in a unit.
+///
+/// Each critical edge is routed through a new block that unconditionally
+/// branches to the original target. Phi nodes in the target are updated to
+/// refer to the new block. Returns `true` if the unit was modified.
+pub fn split_critical_edges(unit: &mut UnitBuilder, pt: &PredecessorTable) -> bool {
+    // Collect the critical edges in a deterministic order.
+    let mut edges = vec![];
+    for pred in unit.blocks() {
+        if pt.succ_set(pred).len() < 2 {
+            continue;
+        }
+        let mut seen = HashSet::new();
+        for &succ in unit[unit.terminator(pred)].blocks() {
+            if seen.insert(succ) && pt.pred_set(succ).len() > 1 {
+                edges.push((pred, succ));
+            }
+        }
+    }
+
+    // Route each edge through a new block.
+    for &(pred, succ) in &edges {
+        let bb = unit.block();
+        unit.remove_block(bb);
+        unit.insert_block_before(bb, succ);
+        debug!(
+            "Split edge {} -> {} at {}",
+            pred.dump(unit),
+            succ.dump(unit),
+            bb.dump(unit)
+        );
+        let term = unit.terminator(pred);
+        unit.replace_block_within_inst(succ, bb, term);
+        unit.append_to(bb);
+        unit.ins().br(succ);
+        let phis: Vec<Inst> = unit
+            .insts(succ)
+            .filter(|&inst| unit[inst].opcode() == Opcode::Phi)
+            .collect();
+        for phi in phis {
+            unit.replace_block_within_inst(pred, bb, phi);
+        }
+    }
+    !edges.is_empty()
+}
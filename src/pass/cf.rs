@@ -34,17 +34,12 @@ pub fn run_on_inst(unit: &mut UnitBuilder, inst: Inst) -> bool {
     let value = unit.inst_result(inst);
     let ty = unit.value_type(value);
     let data = &unit[inst];
-    let replacement = match data.opcode() {
-        Opcode::InsSlice => fold_ins_slice(unit, inst),
-        Opcode::ExtSlice => fold_ext_slice(unit, inst),
-        Opcode::ExtField => fold_ext_field(unit, inst),
-        Opcode::Shl | Opcode::Shr => fold_shift(unit, inst, &ty),
-        Opcode::Mux => fold_mux(unit, inst),
-        _ => match *data {
-            InstData::Unary { opcode, args, .. } => fold_unary(unit, opcode, ty.clone(), args[0]),
-            InstData::Binary { opcode, args, .. } => fold_binary(unit, opcode, ty.clone(), args),
-            _ => None,
-        },
+    let replacement = if propagates_poison(data.opcode())
+        && data.args().iter().any(|&arg| is_poison(unit, arg))
+    {
+        Some(unit.ins().const_poison(ty.clone()))
+    } else {
+        fold_inst(unit, inst, &ty)
     };
     if let Some(replacement) = replacement {
         let new_ty = unit.value_type(replacement);
@@ -55,7 +50,9 @@ pub fn run_on_inst(unit: &mut UnitBuilder, inst: Inst) -> bool {
             inst.dump(&unit),
         );
         if let Some(name) = unit.get_name(value).map(String::from) {
-            unit.set_name(replacement, name);
+            if unit.get_name(replacement).is_none() {
+                unit.set_name(replacement, name);
+            }
             unit.clear_name(value);
         }
         unit.replace_use(value, replacement);
@@ -66,6 +63,77 @@ pub fn run_on_inst(unit: &mut UnitBuilder, inst: Inst) -> bool {
     }
 }
 
+/// Fold an instruction that does not propagate its poison operands.
+fn fold_inst(unit: &mut UnitBuilder, inst: Inst, ty: &Type) -> Option<Value> {
+    let data = &unit[inst];
+    match data.opcode() {
+        Opcode::InsSlice => fold_ins_slice(unit, inst),
+        Opcode::ExtSlice => fold_ext_slice(unit, inst),
+        Opcode::ExtField => fold_ext_field(unit, inst),
+        Opcode::Shl | Opcode::Shr => fold_shift(unit, inst, ty),
+        Opcode::Mux => fold_mux(unit, inst),
+        Opcode::Sel => fold_sel(unit, inst, ty),
+        Opcode::Uaddo | Opcode::Saddo | Opcode::Usubo | Opcode::Ssubo => fold_overflow(unit, inst),
+        Opcode::Eq | Opcode::Neq if !unit.value_type(data.args()[0]).is_int() => {
+            fold_equality(unit, inst)
+        }
+        _ => match *data {
+            InstData::Unary { opcode, args, .. } => fold_unary(unit, opcode, ty.clone(), args[0]),
+            InstData::Binary { opcode, args, .. } => fold_binary(unit, opcode, ty.clone(), args),
+            _ => None,
+        },
+    }
+}
+
+/// Check whether an instruction yields poison if any of its operands is
+/// poison.
+fn propagates_poison(op: Opcode) -> bool {
+    matches!(
+        op,
+        Opcode::Not
+            | Opcode::Neg
+            | Opcode::Add
+            | Opcode::Sub
+            | Opcode::And
+            | Opcode::Or
+            | Opcode::Xor
+            | Opcode::Smul
+            | Opcode::Sdiv
+            | Opcode::Smod
+            | Opcode::Srem
+            | Opcode::Umul
+            | Opcode::Udiv
+            | Opcode::Umod
+            | Opcode::Urem
+            | Opcode::Uaddo
+            | Opcode::Saddo
+            | Opcode::Usubo
+            | Opcode::Ssubo
+            | Opcode::Eq
+            | Opcode::Neq
+            | Opcode::Slt
+            | Opcode::Sgt
+            | Opcode::Sle
+            | Opcode::Sge
+            | Opcode::Ult
+            | Opcode::Ugt
+            | Opcode::Ule
+            | Opcode::Uge
+            | Opcode::Shl
+            | Opcode::Shr
+            | Opcode::Mux
+            | Opcode::ExtField
+            | Opcode::ExtSlice
+    )
+}
+
+/// Check whether a value is a poison constant.
+fn is_poison(unit: &UnitBuilder, value: Value) -> bool {
+    unit.get_value_inst(value)
+        .map(|inst| unit[inst].opcode() == Opcode::ConstPoison)
+        .unwrap_or(false)
+}
+
 /// Fold a value.
 ///
 /// If the value is an instruction, folds it.
@@ -79,6 +147,14 @@ pub fn run_on_value(unit: &mut UnitBuilder, value: Value) -> bool {
 
 /// Fold a unary instruction.
 fn fold_unary(unit: &mut UnitBuilder, op: Opcode, ty: Type, arg: Value) -> Option<Value> {
+    // Fold `not (not %x)` to `%x`.
+    if op == Opcode::Not {
+        if let Some(inst) = unit.get_value_inst(arg) {
+            if unit[inst].opcode() == Opcode::Not {
+                return Some(unit[inst].args()[0]);
+            }
+        }
+    }
     if ty.is_int() {
         fold_unary_int(unit, op, arg)
     } else {
@@ -112,6 +188,15 @@ fn fold_binary_int(
     let imm0 = unit.get_const_int(args[0]);
     let imm1 = unit.get_const_int(args[1]);
 
+    // Handle operations on two identical arguments.
+    if args[0] == args[1] {
+        match op {
+            Opcode::And | Opcode::Or => return Some(args[0]),
+            Opcode::Xor | Opcode::Sub => return Some(unit.ins().const_int(IntValue::zero(width))),
+            _ => (),
+        }
+    }
+
     // Handle symmetric operations between a constant and a variable argument.
     let (arg_kon, arg_var) = match (imm0, imm1) {
         (None, Some(_)) => (imm1, args[0]),
@@ -123,9 +208,7 @@ fn fold_binary_int(
             Opcode::And | Opcode::Smul | Opcode::Umul if a.is_zero() => {
                 return Some(unit.ins().const_int(IntValue::zero(width)))
             }
-            Opcode::Or | Opcode::Xor | Opcode::Add | Opcode::Sub if a.is_zero() => {
-                return Some(arg_var)
-            }
+            Opcode::Or | Opcode::Xor | Opcode::Add if a.is_zero() => return Some(arg_var),
             Opcode::Smul | Opcode::Umul if a.is_one() => return Some(arg_var),
             Opcode::Or if a.is_all_ones() => {
                 return Some(unit.ins().const_int(IntValue::all_ones(width)))
@@ -144,6 +227,7 @@ fn fold_binary_int(
     };
     if let Some(a) = arg_kon {
         match op {
+            Opcode::Sub if a.is_zero() => return Some(arg_var),
             Opcode::Sdiv | Opcode::Udiv if a.is_one() => return Some(arg_var),
             Opcode::Smod | Opcode::Umod | Opcode::Srem | Opcode::Urem if a.is_one() => {
                 return Some(unit.ins().const_int(IntValue::zero(width)))
@@ -160,6 +244,36 @@ fn fold_binary_int(
     Some(unit.ins().const_int(result))
 }
 
+/// Fold an equality comparison of non-integer values.
+///
+/// Aggregates are compared element by element, which amounts to comparing the
+/// constant values as a whole.
+fn fold_equality(unit: &mut UnitBuilder, inst: Inst) -> Option<Value> {
+    let args = unit[inst].args();
+    let equal = if args[0] == args[1] {
+        true
+    } else {
+        unit.get_const(args[0])? == unit.get_const(args[1])?
+    };
+    let result = equal == (unit[inst].opcode() == Opcode::Eq);
+    Some(
+        unit.ins()
+            .const_int(IntValue::from_usize(1, result as usize)),
+    )
+}
+
+/// Fold an overflow-checked arithmetic instruction.
+fn fold_overflow(unit: &mut UnitBuilder, inst: Inst) -> Option<Value> {
+    let opcode = unit[inst].opcode();
+    let args = unit[inst].args();
+    let imm0 = unit.get_const_int(args[0])?;
+    let imm1 = unit.get_const_int(args[1])?;
+    let (result, overflow) = IntValue::try_overflow_op(opcode, imm0, imm1)?;
+    let result = unit.ins().const_int(result);
+    let overflow = unit.ins().const_int(overflow);
+    Some(unit.ins().strukt(vec![result, overflow]))
+}
+
 /// Fold a shift instruction.
 fn fold_shift(unit: &mut UnitBuilder, inst: Inst, ty: &Type) -> Option<Value> {
     let base = unit[inst].args()[0];
@@ -292,3 +406,121 @@ fn fold_mux(unit: &mut UnitBuilder, inst: Inst) -> Option<Value> {
     let const_sel = unit.get_const_int(sel)?.to_usize();
     Some(unit.ins().ext_field(choices, const_sel))
 }
+
+/// Fold a sel instruction.
+///
+/// Only a poison condition makes the result poison. A poison arm that is not
+/// selected has no effect.
+fn fold_sel(unit: &mut UnitBuilder, inst: Inst, ty: &Type) -> Option<Value> {
+    let args = unit[inst].args();
+    if is_poison(unit, args[0]) {
+        return Some(unit.ins().const_poison(ty.clone()));
+    }
+    let cond = unit.get_const_int(args[0])?;
+    Some(if cond.is_zero() { args[2] } else { args[1] })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assembly::{parse_module, write_module_string};
+
+    #[test]
+    fn zero_extend_constant() {
+        let mut module = parse_module(
+            "
+            func @foo () i32 {
+            %entry:
+                %zero = const i32 0
+                %x = const i8 200
+                %ext = inss i32 %zero, i8 %x, 0, 8
+                %trunc = exts i4, i32 %ext, 4, 4
+                %y = inss i32 %ext, i4 %trunc, 8, 4
+                ret i32 %y
+            }
+            ",
+        )
+        .unwrap();
+        assert!(ConstFolding::run_on_module(&PassContext, &mut module));
+        crate::pass::DeadCodeElim::run_on_module(&PassContext, &mut module);
+        assert_eq!(
+            write_module_string(&module),
+            "func @foo () i32 {
+entry:
+    %y = const i32 3272
+    ret i32 %y
+}
+"
+        );
+    }
+
+    #[test]
+    fn aggregate_equality() {
+        let mut module = parse_module(
+            "
+            func @foo ({i8, i8} %a) i1 {
+            %entry:
+                %one = const i8 1
+                %two = const i8 2
+                %s0 = {i8 %one, i8 %two}
+                %s1 = {i8 %one, i8 %two}
+                %s2 = {i8 %two, i8 %one}
+                %x = eq {i8, i8} %s0, %s1
+                %y = neq {i8, i8} %s0, %s2
+                %z = eq {i8, i8} %a, %s0
+                %w = neq {i8, i8} %a, %a
+                %r0 = and i1 %x, %y
+                %r1 = or i1 %z, %w
+                %r = and i1 %r0, %r1
+                ret i1 %r
+            }
+            ",
+        )
+        .unwrap();
+        assert!(ConstFolding::run_on_module(&PassContext, &mut module));
+        crate::pass::DeadCodeElim::run_on_module(&PassContext, &mut module);
+        assert_eq!(
+            write_module_string(&module),
+            "func @foo ({i8, i8} %a) i1 {
+entry:
+    %one = const i8 1
+    %two = const i8 2
+    %s0 = {i8 %one, i8 %two}
+    %z = eq {i8, i8} %a, %s0
+    ret i1 %z
+}
+"
+        );
+    }
+
+    #[test]
+    fn sel_poison() {
+        let mut module = parse_module(
+            "
+            func @foo (i32 %a) {i32, i32} {
+            %entry:
+                %one = const i1 1
+                %p = const i32 poison
+                %pc = const i1 poison
+                %x = sel i32 %one, %a, %p
+                %y = sel i32 %pc, %a, %a
+                %z = {i32 %x, i32 %y}
+                ret {i32, i32} %z
+            }
+            ",
+        )
+        .unwrap();
+        assert!(ConstFolding::run_on_module(&PassContext, &mut module));
+        crate::pass::DeadCodeElim::run_on_module(&PassContext, &mut module);
+        assert_eq!(
+            write_module_string(&module),
+            "func @foo (i32 %a) {i32, i32} {
+entry:
+    %y = const i32 poison
+    %z = {i32 %a, i32 %y}
+    ret {i32, i32} %z
+}
+"
+        );
+    }
+}
@@ -0,0 +1,138 @@
+// Copyright (c) 2017-2020 Fabian Schuiki
+
+//! Drive Coalescing
+
+use crate::{ir::prelude::*, opt::prelude::*};
+use std::collections::HashMap;
+
+/// Drive Coalescing
+///
+/// This pass removes drives in entities that are overridden by a later drive.
+/// See `coalesce_drives` for details.
+pub struct DriveCoalescing;
+
+impl Pass for DriveCoalescing {
+    fn run_on_unit(_ctx: &PassContext, unit: &mut UnitBuilder) -> bool {
+        if !unit.is_entity() {
+            return false;
+        }
+        info!("DrvCoal [{}]", unit.name());
+        coalesce_drives(unit)
+    }
+}
+
+/// Remove drives that are overridden by a later drive.
+///
+/// An unconditional drive of a signal replaces all earlier drives of the same
+/// signal with the same delay, since the last drive wins. Drives with a
+/// different delay are kept. Any other use of the signal in between, such as a
+/// probe, keeps the earlier drives as well.
+///
+/// Returns `true` if the unit was modified.
+pub fn coalesce_drives(unit: &mut UnitBuilder) -> bool {
+    if !unit.is_entity() {
+        return false;
+    }
+    let mut pending: HashMap<Value, Vec<Inst>> = HashMap::new();
+    let mut dead = vec![];
+    for inst in unit.all_insts() {
+        let data = &unit[inst];
+        match data.opcode() {
+            Opcode::Drv | Opcode::DrvCond => {
+                let signal = data.args()[0];
+                let delay = data.args()[2];
+                let earlier = pending.entry(signal).or_default();
+                if data.opcode() == Opcode::Drv {
+                    earlier.retain(|&other| {
+                        if same_delay(unit, unit[other].args()[2], delay) {
+                            dead.push(other);
+                            false
+                        } else {
+                            true
+                        }
+                    });
+                }
+                earlier.push(inst);
+                for arg in &data.args()[1..] {
+                    pending.remove(arg);
+                }
+            }
+            _ => {
+                for arg in data.args() {
+                    pending.remove(arg);
+                }
+            }
+        }
+    }
+    for &inst in &dead {
+        debug!("Removing {}", inst.dump(unit));
+        unit.delete_inst(inst);
+    }
+    !dead.is_empty()
+}
+
+/// Check whether two delays are known to be equal.
+fn same_delay(unit: &Unit, a: Value, b: Value) -> bool {
+    if a == b {
+        return true;
+    }
+    match (unit.get_const_time(a), unit.get_const_time(b)) {
+        (Some(a), Some(b)) => a == b,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assembly::parse_module;
+
+    #[test]
+    fn two_drives() {
+        let mut module = parse_module(
+            "
+            entity @foo (i1$ %a) -> (i1$ %x, i1$ %y) {
+                %t0 = const time 0s 1e
+                %t1 = const time 0s 1e
+                %t2 = const time 1ns
+                %ap = prb i1$ %a
+                %an = not i1 %ap
+                drv i1$ %x, %ap, %t0
+                drv i1$ %x, %an, %t1
+                drv i1$ %y, %ap, %t0
+                drv i1$ %y, %an, %t2
+            }
+            ",
+        )
+        .unwrap();
+        assert!(DriveCoalescing::run_on_module(&PassContext, &mut module));
+        let unit = module.units().next().unwrap();
+        let drives: Vec<_> = unit
+            .all_insts()
+            .filter(|&inst| unit[inst].opcode() == Opcode::Drv)
+            .map(|inst| inst.dump(&unit).to_string())
+            .collect();
+        assert_eq!(
+            drives,
+            vec!["drv %x, %an, %t1", "drv %y, %ap, %t0", "drv %y, %an, %t2"]
+        );
+        assert!(!DriveCoalescing::run_on_module(&PassContext, &mut module));
+    }
+
+    #[test]
+    fn probe_in_between() {
+        let mut module = parse_module(
+            "
+            entity @foo (i1$ %a) -> (i1$ %x) {
+                %t = const time 0s 1e
+                %ap = prb i1$ %a
+                drv i1$ %x, %ap, %t
+                %xp = prb i1$ %x
+                drv i1$ %x, %xp, %t
+            }
+            ",
+        )
+        .unwrap();
+        assert!(!DriveCoalescing::run_on_module(&PassContext, &mut module));
+    }
+}
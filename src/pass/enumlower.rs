@@ -0,0 +1,164 @@
+// Copyright (c) 2017-2020 Fabian Schuiki
+
+//! Enum Lowering
+
+use crate::{
+    ir::prelude::*,
+    opt::prelude::*,
+    ty::{int_ty, TypeKind},
+};
+use std::cell::Cell;
+
+/// Enum Lowering
+///
+/// This pass replaces enum types with integer types, for backends that do not
+/// support `nN`. See `lower_enums` for details.
+pub struct EnumLowering;
+
+impl Pass for EnumLowering {
+    fn run_on_module(_ctx: &PassContext, module: &mut Module) -> bool {
+        lower_enums(module)
+    }
+}
+
+/// Replace enum-typed values with integers.
+///
+/// Every value of type `nN` becomes a value of type `iM`, where `M` is the
+/// number of bits needed to encode the `N` states (but at least one). This
+/// includes enums nested in signals, pointers, arrays, and structs, as well as
+/// the ports of units, the signatures of declarations and external units, and
+/// the named types and constants of the module. Enum constants become integer
+/// constants with the same value, and instructions consuming enums, such as
+/// comparisons and `mux` selectors, operate on the integers instead.
+///
+/// Since all enums with the same number of states map to the same integer
+/// type, and every signature is rewritten along with its users, the operand
+/// types of all instructions still line up. No `zext` or `trunc` is required.
+///
+/// Returns `true` if the module was modified.
+pub fn lower_enums(module: &mut Module) -> bool {
+    let modified = Cell::new(false);
+    module
+        .map_types(|ty| match **ty {
+            TypeKind::EnumType(..) => {
+                modified.set(true);
+                int_ty(ty.bit_width().unwrap().max(1))
+            }
+            _ => ty.clone(),
+        })
+        .expect("enum constants fit their integer type");
+    modified.get()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assembly::parse_module;
+    use crate::value::{EnumValue, IntValue, StructValue};
+
+    #[test]
+    fn mux_selector() {
+        let mut module = parse_module(
+            "
+            entity @foo (i8$ %a, i8$ %b, i8$ %c, i8$ %d) -> (i8$ %x) {
+                %t = const time 0s 1e
+                %ap = prb i8$ %a
+                %bp = prb i8$ %b
+                %cp = prb i8$ %c
+                %dp = prb i8$ %d
+                %arr = [i8 %ap, %bp, %cp, %dp]
+                %init = const n4 0
+                %state = sig n4 %init
+                %s = prb n4$ %state
+                %m = mux [4 x i8] %arr, n4 %s
+                drv i8$ %x, %m, %t
+                %two = const n4 2
+                %eq = eq n4 %s, %two
+            }
+            ",
+        )
+        .unwrap();
+        assert!(EnumLowering::run_on_module(&PassContext, &mut module));
+        module.verify();
+        let unit = module.units().next().unwrap();
+        let insts: Vec<_> = unit
+            .all_insts()
+            .filter(|&inst| {
+                matches!(
+                    unit[inst].opcode(),
+                    Opcode::ConstInt | Opcode::Sig | Opcode::Prb | Opcode::Mux | Opcode::Eq
+                )
+            })
+            .skip(4)
+            .map(|inst| inst.dump(&unit).to_string())
+            .collect();
+        assert_eq!(
+            insts,
+            vec![
+                "%init = const i2 0",
+                "%state = sig i2$ %init",
+                "%s = prb i2 %state",
+                "%m = mux i8 %arr, %s",
+                "%two = const i2 2",
+                "%eq = eq i1 %s, %two",
+            ]
+        );
+        assert!(!EnumLowering::run_on_module(&PassContext, &mut module));
+    }
+
+    #[test]
+    fn enum_ports() {
+        let mut module = parse_module(
+            "
+            type @state = {n4, i8}
+            const @idle = n4 1
+
+            entity @fsm (n4$ %state) -> (n4$ %next) {
+                %t = const time 0s 1e
+                %s = prb n4$ %state
+                drv n4$ %next, %s, %t
+            }
+
+            entity @top () -> () {
+                %idle = const n4 1
+                %a = sig n4 %idle
+                %b = sig n4 %idle
+                inst @fsm (n4$ %a) -> (n4$ %b)
+                inst @ext (n4$ %a) -> ()
+            }
+
+            declare @ext (n4$) -> ()
+            ",
+        )
+        .unwrap();
+        let init = StructValue::new(vec![EnumValue::new(4, 1).into(), IntValue::zero(8).into()]);
+        module.add_const("init", init.into());
+        assert!(EnumLowering::run_on_module(&PassContext, &mut module));
+        module.link();
+        module.verify();
+        assert_eq!(
+            crate::assembly::write_module_string(&module),
+            "type @state = {i2, i8}
+const @idle = i2 1
+const @init = {i2 1, i8 0}
+
+entity @fsm (i2$ %state) -> (i2$ %next) {
+    %t = const time 0s 1e
+    %s = prb i2$ %state
+    drv i2$ %next, %s, %t
+}
+
+entity @top () -> () {
+    %idle = const i2 1
+    %a = sig i2 %idle
+    %b = sig i2 %idle
+    inst @fsm (i2$ %a) -> (i2$ %b)
+    inst @ext (i2$ %a) -> ()
+}
+
+declare @ext (i2$)
+"
+        );
+        assert!(!EnumLowering::run_on_module(&PassContext, &mut module));
+    }
+}
@@ -48,6 +48,8 @@ impl Pass for GlobalCommonSubexprElim {
                 || opcode == Opcode::Ld
                 || opcode == Opcode::Var
                 || opcode == Opcode::Sig
+                || opcode == Opcode::ConstUndef
+                || opcode == Opcode::ConstPoison
             {
                 continue;
             }
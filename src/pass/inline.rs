@@ -0,0 +1,268 @@
+// Copyright (c) 2017-2020 Fabian Schuiki
+
+//! Function Inlining
+
+use crate::{
+    ir::{prelude::*, ExtUnit, InstData},
+    opt::prelude::*,
+    ty::{void_ty, Type},
+};
+use std::collections::{HashMap, HashSet};
+
+/// The number of instructions below which functions are inlined by the pass.
+pub const DEFAULT_INLINE_THRESHOLD: usize = 32;
+
+/// Function Inlining
+///
+/// This pass replaces calls to small functions with a copy of the function's
+/// body. See `inline_calls` for details.
+pub struct FunctionInlining;
+
+impl Pass for FunctionInlining {
+    fn run_on_module(_ctx: &PassContext, module: &mut Module) -> bool {
+        inline_calls(module, DEFAULT_INLINE_THRESHOLD)
+    }
+}
+
+/// Inline calls to functions with fewer than `threshold` instructions.
+///
+/// The block containing the call is split after the call, and the body of the
+/// callee is copied in between the two halves. Arguments are replaced with the
+/// operands of the call, and the returned value replaces the result of the
+/// call. Functions that call themselves, directly or indirectly, are never
+/// inlined. Calls are inlined into functions and processes, but not entities.
+///
+/// Returns `true` if the module was modified.
+pub fn inline_calls(module: &mut Module, threshold: usize) -> bool {
+    let recursive = recursive_functions(module);
+    let callees: HashMap<UnitName, Callee> = module
+        .functions()
+        .filter(|unit| unit.all_insts().count() < threshold)
+        .filter(|unit| !recursive.contains(unit.name()))
+        .filter(|unit| {
+            unit.all_insts()
+                .any(|inst| unit[inst].opcode().is_return())
+        })
+        .map(|unit| (unit.name().clone(), Callee::new(&unit)))
+        .collect();
+    if callees.is_empty() {
+        return false;
+    }
+
+    let ids: Vec<_> = module
+        .units()
+        .filter(|unit| !unit.is_entity())
+        .map(|unit| unit.id())
+        .collect();
+    let mut modified = false;
+    for id in ids {
+        let mut unit = module.unit_mut(id);
+        let calls: Vec<_> = unit
+            .all_insts()
+            .filter(|&inst| unit[inst].opcode() == Opcode::Call)
+            .filter_map(|inst| {
+                let name = unit[unit[inst].get_ext_unit()?].name.clone();
+                let callee = callees.get(&name)?;
+                Some((inst, name, callee))
+            })
+            .collect();
+        for (call, name, callee) in calls {
+            debug!("Inlining call to {} in {}", name, unit.name());
+            inline_call(&mut unit, call, callee);
+            modified = true;
+        }
+    }
+    if modified {
+        module.link();
+    }
+    modified
+}
+
+/// Determine the functions that may call themselves.
+fn recursive_functions(module: &Module) -> HashSet<UnitName> {
+    let calls: HashMap<&UnitName, HashSet<&UnitName>> = module
+        .functions()
+        .map(|unit| {
+            let callees = unit
+                .all_insts()
+                .filter_map(|inst| unit[inst].get_ext_unit())
+                .map(|ext| &unit.extern_units().find(|&(e, _)| e == ext).unwrap().1.name)
+                .collect();
+            (unit.name(), callees)
+        })
+        .collect();
+    let mut recursive = HashSet::new();
+    for &start in calls.keys() {
+        let mut seen = HashSet::new();
+        let mut todo = vec![start];
+        while let Some(name) = todo.pop() {
+            for &callee in calls.get(name).into_iter().flatten() {
+                if callee == start {
+                    recursive.insert(start.clone());
+                }
+                if seen.insert(callee) {
+                    todo.push(callee);
+                }
+            }
+        }
+    }
+    recursive
+}
+
+/// An instruction of a function to be inlined, with its block and result.
+type CalleeInst = (Block, InstData, Option<(Value, Type)>);
+
+/// A copy of the body of a function to be inlined.
+struct Callee {
+    args: Vec<Value>,
+    entry: Block,
+    blocks: Vec<Block>,
+    insts: Vec<CalleeInst>,
+    value_types: HashMap<Value, Type>,
+    ext_units: HashMap<ExtUnit, (UnitName, Signature)>,
+}
+
+impl Callee {
+    fn new(unit: &Unit) -> Self {
+        let mut insts = vec![];
+        let mut value_types = HashMap::new();
+        for bb in unit.blocks() {
+            for inst in unit.insts(bb) {
+                let result = unit.get_inst_result(inst).map(|value| {
+                    let ty = unit.value_type(value);
+                    value_types.insert(value, ty.clone());
+                    (value, ty)
+                });
+                insts.push((bb, unit[inst].clone(), result));
+            }
+        }
+        for arg in unit.args() {
+            value_types.insert(arg, unit.value_type(arg));
+        }
+        Self {
+            args: unit.args().collect(),
+            entry: unit.entry(),
+            blocks: unit.blocks().collect(),
+            insts,
+            value_types,
+            ext_units: unit
+                .extern_units()
+                .map(|(ext, data)| (ext, (data.name.clone(), data.sig.clone())))
+                .collect(),
+        }
+    }
+}
+
+/// Replace a call with a copy of the callee's body.
+fn inline_call(unit: &mut UnitBuilder, call: Inst, callee: &Callee) {
+    // Split the block after the call, and redirect the phi nodes in its
+    // successors to the second half.
+    let bb = unit.inst_block(call).unwrap();
+    let cont = unit.block();
+    unit.remove_block(cont);
+    unit.insert_block_after(cont, bb);
+    while let Some(inst) = unit.next_inst(call) {
+        unit.remove_inst(inst);
+        unit.append_inst(inst, cont);
+    }
+    let phis: Vec<_> = unit
+        .all_insts()
+        .filter(|&inst| unit[inst].opcode() == Opcode::Phi && unit[inst].blocks().contains(&bb))
+        .collect();
+    for phi in phis {
+        unit.replace_block_within_inst(bb, cont, phi);
+    }
+
+    // Create the blocks of the callee.
+    let mut blocks = HashMap::new();
+    for &cb in &callee.blocks {
+        let nb = unit.block();
+        unit.remove_block(nb);
+        unit.insert_block_before(nb, cont);
+        blocks.insert(cb, nb);
+    }
+
+    // Copy the instructions, using placeholders for values that are used
+    // before they are defined.
+    let mut values: HashMap<Value, Value> = callee
+        .args
+        .iter()
+        .cloned()
+        .zip(unit[call].args().iter().cloned())
+        .collect();
+    let mut placeholders: HashMap<Value, Value> = HashMap::new();
+    let mut ext_units = HashMap::new();
+    let mut returns = vec![];
+    for (cb, data, result) in &callee.insts {
+        let nb = blocks[cb];
+        unit.append_to(nb);
+        let mut map = |unit: &mut UnitBuilder, value: Value| match values.get(&value) {
+            Some(&v) => v,
+            None => *placeholders
+                .entry(value)
+                .or_insert_with(|| unit.add_placeholder(callee.value_types[&value].clone())),
+        };
+        match data.opcode() {
+            Opcode::Ret => {
+                unit.ins().br(cont);
+            }
+            Opcode::RetValue => {
+                let value = map(unit, data.args()[0]);
+                returns.push((value, nb));
+                unit.ins().br(cont);
+            }
+            _ => {
+                let mut data = data.clone();
+                #[allow(deprecated)]
+                for arg in data.args_mut() {
+                    *arg = map(unit, *arg);
+                }
+                #[allow(deprecated)]
+                for block in data.blocks_mut() {
+                    *block = blocks[block];
+                }
+                if let InstData::Call { unit: ext, .. } = &mut data {
+                    let (name, sig) = &callee.ext_units[ext];
+                    *ext = *ext_units
+                        .entry(*ext)
+                        .or_insert_with(|| unit.add_extern(name.clone(), sig.clone()));
+                }
+                let ty = result
+                    .as_ref()
+                    .map(|(_, ty)| ty.clone())
+                    .unwrap_or_else(void_ty);
+                let inst = unit.build_inst(data, ty);
+                if let Some((value, _)) = result {
+                    let new = unit.inst_result(inst);
+                    values.insert(*value, new);
+                    if let Some(ph) = placeholders.remove(value) {
+                        unit.replace_use(ph, new);
+                        unit.remove_placeholder(ph);
+                    }
+                }
+            }
+        }
+    }
+
+    // Replace the call's result with the returned value, and jump into the
+    // copied body instead of calling.
+    if let Some(result) = unit.get_inst_result(call) {
+        let value = if returns.len() == 1 {
+            returns[0].0
+        } else {
+            unit.prepend_to(cont);
+            let (args, bbs) = returns.into_iter().unzip();
+            unit.ins().phi(args, bbs)
+        };
+        if let Some(name) = unit.get_name(result).map(String::from) {
+            if unit.get_name(value).is_none() {
+                unit.set_name(value, name);
+            }
+            unit.clear_name(result);
+        }
+        unit.replace_use(result, value);
+    }
+    unit.insert_before(call);
+    unit.ins().br(blocks[&callee.entry]);
+    unit.delete_inst(call);
+}
@@ -50,6 +50,8 @@ impl Pass for InstSimplification {
                 replace(inst, value, zero, unit)
             }
             Opcode::Mux => simplify_mux(ctx, inst, value, unit),
+            Opcode::ExtField => simplify_ext_field(ctx, inst, value, unit),
+            Opcode::ExtSlice => simplify_ext_slice(ctx, inst, value, unit),
             _ => false,
         }
     }
@@ -78,3 +80,192 @@ fn simplify_mux(_ctx: &PassContext, inst: Inst, value: Value, unit: &mut UnitBui
 
     false
 }
+
+fn simplify_ext_field(
+    _ctx: &PassContext,
+    inst: Inst,
+    value: Value,
+    unit: &mut UnitBuilder,
+) -> bool {
+    // extf (insf %a, %v, i), i -> %v
+    // extf (insf %a, %v, i), j -> extf %a, j
+    let ins_inst = match unit.get_value_inst(unit[inst].args()[0]) {
+        Some(ins_inst) if unit[ins_inst].opcode() == Opcode::InsField => ins_inst,
+        _ => return false,
+    };
+    if !unit.has_uses(value) {
+        return false;
+    }
+    let ins = &unit[ins_inst];
+    let (target, field) = (ins.args()[0], ins.args()[1]);
+    let index = unit[inst].imms()[0];
+    if ins.imms()[0] == index {
+        replace(inst, value, field, unit)
+    } else {
+        let ext = unit.ins().ext_field(target, index);
+        replace(inst, value, ext, unit)
+    }
+}
+
+fn simplify_ext_slice(
+    _ctx: &PassContext,
+    inst: Inst,
+    value: Value,
+    unit: &mut UnitBuilder,
+) -> bool {
+    // exts (inss %a, %v, o, l), o, l -> %v
+    // exts (inss %a, %v, o0, l0), o1, l1 -> exts %a, o1, l1 (if disjoint)
+    //
+    // The IR has no dedicated extension and truncation instructions. Instead,
+    // a zero extension inserts a value into the low bits of a zero constant,
+    // and a truncation extracts the low bits of a value. Truncating a zero
+    // extension back to the original width is thus covered by the first rule:
+    //
+    //   %zero = const i32 0
+    //   %ext = inss i32 %zero, i8 %x, 0, 8
+    //   %trunc = exts i8, i32 %ext, 0, 8
+    let ins_inst = match unit.get_value_inst(unit[inst].args()[0]) {
+        Some(ins_inst) if unit[ins_inst].opcode() == Opcode::InsSlice => ins_inst,
+        _ => return false,
+    };
+    if !unit.has_uses(value) {
+        return false;
+    }
+    let ins = &unit[ins_inst];
+    let (target, slice) = (ins.args()[0], ins.args()[1]);
+    let (ins_off, ins_len) = (ins.imms()[0], ins.imms()[1]);
+    let (off, len) = (unit[inst].imms()[0], unit[inst].imms()[1]);
+    if (off, len) == (ins_off, ins_len) {
+        replace(inst, value, slice, unit)
+    } else if off + len <= ins_off || ins_off + ins_len <= off {
+        let ext = unit.ins().ext_slice(target, off, len);
+        replace(inst, value, ext, unit)
+    } else {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assembly::{parse_module, write_module_string};
+
+    #[test]
+    fn truncate_zero_extension() {
+        let mut module = parse_module(
+            "
+            func @foo (i8 %x) i8 {
+            %entry:
+                %zero = const i32 0
+                %ext = inss i32 %zero, i8 %x, 0, 8
+                %a = exts i8, i32 %ext, 0, 8
+                %b = exts i4, i32 %ext, 0, 4
+                %c = add i8 %a, %a
+                %d = exts i4, i8 %c, 0, 4
+                %e = add i4 %b, %d
+                %f = inss i8 %x, i4 %e, 0, 4
+                ret i8 %f
+            }
+            ",
+        )
+        .unwrap();
+        assert!(InstSimplification::run_on_module(&PassContext, &mut module));
+        assert!(!InstSimplification::run_on_module(
+            &PassContext,
+            &mut module
+        ));
+        crate::pass::DeadCodeElim::run_on_module(&PassContext, &mut module);
+        assert_eq!(
+            write_module_string(&module),
+            "func @foo (i8 %x) i8 {
+entry:
+    %zero = const i32 0
+    %ext = inss i32 %zero, i8 %x, 0, 8
+    %b = exts i4, i32 %ext, 0, 4
+    %c = add i8 %x, %x
+    %d = exts i4, i8 %c, 0, 4
+    %e = add i4 %b, %d
+    %f = inss i8 %x, i4 %e, 0, 4
+    ret i8 %f
+}
+"
+        );
+    }
+
+    #[test]
+    fn extract_inserted_field() {
+        let mut module = parse_module(
+            "
+            func @foo ({i8, i16} %s, [4 x i8] %a, i8 %x) i8 {
+            %entry:
+                %s1 = insf {i8, i16} %s, i8 %x, 0
+                %f0 = extf i8, {i8, i16} %s1, 0
+                %f1 = extf i16, {i8, i16} %s1, 1
+                %a1 = insf [4 x i8] %a, i8 %f0, 2
+                %e2 = extf i8, [4 x i8] %a1, 2
+                %e3 = extf i8, [4 x i8] %a1, 3
+                %f1t = exts i8, i16 %f1, 0, 8
+                %y0 = add i8 %e2, %e3
+                %y = add i8 %y0, %f1t
+                ret i8 %y
+            }
+            ",
+        )
+        .unwrap();
+        assert!(InstSimplification::run_on_module(&PassContext, &mut module));
+        crate::pass::DeadCodeElim::run_on_module(&PassContext, &mut module);
+        assert_eq!(
+            write_module_string(&module),
+            "func @foo ({i8, i16} %s, [4 x i8] %a, i8 %x) i8 {
+entry:
+    %0 = extf i16, {i8, i16} %s, 1
+    %1 = extf i8, [4 x i8] %a, 3
+    %f1t = exts i8, i16 %0, 0, 8
+    %y0 = add i8 %x, %1
+    %y = add i8 %y0, %f1t
+    ret i8 %y
+}
+"
+        );
+    }
+
+    #[test]
+    fn extract_inserted_slice() {
+        let mut module = parse_module(
+            "
+            func @foo (i32 %a, i8 %x) i32 {
+            %entry:
+                %a1 = inss i32 %a, i8 %x, 8, 8
+                %same = exts i8, i32 %a1, 8, 8
+                %below = exts i8, i32 %a1, 0, 8
+                %above = exts i16, i32 %a1, 16, 16
+                %overlap = exts i8, i32 %a1, 12, 8
+                %b0 = inss i32 %a, i8 %same, 0, 8
+                %b1 = inss i32 %b0, i8 %below, 8, 8
+                %b2 = inss i32 %b1, i16 %above, 16, 16
+                %b3 = inss i32 %b2, i8 %overlap, 4, 8
+                ret i32 %b3
+            }
+            ",
+        )
+        .unwrap();
+        assert!(InstSimplification::run_on_module(&PassContext, &mut module));
+        crate::pass::DeadCodeElim::run_on_module(&PassContext, &mut module);
+        assert_eq!(
+            write_module_string(&module),
+            "func @foo (i32 %a, i8 %x) i32 {
+entry:
+    %a1 = inss i32 %a, i8 %x, 8, 8
+    %0 = exts i8, i32 %a, 0, 8
+    %1 = exts i16, i32 %a, 16, 16
+    %overlap = exts i8, i32 %a1, 12, 8
+    %b0 = inss i32 %a, i8 %x, 0, 8
+    %b1 = inss i32 %b0, i8 %0, 8, 8
+    %b2 = inss i32 %b1, i16 %1, 16, 16
+    %b3 = inss i32 %b2, i8 %overlap, 4, 8
+    ret i32 %b3
+}
+"
+        );
+    }
+}
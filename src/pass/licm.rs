@@ -0,0 +1,246 @@
+// Copyright (c) 2017-2020 Fabian Schuiki
+
+//! Loop-Invariant Code Motion
+
+use crate::{
+    analysis::{DominatorTree, PredecessorTable},
+    ir::prelude::*,
+    opt::prelude::*,
+};
+use std::collections::{HashMap, HashSet};
+
+/// Loop-Invariant Code Motion
+///
+/// This pass finds the natural loops in a function or process, and moves pure
+/// instructions whose arguments are all defined outside of a loop into the
+/// loop's preheader. A preheader is created if the loop does not have one.
+pub struct LoopInvariantCodeMotion;
+
+impl Pass for LoopInvariantCodeMotion {
+    fn run_on_cfg(_ctx: &PassContext, unit: &mut UnitBuilder) -> bool {
+        if unit.is_entity() {
+            return false;
+        }
+        info!("LICM [{}]", unit.name());
+
+        // Hoisting instructions out of a loop may make instructions in an
+        // enclosing loop invariant, and may change the control flow graph. So
+        // we start over after every loop we touch.
+        let mut modified = false;
+        while hoist_innermost(unit) {
+            modified = true;
+        }
+        modified
+    }
+}
+
+/// Hoist the invariant instructions out of the innermost loop that has any.
+///
+/// Returns `true` if the unit was modified.
+fn hoist_innermost(unit: &mut UnitBuilder) -> bool {
+    let pt = unit.predtbl();
+    let dt = unit.domtree_with_predtbl(&pt);
+    for (header, body) in find_loops(unit, &pt, &dt) {
+        let invariants = find_invariants(unit, &pt, &dt, header, &body);
+        if invariants.is_empty() {
+            continue;
+        }
+        let preheader = match make_preheader(unit, &pt, header, &body) {
+            Some(bb) => bb,
+            None => continue,
+        };
+        let term = unit.terminator(preheader);
+        for inst in invariants {
+            debug!("Hoist {} into {}", inst.dump(unit), preheader.dump(unit));
+            unit.remove_inst(inst);
+            unit.insert_inst_before(inst, term);
+        }
+        return true;
+    }
+    false
+}
+
+/// Find the natural loops in a unit.
+///
+/// Returns the header and body of each loop, innermost loops first. Loops that
+/// share a header are merged.
+fn find_loops(
+    unit: &Unit,
+    pt: &PredecessorTable,
+    dt: &DominatorTree,
+) -> Vec<(Block, HashSet<Block>)> {
+    let mut loops = HashMap::<Block, HashSet<Block>>::new();
+    for bb in unit.blocks() {
+        for header in pt.succ(bb) {
+            if !dt.dominates(header, bb) {
+                continue;
+            }
+            // Collect the blocks that reach the back edge without passing
+            // through the header.
+            let body = loops.entry(header).or_default();
+            body.insert(header);
+            let mut pending = vec![bb];
+            while let Some(bb) = pending.pop() {
+                if body.insert(bb) {
+                    pending.extend(pt.pred(bb));
+                }
+            }
+        }
+    }
+    let mut loops: Vec<_> = loops.into_iter().collect();
+    loops.sort_by_key(|(header, body)| (body.len(), *header));
+    loops
+}
+
+/// Find the instructions in a loop that may be hoisted out of it.
+///
+/// The instructions are returned in an order that respects their dependencies.
+/// Instructions that may trap, such as divisions, are only hoisted if they are
+/// executed whenever the loop is entered.
+fn find_invariants(
+    unit: &Unit,
+    pt: &PredecessorTable,
+    dt: &DominatorTree,
+    header: Block,
+    body: &HashSet<Block>,
+) -> Vec<Inst> {
+    // The blocks through which the loop may be left.
+    let exits: Vec<Block> = unit
+        .blocks()
+        .filter(|bb| body.contains(bb))
+        .filter(|&bb| pt.succ_set(bb).is_empty() || pt.succ(bb).any(|succ| !body.contains(&succ)))
+        .collect();
+    let always_executed = |bb: Block| {
+        bb == header || (!exits.is_empty() && exits.iter().all(|&exit| dt.dominates(bb, exit)))
+    };
+
+    let mut invariants = vec![];
+    let mut hoisted = HashSet::new();
+    let mut changed = true;
+    while changed {
+        changed = false;
+        for bb in unit.blocks().filter(|bb| body.contains(bb)) {
+            for inst in unit.insts(bb) {
+                if hoisted.contains(&inst) || !is_hoistable(unit, inst) {
+                    continue;
+                }
+                if may_trap(unit, inst) && !always_executed(bb) {
+                    continue;
+                }
+                let invariant =
+                    unit[inst]
+                        .args()
+                        .iter()
+                        .all(|&arg| match unit.get_value_inst(arg) {
+                            Some(def) => {
+                                hoisted.contains(&def)
+                                    || !body.contains(&unit.inst_block(def).unwrap())
+                            }
+                            None => true,
+                        });
+                if invariant {
+                    hoisted.insert(inst);
+                    invariants.push(inst);
+                    changed = true;
+                }
+            }
+        }
+    }
+    invariants
+}
+
+/// Check whether an instruction is free of side effects and does not depend
+/// on the temporal region it executes in.
+fn is_hoistable(unit: &Unit, inst: Inst) -> bool {
+    let op = unit[inst].opcode();
+    unit.has_result(inst)
+        && op != Opcode::Ld
+        && op != Opcode::Var
+        && op != Opcode::Sig
+        && op != Opcode::Prb
+        && op != Opcode::Phi
+        && op != Opcode::Call
+        && op != Opcode::CallIndirect
+}
+
+/// Check whether an instruction may abort execution, such as a division by
+/// zero.
+fn may_trap(unit: &Unit, inst: Inst) -> bool {
+    match unit[inst].opcode() {
+        Opcode::Udiv | Opcode::Sdiv | Opcode::Umod | Opcode::Smod | Opcode::Urem | Opcode::Srem => {
+            true
+        }
+        _ => false,
+    }
+}
+
+/// Get or create the preheader of a loop.
+///
+/// Reuses the single predecessor of the header outside of the loop if it
+/// branches to nowhere else. Otherwise a new block is inserted before the
+/// header, through which all edges entering the loop are routed. Returns
+/// `None` if the loop cannot be entered from outside.
+fn make_preheader(
+    unit: &mut UnitBuilder,
+    pt: &PredecessorTable,
+    header: Block,
+    body: &HashSet<Block>,
+) -> Option<Block> {
+    let outside: Vec<Block> = pt.pred(header).filter(|bb| !body.contains(bb)).collect();
+    match outside[..] {
+        [] => return None,
+        [pred] if pt.succ(pred).all(|bb| bb == header) => return Some(pred),
+        _ => (),
+    }
+
+    // Route the edges entering the loop through a new block.
+    let preheader = unit.block();
+    unit.remove_block(preheader);
+    unit.insert_block_before(preheader, header);
+    for &pred in &outside {
+        let term = unit.terminator(pred);
+        unit.replace_block_within_inst(header, preheader, term);
+    }
+    unit.append_to(preheader);
+
+    // Merge the values the header's phi nodes receive from outside the loop in
+    // the preheader, and rebuild the phi nodes accordingly.
+    let phis: Vec<Inst> = unit
+        .insts(header)
+        .filter(|&inst| unit[inst].opcode() == Opcode::Phi)
+        .collect();
+    let mut rebuilt = vec![];
+    for phi in phis {
+        let edges: Vec<(Value, Block)> = unit[phi]
+            .args()
+            .iter()
+            .cloned()
+            .zip(unit[phi].blocks().iter().cloned())
+            .collect();
+        let (entering, looping): (Vec<_>, Vec<_>) =
+            edges.into_iter().partition(|(_, bb)| outside.contains(bb));
+        let merged = match entering[..] {
+            [(value, _)] => value,
+            _ => {
+                let (args, bbs) = entering.into_iter().unzip();
+                unit.ins().phi(args, bbs)
+            }
+        };
+        let (mut args, mut bbs): (Vec<_>, Vec<_>) = looping.into_iter().unzip();
+        args.push(merged);
+        bbs.push(preheader);
+        rebuilt.push((phi, args, bbs));
+    }
+    unit.ins().br(header);
+    for (phi, args, bbs) in rebuilt {
+        unit.insert_before(phi);
+        let value = unit.ins().phi(args, bbs);
+        let old = unit.inst_result(phi);
+        if let Some(name) = unit.get_name(old).map(String::from) {
+            unit.set_name(value, name);
+        }
+        unit.replace_use(old, value);
+        unit.delete_inst(phi);
+    }
+    Some(preheader)
+}
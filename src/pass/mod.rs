@@ -5,24 +5,40 @@
 //! This module implements various passes that analyze or mutate an LLHD
 //! intermediate representation.
 
+pub mod canon;
+pub mod ces;
 pub mod cf;
 pub mod cfs;
+pub mod coalesce;
 pub mod dce;
 pub mod deseq;
 pub mod ecm;
+pub mod enumlower;
 pub mod gcse;
+pub mod inline;
 pub mod insim;
+pub mod licm;
 pub mod proclower;
+pub mod renum;
+pub mod sccp;
 pub mod tcm;
 pub mod vtpp;
 
+pub use canon::Canonicalization;
+pub use ces::CriticalEdgeSplitting;
 pub use cf::ConstFolding;
 pub use cfs::ControlFlowSimplification;
+pub use coalesce::DriveCoalescing;
 pub use dce::DeadCodeElim;
 pub use deseq::Desequentialization;
 pub use ecm::EarlyCodeMotion;
+pub use enumlower::EnumLowering;
 pub use gcse::GlobalCommonSubexprElim;
+pub use inline::FunctionInlining;
 pub use insim::InstSimplification;
+pub use licm::LoopInvariantCodeMotion;
 pub use proclower::ProcessLowering;
+pub use renum::TempRenumbering;
+pub use sccp::ConstPropagation;
 pub use tcm::TemporalCodeMotion;
 pub use vtpp::VarToPhiPromotion;
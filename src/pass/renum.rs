@@ -0,0 +1,86 @@
+// Copyright (c) 2017-2020 Fabian Schuiki
+
+//! Temporary Renumbering
+
+use crate::ir::prelude::*;
+use crate::opt::prelude::*;
+
+/// Temporary Renumbering
+///
+/// This pass assigns a dense sequence of anonymous names `%0`, `%1`, `%2`, etc.
+/// to the arguments, blocks, and instruction results of a unit that do not
+/// carry an explicit name. Numbers are handed out in the order in which the
+/// values and blocks appear in the unit, such that the result only depends on
+/// the structure of the unit and not on the transformations applied to it.
+pub struct TempRenumbering;
+
+impl Pass for TempRenumbering {
+    fn run_on_unit(_ctx: &PassContext, unit: &mut UnitBuilder) -> bool {
+        info!("Renum [{}]", unit.name());
+        let mut modified = false;
+        let mut index = 0;
+
+        for value in unit.args().collect::<Vec<_>>() {
+            if unit.get_name(value).is_none() {
+                modified |= unit.get_anonymous_hint(value) != Some(index);
+                unit.set_anonymous_hint(value, index);
+                index += 1;
+            }
+        }
+        for bb in unit.blocks().collect::<Vec<_>>() {
+            if unit.get_block_name(bb).is_none() {
+                modified |= unit.get_anonymous_block_hint(bb) != Some(index);
+                unit.set_anonymous_block_hint(bb, index);
+                index += 1;
+            }
+            for inst in unit.insts(bb).collect::<Vec<_>>() {
+                let value = match unit.get_inst_result(inst) {
+                    Some(value) => value,
+                    None => continue,
+                };
+                if unit.get_name(value).is_none() {
+                    modified |= unit.get_anonymous_hint(value) != Some(index);
+                    unit.set_anonymous_hint(value, index);
+                    index += 1;
+                }
+            }
+        }
+
+        modified
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assembly::parse_module;
+
+    #[test]
+    fn dense_numbering() {
+        let mut module = parse_module(
+            "
+            func @foo (i32 %3, i32 %a) i32 {
+            %7:
+                %12 = add i32 %3, %a
+                %x = sub i32 %12, %a
+                %9 = add i32 %x, %12
+                ret i32 %9
+            }
+            ",
+        )
+        .unwrap();
+        assert!(TempRenumbering::run_on_module(&PassContext, &mut module));
+        let unit = module.units().next().unwrap();
+        assert_eq!(
+            unit.to_string(),
+            "func @foo (i32 %0, i32 %a) i32 {
+%1:
+    %2 = add i32 %0, %a
+    %x = sub i32 %2, %a
+    %3 = add i32 %x, %2
+    ret %3
+}"
+        );
+        assert!(!TempRenumbering::run_on_module(&PassContext, &mut module));
+    }
+}
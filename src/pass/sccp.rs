@@ -0,0 +1,414 @@
+// Copyright (c) 2017-2020 Fabian Schuiki
+
+//! Sparse Conditional Constant Propagation
+
+use crate::{
+    ir::{prelude::*, InstData},
+    opt::prelude::*,
+    value::IntValue,
+};
+use std::collections::{HashMap, HashSet};
+
+/// Sparse Conditional Constant Propagation
+///
+/// This pass propagates values that are proven constant across the entire
+/// control flow graph of a unit. See `propagate_constants` for details.
+pub struct ConstPropagation;
+
+impl Pass for ConstPropagation {
+    fn run_on_cfg(_ctx: &PassContext, unit: &mut UnitBuilder) -> bool {
+        info!("SCCP [{}]", unit.name());
+        propagate_constants(unit)
+    }
+}
+
+/// Replace values that are constant on all executed paths with constants.
+///
+/// Evaluates the instructions of the unit on a lattice of unknown, constant
+/// integer, and varying values, starting at the entry block and following only
+/// the branches that may be taken given the values known so far. Phi nodes
+/// merge the values of their executed predecessors only. Unlike local constant
+/// folding, this proves values constant through phi nodes and loops.
+///
+/// Values proven constant are replaced with a `const` instruction, conditional
+/// branches on a constant condition become unconditional, and blocks that are
+/// never executed are removed.
+///
+/// Returns `true` if the unit was modified.
+pub fn propagate_constants(unit: &mut UnitBuilder) -> bool {
+    let mut solver = Solver::new(unit);
+    solver.solve();
+    let Solver {
+        values, executable, ..
+    } = solver;
+    let mut modified = false;
+
+    // Replace the values proven constant.
+    let consts: Vec<_> = unit
+        .all_insts()
+        .filter(|&inst| unit[inst].opcode() != Opcode::ConstInt)
+        .filter_map(|inst| {
+            let value = unit.get_inst_result(inst)?;
+            match values.get(&value) {
+                Some(Lattice::Const(imm)) => Some((inst, value, imm.clone())),
+                _ => None,
+            }
+        })
+        .collect();
+    let mut replaced = vec![];
+    for (inst, value, imm) in consts {
+        if unit[inst].opcode() == Opcode::Phi {
+            let bb = unit.inst_block(inst).unwrap();
+            match unit.insts(bb).find(|&i| unit[i].opcode() != Opcode::Phi) {
+                Some(first) => unit.insert_before(first),
+                None => unit.append_to(bb),
+            }
+        } else {
+            unit.insert_before(inst);
+        }
+        let replacement = unit.ins().const_int(imm);
+        debug!(
+            "Replacing {} with {}",
+            inst.dump(unit),
+            replacement.dump(unit)
+        );
+        if let Some(name) = unit.get_name(value).map(String::from) {
+            unit.clear_name(value);
+            unit.set_name(replacement, name);
+        }
+        unit.replace_use(value, replacement);
+        replaced.push(inst);
+    }
+    for &inst in &replaced {
+        if unit.inst_block(inst).is_some() {
+            unit.prune_if_unused(inst);
+        }
+    }
+    modified |= !replaced.is_empty();
+
+    // Replace branches on a constant condition.
+    let branches: Vec<_> = unit
+        .blocks()
+        .filter(|bb| executable.contains(bb))
+        .map(|bb| (bb, unit.terminator(bb)))
+        .filter(|&(_, inst)| unit[inst].opcode() == Opcode::BrCond)
+        .filter_map(|(bb, inst)| {
+            let imm = unit.get_const_int(unit[inst].args()[0])?;
+            let blocks = unit[inst].blocks();
+            let taken = !imm.is_zero() as usize;
+            Some((bb, inst, blocks[taken], blocks[1 - taken]))
+        })
+        .collect();
+    for (bb, inst, taken, skipped) in branches {
+        debug!("Replacing {} with br {}", inst.dump(unit), taken.dump(unit));
+        unit.insert_before(inst);
+        unit.ins().br(taken);
+        unit.delete_inst(inst);
+        if taken != skipped {
+            let phis: Vec<_> = unit
+                .insts(skipped)
+                .filter(|&inst| unit[inst].opcode() == Opcode::Phi)
+                .collect();
+            for phi in phis {
+                unit.remove_block_from_inst(bb, phi);
+            }
+        }
+        modified = true;
+    }
+
+    // Remove the blocks that are never executed.
+    let dead: Vec<_> = unit
+        .blocks()
+        .filter(|bb| !executable.contains(bb))
+        .collect();
+    for bb in dead {
+        debug!("Removing unreachable block {}", bb.dump(unit));
+        unit.delete_block(bb);
+        modified = true;
+    }
+
+    modified
+}
+
+/// The value of an instruction as far as it is known.
+///
+/// Values that have not been assigned a lattice value yet are unknown.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Lattice {
+    /// The value is always the given constant.
+    Const(IntValue),
+    /// The value may differ between executions.
+    Varying,
+}
+
+/// The state of the propagation.
+struct Solver<'a> {
+    unit: &'a Unit<'a>,
+    values: HashMap<Value, Lattice>,
+    executable: HashSet<Block>,
+    edges: HashSet<(Block, Block)>,
+    block_work: Vec<Block>,
+    inst_work: Vec<Inst>,
+}
+
+impl<'a> Solver<'a> {
+    fn new(unit: &'a Unit<'a>) -> Self {
+        let entry = unit.entry();
+        Self {
+            unit,
+            values: unit.args().map(|arg| (arg, Lattice::Varying)).collect(),
+            executable: Some(entry).into_iter().collect(),
+            edges: HashSet::new(),
+            block_work: vec![entry],
+            inst_work: vec![],
+        }
+    }
+
+    /// Evaluate instructions until no more values change.
+    fn solve(&mut self) {
+        loop {
+            if let Some(inst) = self.inst_work.pop() {
+                let bb = self.unit.inst_block(inst);
+                if bb.map(|bb| self.executable.contains(&bb)).unwrap_or(false) {
+                    self.visit(inst);
+                }
+            } else if let Some(bb) = self.block_work.pop() {
+                for inst in self.unit.insts(bb) {
+                    self.visit(inst);
+                }
+            } else {
+                break;
+            }
+        }
+    }
+
+    /// Evaluate an instruction, and update its result or successors.
+    fn visit(&mut self, inst: Inst) {
+        let unit = self.unit;
+        let data = &unit[inst];
+        if data.opcode().is_terminator() {
+            let bb = unit.inst_block(inst).unwrap();
+            let targets = match data.opcode() {
+                Opcode::BrCond => match self.get(data.args()[0]) {
+                    None => &[],
+                    Some(Lattice::Const(imm)) => {
+                        let taken = !imm.is_zero() as usize;
+                        &data.blocks()[taken..taken + 1]
+                    }
+                    Some(Lattice::Varying) => data.blocks(),
+                },
+                _ => data.blocks(),
+            };
+            for &target in targets {
+                self.mark_edge(bb, target);
+            }
+            return;
+        }
+        if let Some(value) = unit.get_inst_result(inst) {
+            if let Some(lattice) = self.evaluate(inst) {
+                self.update(value, lattice);
+            }
+        }
+    }
+
+    /// Determine the value of an instruction's result.
+    ///
+    /// Returns `None` if the value is still unknown.
+    fn evaluate(&self, inst: Inst) -> Option<Lattice> {
+        let unit = self.unit;
+        let data = &unit[inst];
+        if data.opcode() == Opcode::Phi {
+            let bb = unit.inst_block(inst).unwrap();
+            let mut result = None;
+            for (&arg, &pred) in data.args().iter().zip(data.blocks()) {
+                if !self.edges.contains(&(pred, bb)) {
+                    continue;
+                }
+                result = match (result, self.get(arg).cloned()) {
+                    (None, value) | (value, None) => value,
+                    (Some(a), Some(b)) if a == b => Some(a),
+                    _ => Some(Lattice::Varying),
+                };
+            }
+            return result;
+        }
+        if let Some(imm) = data.get_const_int() {
+            return Some(Lattice::Const(imm.clone()));
+        }
+        let result_is_int = unit.value_type(unit.inst_result(inst)).is_int();
+        let args_are_int = data.args().iter().all(|&arg| unit.value_type(arg).is_int());
+        if !result_is_int || !args_are_int {
+            return Some(Lattice::Varying);
+        }
+        let mut imms = vec![];
+        for &arg in data.args() {
+            match self.get(arg)? {
+                Lattice::Const(imm) => imms.push(imm),
+                Lattice::Varying => return Some(Lattice::Varying),
+            }
+        }
+        let result = match (data, imms.as_slice()) {
+            (InstData::Unary { opcode, .. }, [arg]) => IntValue::try_unary_op(*opcode, arg),
+            // Leave divisions by zero to fail at runtime.
+            (InstData::Binary { opcode, .. }, [_, rhs])
+                if rhs.is_zero()
+                    && matches!(
+                        opcode,
+                        Opcode::Udiv
+                            | Opcode::Sdiv
+                            | Opcode::Umod
+                            | Opcode::Smod
+                            | Opcode::Urem
+                            | Opcode::Srem
+                    ) =>
+            {
+                None
+            }
+            (InstData::Binary { opcode, .. }, [lhs, rhs]) => None
+                .or_else(|| IntValue::try_binary_op(*opcode, lhs, rhs))
+                .or_else(|| IntValue::try_compare_op(*opcode, lhs, rhs)),
+            _ => None,
+        };
+        Some(result.map(Lattice::Const).unwrap_or(Lattice::Varying))
+    }
+
+    /// Get the known value of a value, if any.
+    fn get(&self, value: Value) -> Option<&Lattice> {
+        self.values.get(&value)
+    }
+
+    /// Lower the known value of a value, and revisit its users if it changed.
+    fn update(&mut self, value: Value, lattice: Lattice) {
+        let lattice = match self.values.get(&value) {
+            None => lattice,
+            Some(old) if *old == lattice => return,
+            Some(Lattice::Varying) => return,
+            Some(_) => Lattice::Varying,
+        };
+        self.values.insert(value, lattice);
+        self.inst_work.extend(self.unit.uses(value).iter().cloned());
+    }
+
+    /// Mark a control flow edge as executed.
+    fn mark_edge(&mut self, from: Block, to: Block) {
+        if !self.edges.insert((from, to)) {
+            return;
+        }
+        if self.executable.insert(to) {
+            self.block_work.push(to);
+        } else {
+            let unit = self.unit;
+            self.inst_work.extend(
+                unit.insts(to)
+                    .filter(|&inst| unit[inst].opcode() == Opcode::Phi),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assembly::{parse_module, write_module_string};
+
+    #[test]
+    fn always_taken_branch() {
+        let mut module = parse_module(
+            "
+            func @foo (i32 %x) i32 {
+            %entry:
+                %a = const i32 2
+                %b = const i32 3
+                %c = add i32 %a, %b
+                %five = const i32 5
+                %cond = eq i32 %c, %five
+                br %cond, %no, %yes
+            %yes:
+                %y = add i32 %x, %c
+                br %exit
+            %no:
+                %n = sub i32 %x, %c
+                br %exit
+            %exit:
+                %r = phi i32 [%y, %yes], [%n, %no]
+                ret i32 %r
+            }
+            ",
+        )
+        .unwrap();
+        assert!(ConstPropagation::run_on_module(&PassContext, &mut module));
+        module.verify();
+        assert_eq!(
+            write_module_string(&module),
+            "func @foo (i32 %x) i32 {
+entry:
+    %c = const i32 5
+    %cond = const i1 1
+    br %yes
+yes:
+    %y = add i32 %x, %c
+    br %exit
+exit:
+    %r = phi i32 [%y, %yes]
+    ret i32 %r
+}
+"
+        );
+        assert!(!ConstPropagation::run_on_module(&PassContext, &mut module));
+    }
+
+    #[test]
+    fn loop_invariant_phi() {
+        let mut module = parse_module(
+            "
+            func @foo (i1 %x) i8 {
+            %entry:
+                %one = const i8 1
+                br %loop
+            %loop:
+                %p = phi i8 [%one, %entry], [%q, %loop]
+                %q = umul i8 %p, %one
+                br %x, %exit, %loop
+            %exit:
+                ret i8 %q
+            }
+            ",
+        )
+        .unwrap();
+        assert!(ConstPropagation::run_on_module(&PassContext, &mut module));
+        module.verify();
+        let unit = module.units().next().unwrap();
+        let ret = unit
+            .all_insts()
+            .find(|&inst| unit[inst].opcode() == Opcode::RetValue)
+            .unwrap();
+        assert_eq!(
+            unit.get_const_int(unit[ret].args()[0])
+                .map(|imm| imm.to_usize()),
+            Some(1)
+        );
+    }
+
+    #[test]
+    fn division_by_zero() {
+        let mut module = parse_module(
+            "
+            func @foo () i32 {
+            %entry:
+                %a = const i32 42
+                %z = const i32 0
+                %q = udiv i32 %a, %z
+                %r = srem i32 %a, %z
+                %s = add i32 %q, %r
+                ret i32 %s
+            }
+            ",
+        )
+        .unwrap();
+        assert!(!ConstPropagation::run_on_module(&PassContext, &mut module));
+        let unit = module.units().next().unwrap();
+        assert!(unit
+            .all_insts()
+            .any(|inst| unit[inst].opcode() == Opcode::Udiv));
+    }
+}
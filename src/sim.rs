@@ -0,0 +1,598 @@
+// Copyright (c) 2017-2020 Fabian Schuiki
+
+//! Simulation
+//!
+//! This module implements a minimal event-driven simulator for processes and
+//! entities, together with a writer that emits the recorded signal changes as
+//! a VCD file. Only integer-valued signals and arrays of such signals are
+//! supported.
+
+use crate::{
+    interp::{InterpError, Interpreter},
+    ir::prelude::*,
+    ty::TypeKind,
+    value::{IntValue, TimeValue},
+};
+use num::{traits::*, BigInt, BigRational};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    io::Write,
+};
+
+/// An error that occurred during simulation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SimError {
+    /// The unit to be simulated is not a process or entity.
+    NotSimulatable(String),
+    /// A signal does not carry an integer value, or cannot be traced back to
+    /// a signal argument or `sig` instruction.
+    UnsupportedSignal(String),
+    /// An instruction could not be executed.
+    Interp(InterpError),
+}
+
+impl std::fmt::Display for SimError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            SimError::NotSimulatable(name) => {
+                write!(f, "{} is not a process or entity", name)
+            }
+            SimError::UnsupportedSignal(value) => {
+                write!(f, "signal {} cannot be simulated", value)
+            }
+            SimError::Interp(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for SimError {}
+
+impl From<InterpError> for SimError {
+    fn from(err: InterpError) -> SimError {
+        SimError::Interp(err)
+    }
+}
+
+/// A signal recorded in a trace.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceSignal {
+    /// The name of the signal.
+    pub name: String,
+    /// The value of the signal at the start of the simulation.
+    pub init: IntValue,
+}
+
+/// A change of a signal's value.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceChange {
+    /// The time at which the change occurred.
+    pub time: TimeValue,
+    /// The index of the signal in the trace.
+    pub signal: usize,
+    /// The new value of the signal.
+    pub value: IntValue,
+}
+
+/// The signal value changes recorded during a simulation.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Trace {
+    /// The signals that were simulated.
+    pub signals: Vec<TraceSignal>,
+    /// The value changes, in the order in which they occurred.
+    pub changes: Vec<TraceChange>,
+}
+
+/// Simulate a process or entity.
+///
+/// The simulation runs until no more events are pending, the process halts,
+/// or the next event lies beyond `limit`. The signal arguments of the unit
+/// start out as zero, signals created by `sig` with their initial value.
+/// Drives with a zero delay take effect after one delta step. Entities are
+/// re-evaluated whenever one of the signals they probe changes.
+pub fn simulate(unit: &Unit, limit: &TimeValue) -> Result<Trace, SimError> {
+    if !unit.is_process() && !unit.is_entity() {
+        return Err(SimError::NotSimulatable(unit.name().to_string()));
+    }
+    let mut sim = Simulator {
+        unit: *unit,
+        interp: Interpreter::new(*unit),
+        signals: HashMap::new(),
+        buses: HashMap::new(),
+        values: vec![],
+        events: BTreeMap::new(),
+        time: TimeValue::zero(),
+        state: State::Halted,
+        trace: Trace::default(),
+    };
+    for arg in unit.args() {
+        if unit.value_type(arg).is_array() {
+            sim.add_bus(arg)?;
+        } else {
+            sim.add_signal(arg, None)?;
+        }
+    }
+    sim.run(limit)?;
+    Ok(sim.trace)
+}
+
+/// The execution state of the simulated unit.
+enum State {
+    /// Suspended until one of the signals changes or the time is reached.
+    Waiting {
+        bb: Block,
+        from: Option<Block>,
+        signals: Vec<usize>,
+        until: Option<TimeValue>,
+    },
+    /// Finished execution.
+    Halted,
+}
+
+struct Simulator<'a> {
+    unit: Unit<'a>,
+    interp: Interpreter<'a>,
+    /// The signal index assigned to each signal value.
+    signals: HashMap<Value, usize>,
+    /// The element signals of each array of signals.
+    buses: HashMap<Value, Vec<usize>>,
+    /// The current value of each signal.
+    values: Vec<IntValue>,
+    /// The scheduled signal drives.
+    events: BTreeMap<TimeValue, Vec<(usize, IntValue)>>,
+    time: TimeValue,
+    state: State,
+    trace: Trace,
+}
+
+impl Simulator<'_> {
+    /// Run the simulation until no more events are pending or `limit` is
+    /// exceeded.
+    fn run(&mut self, limit: &TimeValue) -> Result<(), SimError> {
+        self.execute(self.unit.entry(), None)?;
+        loop {
+            let wake = match self.state {
+                State::Waiting {
+                    until: Some(ref until),
+                    ..
+                } => Some(until.clone()),
+                _ => None,
+            };
+            let next = match (self.events.keys().next().cloned(), wake) {
+                (Some(a), Some(b)) => std::cmp::min(a, b),
+                (a, b) => match a.or(b) {
+                    Some(t) => t,
+                    None => break,
+                },
+            };
+            if next > *limit {
+                break;
+            }
+            self.time = next;
+            trace!("Simulating {}", self.time);
+
+            let changed = self.apply_events();
+            let resume = match self.state {
+                State::Waiting {
+                    bb,
+                    from,
+                    ref signals,
+                    ref until,
+                } => {
+                    if until.as_ref() == Some(&self.time)
+                        || signals.iter().any(|s| changed.contains(s))
+                    {
+                        Some((bb, from))
+                    } else {
+                        None
+                    }
+                }
+                State::Halted => None,
+            };
+            if let Some((bb, from)) = resume {
+                self.execute(bb, from)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Apply the drives scheduled for the current time.
+    ///
+    /// Returns the signals whose value changed.
+    fn apply_events(&mut self) -> HashSet<usize> {
+        let mut latest = BTreeMap::new();
+        for (signal, value) in self.events.remove(&self.time).unwrap_or_default() {
+            latest.insert(signal, value);
+        }
+        let mut changed = HashSet::new();
+        for (signal, value) in latest {
+            if self.values[signal] == value {
+                continue;
+            }
+            self.values[signal] = value.clone();
+            self.trace.changes.push(TraceChange {
+                time: self.time.clone(),
+                signal,
+                value,
+            });
+            changed.insert(signal);
+        }
+        changed
+    }
+
+    /// Execute the unit starting at a block until it suspends.
+    fn execute(&mut self, mut bb: Block, mut prev_bb: Option<Block>) -> Result<(), SimError> {
+        let unit = self.unit;
+        let mut probed = vec![];
+        'blocks: loop {
+            self.interp.enter_block(bb, prev_bb)?;
+            for inst in unit.insts(bb) {
+                let data = &unit[inst];
+                match data.opcode() {
+                    Opcode::Phi => (),
+                    Opcode::Br => {
+                        prev_bb = Some(bb);
+                        bb = data.blocks()[0];
+                        continue 'blocks;
+                    }
+                    Opcode::BrCond => {
+                        let cond = self.interp.int(data.args()[0]);
+                        prev_bb = Some(bb);
+                        bb = data.blocks()[if cond.is_zero() { 0 } else { 1 }];
+                        continue 'blocks;
+                    }
+                    Opcode::Wait | Opcode::WaitTime => {
+                        let (until, observed) = if data.opcode() == Opcode::WaitTime {
+                            let delay = self.interp.value(data.args()[0]);
+                            let until = self.time.advance(delay.unwrap_time());
+                            (Some(until), &data.args()[1..])
+                        } else {
+                            (None, data.args())
+                        };
+                        let signals = observed
+                            .iter()
+                            .map(|&v| self.signal(v))
+                            .collect::<Result<_, _>>()?;
+                        self.state = State::Waiting {
+                            bb: data.blocks()[0],
+                            from: Some(bb),
+                            signals,
+                            until,
+                        };
+                        return Ok(());
+                    }
+                    Opcode::Halt => {
+                        self.state = if unit.is_entity() {
+                            State::Waiting {
+                                bb,
+                                from: None,
+                                signals: probed,
+                                until: None,
+                            }
+                        } else {
+                            State::Halted
+                        };
+                        return Ok(());
+                    }
+                    Opcode::Sig => {
+                        let value = unit.inst_result(inst);
+                        if !self.signals.contains_key(&value) {
+                            let init = self.interp.int(data.args()[0]);
+                            self.add_signal(value, Some(init))?;
+                        }
+                    }
+                    Opcode::Prb => {
+                        let signal = self.signal(data.args()[0])?;
+                        probed.push(signal);
+                        self.interp
+                            .set(unit.inst_result(inst), self.values[signal].clone().into());
+                    }
+                    Opcode::Drv | Opcode::DrvCond => {
+                        if data.opcode() == Opcode::DrvCond
+                            && self.interp.int(data.args()[3]).is_zero()
+                        {
+                            continue;
+                        }
+                        let signal = self.signal(data.args()[0])?;
+                        let value = self.interp.int(data.args()[1]);
+                        let delay = self.interp.value(data.args()[2]).unwrap_time().clone();
+                        let delay = if delay.is_zero() {
+                            TimeValue::delta_step()
+                        } else {
+                            delay
+                        };
+                        self.events
+                            .entry(self.time.advance(&delay))
+                            .or_default()
+                            .push((signal, value));
+                    }
+                    Opcode::Array | Opcode::ArrayUniform
+                        if unit.inst_type(inst).unwrap_array().1.is_signal() =>
+                    {
+                        let signals = if data.opcode() == Opcode::ArrayUniform {
+                            vec![self.signal(data.args()[0])?; data.imms()[0]]
+                        } else {
+                            data.args()
+                                .iter()
+                                .map(|&v| self.signal(v))
+                                .collect::<Result<_, _>>()?
+                        };
+                        self.buses.insert(unit.inst_result(inst), signals);
+                    }
+                    Opcode::ExtField if self.buses.contains_key(&data.args()[0]) => {
+                        let signal = self.buses[&data.args()[0]][data.imms()[0]];
+                        self.signals.insert(unit.inst_result(inst), signal);
+                    }
+                    _ => self.interp.step(inst)?,
+                }
+            }
+
+            // Blocks without a terminator are rejected by the verifier.
+            return Err(InterpError::UnsupportedInst(format!(
+                "fall off the end of {}",
+                bb.dump(&unit)
+            ))
+            .into());
+        }
+    }
+
+    /// Register a new signal.
+    fn add_signal(&mut self, value: Value, init: Option<IntValue>) -> Result<(), SimError> {
+        let width = match *self.unit.value_type(value) {
+            TypeKind::SignalType(ref ty) if ty.is_int() => ty.unwrap_int(),
+            _ => {
+                return Err(SimError::UnsupportedSignal(
+                    value.dump(&self.unit).to_string(),
+                ))
+            }
+        };
+        let init = init.unwrap_or_else(|| IntValue::zero(width));
+        let name = value.dump(&self.unit).to_string();
+        let signal = self.push_signal(name.trim_start_matches('%').to_string(), init);
+        self.signals.insert(value, signal);
+        Ok(())
+    }
+
+    /// Register the element signals of an array of signals.
+    ///
+    /// The elements are named after the array and their index, as in
+    /// `bus[3]`, and start out as zero.
+    fn add_bus(&mut self, value: Value) -> Result<(), SimError> {
+        let (len, width) = match *self.unit.value_type(value) {
+            TypeKind::ArrayType(len, ref ty) if ty.is_signal() && ty.unwrap_signal().is_int() => {
+                (len, ty.unwrap_signal().unwrap_int())
+            }
+            _ => {
+                return Err(SimError::UnsupportedSignal(
+                    value.dump(&self.unit).to_string(),
+                ))
+            }
+        };
+        let name = value.dump(&self.unit).to_string();
+        let signals = (0..len)
+            .map(|i| {
+                let name = format!("{}[{}]", name.trim_start_matches('%'), i);
+                self.push_signal(name, IntValue::zero(width))
+            })
+            .collect();
+        self.buses.insert(value, signals);
+        Ok(())
+    }
+
+    /// Add a signal to the trace and return its index.
+    fn push_signal(&mut self, name: String, init: IntValue) -> usize {
+        self.trace.signals.push(TraceSignal {
+            name,
+            init: init.clone(),
+        });
+        self.values.push(init);
+        self.values.len() - 1
+    }
+
+    /// Look up the signal index assigned to a value.
+    fn signal(&self, value: Value) -> Result<usize, SimError> {
+        self.signals
+            .get(&value)
+            .copied()
+            .ok_or_else(|| SimError::UnsupportedSignal(value.dump(&self.unit).to_string()))
+    }
+}
+
+/// Write a simulation trace as a VCD file.
+///
+/// All signals are placed in a single `top` scope. Changes within the same
+/// physical time are collapsed to the last value, such that delta and epsilon
+/// steps do not appear in the output. The timescale is the coarsest unit in
+/// which all change times are integral.
+pub fn write_vcd(mut writer: impl Write, trace: &Trace) -> std::io::Result<()> {
+    let (scale, unit) = timescale(trace);
+    writeln!(writer, "$timescale 1{} $end", unit)?;
+    writeln!(writer, "$scope module top $end")?;
+    for (index, signal) in trace.signals.iter().enumerate() {
+        writeln!(
+            writer,
+            "$var wire {} {} {} $end",
+            signal.init.width,
+            vcd_id(index),
+            signal.name
+        )?;
+    }
+    writeln!(writer, "$upscope $end")?;
+    writeln!(writer, "$enddefinitions $end")?;
+
+    // Dump the initial values.
+    writeln!(writer, "#0")?;
+    writeln!(writer, "$dumpvars")?;
+    let mut current: Vec<&IntValue> = trace.signals.iter().map(|s| &s.init).collect();
+    for (index, value) in current.iter().enumerate() {
+        write_vcd_value(&mut writer, index, value)?;
+    }
+    writeln!(writer, "$end")?;
+
+    // Dump the changes, grouped by physical time.
+    let mut stamp = BigInt::zero();
+    let mut changes = trace.changes.iter().peekable();
+    while let Some(first) = changes.next() {
+        let mut latest = BTreeMap::new();
+        latest.insert(first.signal, &first.value);
+        while let Some(change) = changes.next_if(|c| c.time.time == first.time.time) {
+            latest.insert(change.signal, &change.value);
+        }
+        for (index, value) in latest {
+            if current[index] == value {
+                continue;
+            }
+            let time = (first.time.time.clone() * &scale).floor().to_integer();
+            if time != stamp {
+                writeln!(writer, "#{}", time)?;
+                stamp = time;
+            }
+            write_vcd_value(&mut writer, index, value)?;
+            current[index] = value;
+        }
+    }
+    Ok(())
+}
+
+/// Determine the coarsest VCD timescale in which all change times of a trace
+/// are integral.
+///
+/// Returns the factor to convert seconds into the timescale, and its unit.
+fn timescale(trace: &Trace) -> (BigRational, &'static str) {
+    let units = ["s", "ms", "us", "ns", "ps", "fs"];
+    let mut scale = BigRational::one();
+    for (i, unit) in units.iter().enumerate() {
+        let exact = trace
+            .changes
+            .iter()
+            .all(|c| (c.time.time.clone() * &scale).is_integer());
+        if exact || i + 1 == units.len() {
+            return (scale, unit);
+        }
+        scale *= BigRational::from_integer(BigInt::from(1000));
+    }
+    unreachable!()
+}
+
+/// Write a single VCD value change.
+fn write_vcd_value(mut writer: impl Write, index: usize, value: &IntValue) -> std::io::Result<()> {
+    if value.width == 1 {
+        writeln!(writer, "{}{}", value.value, vcd_id(index))
+    } else {
+        writeln!(writer, "b{:b} {}", value.value, vcd_id(index))
+    }
+}
+
+/// Compute the VCD identifier code of a signal.
+fn vcd_id(mut index: usize) -> String {
+    let mut id = String::new();
+    loop {
+        id.push((b'!' + (index % 94) as u8) as char);
+        index /= 94;
+        if index == 0 {
+            break id;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assembly::parse_module;
+
+    #[test]
+    fn drive_sequence() {
+        let module = parse_module(
+            "
+            proc @top () -> (i8$ %x) {
+            %init:
+                %eps = const time 0s 1e
+                %step = const time 1ns
+                %a = const i8 1
+                drv i8$ %x, %a, %eps
+                wait %next for %step
+            %next:
+                %b = const i8 2
+                drv i8$ %x, %b, %eps
+                wait %last for %step
+            %last:
+                %c = const i8 3
+                drv i8$ %x, %c, %eps
+                halt
+            }
+            ",
+        )
+        .unwrap();
+        let unit = module.units().next().unwrap();
+        let limit = TimeValue::new(BigRational::from_integer(BigInt::from(1)), 0, 0);
+        let trace = simulate(&unit, &limit).unwrap();
+        assert_eq!(trace.signals.len(), 1);
+        assert_eq!(trace.changes.len(), 3);
+
+        let mut vcd = vec![];
+        write_vcd(&mut vcd, &trace).unwrap();
+        let vcd = String::from_utf8(vcd).unwrap();
+        let lines: Vec<&str> = vcd.lines().collect();
+        assert!(lines.contains(&"$timescale 1ns $end"));
+        assert!(lines.contains(&"$var wire 8 ! x $end"));
+        let changes = lines.iter().position(|&l| l == "$end").unwrap() + 1;
+        assert_eq!(
+            lines[changes - 3..],
+            [
+                "$dumpvars",
+                "b0 !",
+                "$end",
+                "b1 !",
+                "#1",
+                "b10 !",
+                "#2",
+                "b11 !"
+            ]
+        );
+    }
+
+    #[test]
+    fn signal_array() {
+        let module = parse_module(
+            "
+            entity @top ([8 x i1$] %bus, i1$ %a) -> () {
+                %t = const time 1ns
+                %ap = prb i1$ %a
+                %one = const i1 1
+                %b3 = extf i1$, [8 x i1$] %bus, 3
+                drv i1$ %b3, %one, %t
+                %z = const i1 0
+                %s0 = sig i1 %z
+                %s1 = sig i1 %z
+                %pair = [i1$ %s0, %s1]
+                %p1 = extf i1$, [2 x i1$] %pair, 1
+                %b3p = prb i1$ %b3
+                drv i1$ %p1, %b3p, %t
+            }
+            ",
+        )
+        .unwrap();
+        let unit = module.units().next().unwrap();
+        let limit = TimeValue::new(BigRational::from_integer(BigInt::from(1)), 0, 0);
+        let trace = simulate(&unit, &limit).unwrap();
+        let names: Vec<_> = trace.signals.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(
+            names,
+            [
+                "bus[0]", "bus[1]", "bus[2]", "bus[3]", "bus[4]", "bus[5]", "bus[6]", "bus[7]",
+                "a", "s0", "s1"
+            ]
+        );
+        let changes: Vec<_> = trace
+            .changes
+            .iter()
+            .map(|c| (c.time.to_string(), c.signal, c.value.to_string()))
+            .collect();
+        assert_eq!(
+            changes,
+            [
+                ("1ns".to_string(), 3, "i1 1".to_string()),
+                ("2ns".to_string(), 10, "i1 1".to_string()),
+            ]
+        );
+    }
+}
@@ -3,7 +3,7 @@
 //! Types of values.
 
 use itertools::Itertools;
-use std::sync::Arc;
+use std::{collections::HashSet, sync::Arc};
 
 pub use self::TypeKind::*;
 
@@ -226,6 +226,29 @@ impl TypeKind {
             _ => 0,
         }
     }
+
+    /// Compute the number of bits needed to represent a value of this type.
+    ///
+    /// Integers occupy one bit per bit, enums the number of bits needed to
+    /// encode their states, arrays the width of their elements times their
+    /// length, and structs the sum of the widths of their fields. Signals have
+    /// the width of the value they carry.
+    ///
+    /// Returns `None` for types that have no bit representation, such as
+    /// `void`, `time`, and pointers, and for types containing them.
+    pub fn bit_width(&self) -> Option<usize> {
+        match *self {
+            IntType(w) => Some(w),
+            EnumType(n) if n <= 1 => Some(0),
+            EnumType(n) => Some((0usize.leading_zeros() - (n - 1).leading_zeros()) as usize),
+            SignalType(ref ty) => ty.bit_width(),
+            ArrayType(l, ref ty) => ty.bit_width()?.checked_mul(l),
+            StructType(ref fields) => fields
+                .iter()
+                .try_fold(0usize, |sum, ty| sum.checked_add(ty.bit_width()?)),
+            _ => None,
+        }
+    }
 }
 
 /// Create a void type.
@@ -278,3 +301,78 @@ pub fn func_ty(args: Vec<Type>, ret: Type) -> Type {
 pub fn entity_ty(ins: Vec<Type>, outs: Vec<Type>) -> Type {
     Type::new(EntityType(ins, outs))
 }
+
+/// A cache of types, such that equal types share their storage.
+///
+/// Cloning a `Type` is cheap, but constructing one allocates, such that every
+/// call to `int_ty(32)` creates a new copy of the same type. Interning types
+/// through a context returns the same allocation for all equal types, which
+/// saves memory when many values have the same type.
+#[derive(Debug, Default)]
+pub struct TypeContext {
+    types: HashSet<Type>,
+}
+
+impl TypeContext {
+    /// Create a new empty context.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Get the type in the context that is equal to `ty`.
+    ///
+    /// Adds `ty` to the context if no equal type has been interned before. The
+    /// inner types of `ty` are not interned; build types from the inside out to
+    /// share them as well.
+    pub fn intern(&mut self, ty: Type) -> Type {
+        if let Some(existing) = self.types.get(&ty) {
+            return existing.clone();
+        }
+        self.types.insert(ty.clone());
+        ty
+    }
+
+    /// Return the number of distinct types in the context.
+    pub fn len(&self) -> usize {
+        self.types.len()
+    }
+
+    /// Check if no types have been interned.
+    pub fn is_empty(&self) -> bool {
+        self.types.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bit_width() {
+        assert_eq!(int_ty(7).bit_width(), Some(7));
+        assert_eq!(enum_ty(1).bit_width(), Some(0));
+        assert_eq!(enum_ty(4).bit_width(), Some(2));
+        assert_eq!(enum_ty(5).bit_width(), Some(3));
+        assert_eq!(struct_ty(vec![int_ty(8), int_ty(4)]).bit_width(), Some(12));
+        assert_eq!(array_ty(4, int_ty(8)).bit_width(), Some(32));
+        assert_eq!(signal_ty(int_ty(3)).bit_width(), Some(3));
+        assert_eq!(void_ty().bit_width(), None);
+        assert_eq!(time_ty().bit_width(), None);
+        assert_eq!(pointer_ty(int_ty(8)).bit_width(), None);
+        assert_eq!(signal_ty(time_ty()).bit_width(), None);
+        assert_eq!(struct_ty(vec![int_ty(8), time_ty()]).bit_width(), None);
+    }
+
+    #[test]
+    fn interning() {
+        let mut cx = TypeContext::new();
+        let a = cx.intern(int_ty(32));
+        let b = cx.intern(int_ty(32));
+        assert!(Arc::ptr_eq(&a, &b));
+        let c = cx.intern(signal_ty(a.clone()));
+        let d = cx.intern(signal_ty(b));
+        assert!(Arc::ptr_eq(&c, &d));
+        assert!(!Arc::ptr_eq(&a, &cx.intern(int_ty(8))));
+        assert_eq!(cx.len(), 3);
+    }
+}
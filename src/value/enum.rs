@@ -0,0 +1,84 @@
+// Copyright (c) 2017-2020 Fabian Schuiki
+
+//! Enum values
+//!
+//! This module implements enumerated values.
+
+use crate::ty::{enum_ty, Type};
+use std::fmt::{Debug, Display};
+
+/// An enum value.
+#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct EnumValue {
+    /// The number of states of the enum.
+    pub states: usize,
+    /// The state itself.
+    pub value: usize,
+}
+
+impl EnumValue {
+    /// Create a new enum value.
+    ///
+    /// Panics if `value` is not a valid state, i.e. not less than `states`.
+    pub fn new(states: usize, value: usize) -> Self {
+        assert!(
+            value < states,
+            "enum value {} out of range for n{}",
+            value,
+            states
+        );
+        Self { states, value }
+    }
+
+    /// Create the zero value, which is the first state.
+    pub fn zero(states: usize) -> Self {
+        Self::new(states, 0)
+    }
+
+    /// Get the type of the value.
+    pub fn ty(&self) -> Type {
+        enum_ty(self.states)
+    }
+
+    /// Check if the value is zero.
+    pub fn is_zero(&self) -> bool {
+        self.value == 0
+    }
+}
+
+impl Display for EnumValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "n{} {}", self.states, self.value)
+    }
+}
+
+impl Debug for EnumValue {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self)
+    }
+}
+
+impl From<(usize, usize)> for EnumValue {
+    fn from(v: (usize, usize)) -> Self {
+        EnumValue::new(v.0, v.1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn enum_value() {
+        let v = EnumValue::new(4, 3);
+        assert_eq!(v.to_string(), "n4 3");
+        assert_eq!(v.ty(), enum_ty(4));
+        assert!(EnumValue::zero(4).is_zero());
+    }
+
+    #[test]
+    #[should_panic]
+    fn out_of_range() {
+        EnumValue::new(4, 4);
+    }
+}
@@ -149,6 +149,21 @@ impl IntValue {
     }
 }
 
+/// Width conversions.
+impl IntValue {
+    /// Zero-extend the value to a larger width.
+    pub fn zext_to(&self, width: usize) -> IntValue {
+        assert!(width >= self.width, "cannot zero-extend to a smaller width");
+        IntValue::from_unsigned(width, self.value.clone())
+    }
+
+    /// Sign-extend the value to a larger width.
+    pub fn sext_to(&self, width: usize) -> IntValue {
+        assert!(width >= self.width, "cannot sign-extend to a smaller width");
+        IntValue::from_signed(width, self.to_signed())
+    }
+}
+
 /// Unary operators.
 impl IntValue {
     /// Compute `not`.
@@ -193,6 +208,14 @@ impl IntValue {
         IntValue::from_unsigned(self.width, &self.value ^ &other.value)
     }
 
+    /// Compute `mul`.
+    ///
+    /// The result is truncated to the width of the value, which makes this
+    /// equivalent to both `umul` and `smul`.
+    pub fn mul(&self, other: &Self) -> IntValue {
+        self.umul(other)
+    }
+
     /// Compute `umul`.
     pub fn umul(&self, other: &Self) -> IntValue {
         IntValue::from_unsigned(self.width, &self.value * &other.value)
@@ -240,6 +263,39 @@ impl IntValue {
     }
 }
 
+/// Arithmetic with overflow detection.
+impl IntValue {
+    /// Compute `uaddo`, returning the wrapped sum and whether it overflowed.
+    pub fn uaddo(&self, other: &Self) -> (IntValue, bool) {
+        let sum = &self.value + &other.value;
+        let overflow = sum >= BigUint::one() << self.width;
+        (IntValue::from_unsigned(self.width, sum), overflow)
+    }
+
+    /// Compute `saddo`, returning the wrapped sum and whether it overflowed.
+    pub fn saddo(&self, other: &Self) -> (IntValue, bool) {
+        let sum = self.to_signed() + other.to_signed();
+        let result = IntValue::from_signed(self.width, sum.clone());
+        let overflow = result.to_signed() != sum;
+        (result, overflow)
+    }
+
+    /// Compute `usubo`, returning the wrapped difference and whether it
+    /// overflowed.
+    pub fn usubo(&self, other: &Self) -> (IntValue, bool) {
+        (self.sub(other), self.value < other.value)
+    }
+
+    /// Compute `ssubo`, returning the wrapped difference and whether it
+    /// overflowed.
+    pub fn ssubo(&self, other: &Self) -> (IntValue, bool) {
+        let diff = self.to_signed() - other.to_signed();
+        let result = IntValue::from_signed(self.width, diff.clone());
+        let overflow = result.to_signed() != diff;
+        (result, overflow)
+    }
+}
+
 /// Comparisons.
 impl IntValue {
     /// Compute `==`.
@@ -352,6 +408,24 @@ impl IntValue {
         }
     }
 
+    /// Execute an overflow-checked opcode.
+    ///
+    /// Returns the wrapped result and a 1-bit flag indicating overflow.
+    pub fn try_overflow_op(
+        op: Opcode,
+        lhs: &IntValue,
+        rhs: &IntValue,
+    ) -> Option<(IntValue, IntValue)> {
+        let (result, overflow) = match op {
+            Opcode::Uaddo => lhs.uaddo(rhs),
+            Opcode::Saddo => lhs.saddo(rhs),
+            Opcode::Usubo => lhs.usubo(rhs),
+            Opcode::Ssubo => lhs.ssubo(rhs),
+            _ => return None,
+        };
+        Some((result, IntValue::from_usize(1, overflow as usize)))
+    }
+
     /// Execute a comparison opcode.
     pub fn try_compare_op(op: Opcode, lhs: &IntValue, rhs: &IntValue) -> Option<IntValue> {
         // trace!("{} ({}, {})", op, lhs, rhs);
@@ -455,4 +529,40 @@ mod tests {
         assert_eq!(cn.srem(&b), IntValue::from_isize(8, 0));
         assert_eq!(cn.srem(&bn), IntValue::from_isize(8, 0));
     }
+
+    #[test]
+    fn wrapping() {
+        let a = IntValue::from_usize(8, 200);
+        let b = IntValue::from_usize(8, 100);
+        assert_eq!(a.add(&b), IntValue::from_usize(8, 44));
+        assert_eq!(b.sub(&a), IntValue::from_usize(8, 156));
+        assert_eq!(a.mul(&b), IntValue::from_usize(8, 32));
+        assert_eq!(a.mul(&b), a.umul(&b));
+        assert_eq!(a.mul(&b), a.smul(&b));
+    }
+
+    #[test]
+    fn extend() {
+        let a = IntValue::from_isize(8, -1);
+        assert_eq!(a.sext_to(16), IntValue::from_usize(16, 0xFFFF));
+        assert_eq!(a.zext_to(16), IntValue::from_usize(16, 0xFF));
+        let b = IntValue::from_usize(8, 0x7F);
+        assert_eq!(b.sext_to(16), IntValue::from_usize(16, 0x7F));
+        assert_eq!(b.zext_to(8), b);
+    }
+
+    #[test]
+    fn overflow() {
+        let a = IntValue::from_usize(8, 200);
+        let b = IntValue::from_usize(8, 100);
+        let c = IntValue::from_usize(8, 20);
+        assert_eq!(a.uaddo(&b), (IntValue::from_usize(8, 44), true));
+        assert_eq!(b.uaddo(&c), (IntValue::from_usize(8, 120), false));
+        assert_eq!(b.saddo(&b), (IntValue::from_isize(8, -56), true));
+        assert_eq!(b.saddo(&c), (IntValue::from_usize(8, 120), false));
+        assert_eq!(b.usubo(&a), (IntValue::from_usize(8, 156), true));
+        assert_eq!(a.usubo(&b), (IntValue::from_usize(8, 100), false));
+        assert_eq!(b.neg().ssubo(&b), (IntValue::from_usize(8, 56), true));
+        assert_eq!(c.ssubo(&b), (IntValue::from_isize(8, -80), false));
+    }
 }
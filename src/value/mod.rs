@@ -5,6 +5,7 @@
 //! This module implements representations for LLHD values.
 
 mod array;
+mod r#enum;
 mod int;
 mod r#struct;
 mod time;
@@ -12,18 +13,20 @@ mod time;
 pub use self::time::*;
 pub use array::*;
 pub use int::*;
+pub use r#enum::*;
 pub use r#struct::*;
 
 use crate::ty::Type;
 use std::fmt::{Debug, Display};
 
 /// A value.
-#[derive(Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
 #[allow(missing_docs)]
 pub enum Value {
     Void,
     Time(TimeValue),
     Int(IntValue),
+    Enum(EnumValue),
     Array(ArrayValue),
     Struct(StructValue),
 }
@@ -35,7 +38,7 @@ impl Value {
         match ty.as_ref() {
             VoidType => Value::Void,
             IntType(w) => IntValue::zero(*w).into(),
-            EnumType(_) => unimplemented!("zero value for {}", ty),
+            EnumType(n) => EnumValue::zero(*n).into(),
             ArrayType(l, ty) => ArrayValue::zero(*l, ty).into(),
             StructType(tys) => StructValue::zero(tys).into(),
             _ => panic!("no zero value for {}", ty),
@@ -68,6 +71,19 @@ impl Value {
         self.get_int().expect("value is not an integer")
     }
 
+    /// If this value is an enum, access it.
+    pub fn get_enum(&self) -> Option<&EnumValue> {
+        match self {
+            Value::Enum(v) => Some(v),
+            _ => None,
+        }
+    }
+
+    /// Unwrap this value as an enum, or panic.
+    pub fn unwrap_enum(&self) -> &EnumValue {
+        self.get_enum().expect("value is not an enum")
+    }
+
     /// If this value is an array, access it.
     pub fn get_array(&self) -> Option<&ArrayValue> {
         match self {
@@ -101,6 +117,7 @@ impl Value {
             Value::Void => void_ty(),
             Value::Time(v) => v.ty(),
             Value::Int(v) => v.ty(),
+            Value::Enum(v) => v.ty(),
             Value::Array(v) => v.ty(),
             Value::Struct(v) => v.ty(),
         }
@@ -111,6 +128,7 @@ impl Value {
         match self {
             Value::Int(v) => v.is_zero(),
             Value::Time(v) => v.is_zero(),
+            Value::Enum(v) => v.is_zero(),
             Value::Void | Value::Array(..) | Value::Struct(..) => false,
         }
     }
@@ -119,7 +137,11 @@ impl Value {
     pub fn is_one(&self) -> bool {
         match self {
             Value::Int(v) => v.is_one(),
-            Value::Void | Value::Time(_) | Value::Array(..) | Value::Struct(..) => false,
+            Value::Void
+            | Value::Time(_)
+            | Value::Enum(_)
+            | Value::Array(..)
+            | Value::Struct(..) => false,
         }
     }
 }
@@ -136,6 +158,12 @@ impl From<IntValue> for Value {
     }
 }
 
+impl From<EnumValue> for Value {
+    fn from(v: EnumValue) -> Value {
+        Value::Enum(v)
+    }
+}
+
 impl From<ArrayValue> for Value {
     fn from(v: ArrayValue) -> Value {
         Value::Array(v)
@@ -154,6 +182,7 @@ impl Display for Value {
             Value::Void => write!(f, "void"),
             Value::Time(v) => write!(f, "time {}", v),
             Value::Int(v) => Display::fmt(v, f),
+            Value::Enum(v) => Display::fmt(v, f),
             Value::Array(v) => Display::fmt(v, f),
             Value::Struct(v) => Display::fmt(v, f),
         }
@@ -6,7 +6,10 @@
 
 use crate::ty::{time_ty, Type};
 use num::{traits::*, BigInt, BigRational};
-use std::fmt::{Debug, Display};
+use std::{
+    fmt::{Debug, Display},
+    ops::{Add, Sub},
+};
 
 /// A constant time value.
 #[derive(Clone, PartialOrd, Ord, PartialEq, Eq, Hash, Serialize, Deserialize)]
@@ -38,6 +41,15 @@ impl TimeValue {
         }
     }
 
+    /// Create a time that advances by one delta step.
+    pub fn delta_step() -> Self {
+        TimeValue {
+            time: BigRational::zero(),
+            delta: 1,
+            epsilon: 0,
+        }
+    }
+
     /// Get the type of the value.
     pub fn ty(&self) -> Type {
         time_ty()
@@ -62,6 +74,184 @@ impl TimeValue {
     pub fn is_zero(&self) -> bool {
         self.time.is_zero() && self.delta.is_zero() && self.epsilon.is_zero()
     }
+
+    /// Subtract another time component by component.
+    ///
+    /// Returns `None` if any component of `rhs` exceeds that of `self`, since
+    /// times cannot be negative.
+    pub fn checked_sub(&self, rhs: &TimeValue) -> Option<TimeValue> {
+        if rhs.time > self.time {
+            return None;
+        }
+        Some(TimeValue::new(
+            &self.time - &rhs.time,
+            self.delta.checked_sub(rhs.delta)?,
+            self.epsilon.checked_sub(rhs.epsilon)?,
+        ))
+    }
+
+    /// Compute the time at which an event occurs after a delay.
+    ///
+    /// A delay with a non-zero physical time resets the delta and epsilon steps
+    /// to those of the delay, and a delay with a non-zero delta step resets the
+    /// epsilon steps. This mirrors how time advances during simulation.
+    pub fn advance(&self, delay: &TimeValue) -> TimeValue {
+        if !delay.time.is_zero() {
+            TimeValue::new(&self.time + &delay.time, delay.delta, delay.epsilon)
+        } else if delay.delta != 0 {
+            TimeValue::new(self.time.clone(), self.delta + delay.delta, delay.epsilon)
+        } else {
+            TimeValue::new(self.time.clone(), self.delta, self.epsilon + delay.epsilon)
+        }
+    }
+
+    /// Format the time with a fixed SI prefix.
+    ///
+    /// The physical time is printed as an integer multiple of the unit, for
+    /// example `1000ps` for one nanosecond. If the time is not a multiple of
+    /// the unit, the next finer prefix that represents it exactly is used
+    /// instead. Times that are not a multiple of an attosecond are formatted
+    /// as by `Display`.
+    pub fn to_string_in(&self, prefix: SiPrefix) -> String {
+        let mut prefix = Some(prefix);
+        while let Some(p) = prefix {
+            let scaled = p.scale(&self.time);
+            if scaled.is_integer() {
+                let mut s = format!("{}{}s", scaled, p.symbol());
+                if !self.delta.is_zero() {
+                    s += &format!(" {}d", self.delta);
+                }
+                if !self.epsilon.is_zero() {
+                    s += &format!(" {}e", self.epsilon);
+                }
+                return s;
+            }
+            prefix = p.finer();
+        }
+        self.to_string()
+    }
+}
+
+/// An SI prefix of the unit of a time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[allow(missing_docs)]
+pub enum SiPrefix {
+    Exa,
+    Peta,
+    Tera,
+    Giga,
+    Mega,
+    Kilo,
+    /// No prefix, i.e. seconds.
+    Base,
+    Milli,
+    Micro,
+    Nano,
+    Pico,
+    Femto,
+    Atto,
+}
+
+impl SiPrefix {
+    /// Get the power of ten the prefix stands for.
+    pub fn exponent(self) -> i32 {
+        match self {
+            SiPrefix::Exa => 18,
+            SiPrefix::Peta => 15,
+            SiPrefix::Tera => 12,
+            SiPrefix::Giga => 9,
+            SiPrefix::Mega => 6,
+            SiPrefix::Kilo => 3,
+            SiPrefix::Base => 0,
+            SiPrefix::Milli => -3,
+            SiPrefix::Micro => -6,
+            SiPrefix::Nano => -9,
+            SiPrefix::Pico => -12,
+            SiPrefix::Femto => -15,
+            SiPrefix::Atto => -18,
+        }
+    }
+
+    /// Get the symbol of the prefix, such as `n` for nano.
+    pub fn symbol(self) -> &'static str {
+        match self {
+            SiPrefix::Exa => "E",
+            SiPrefix::Peta => "P",
+            SiPrefix::Tera => "T",
+            SiPrefix::Giga => "G",
+            SiPrefix::Mega => "M",
+            SiPrefix::Kilo => "k",
+            SiPrefix::Base => "",
+            SiPrefix::Milli => "m",
+            SiPrefix::Micro => "u",
+            SiPrefix::Nano => "n",
+            SiPrefix::Pico => "p",
+            SiPrefix::Femto => "f",
+            SiPrefix::Atto => "a",
+        }
+    }
+
+    /// Express a time in seconds in units of this prefix.
+    fn scale(self, time: &BigRational) -> BigRational {
+        let exp = BigRational::from_integer(num::pow(
+            BigInt::from(10),
+            self.exponent().unsigned_abs() as usize,
+        ));
+        if self.exponent() < 0 {
+            time * exp
+        } else {
+            time / exp
+        }
+    }
+
+    /// Get the next smaller prefix, if any.
+    fn finer(self) -> Option<SiPrefix> {
+        match self {
+            SiPrefix::Exa => Some(SiPrefix::Peta),
+            SiPrefix::Peta => Some(SiPrefix::Tera),
+            SiPrefix::Tera => Some(SiPrefix::Giga),
+            SiPrefix::Giga => Some(SiPrefix::Mega),
+            SiPrefix::Mega => Some(SiPrefix::Kilo),
+            SiPrefix::Kilo => Some(SiPrefix::Base),
+            SiPrefix::Base => Some(SiPrefix::Milli),
+            SiPrefix::Milli => Some(SiPrefix::Micro),
+            SiPrefix::Micro => Some(SiPrefix::Nano),
+            SiPrefix::Nano => Some(SiPrefix::Pico),
+            SiPrefix::Pico => Some(SiPrefix::Femto),
+            SiPrefix::Femto => Some(SiPrefix::Atto),
+            SiPrefix::Atto => None,
+        }
+    }
+}
+
+/// Add two times component by component.
+///
+/// The physical times, delta steps, and epsilon steps are added separately.
+/// Use `TimeValue::advance` to schedule an event after a delay.
+impl Add for TimeValue {
+    type Output = TimeValue;
+
+    fn add(self, rhs: TimeValue) -> TimeValue {
+        TimeValue::new(
+            self.time + rhs.time,
+            self.delta + rhs.delta,
+            self.epsilon + rhs.epsilon,
+        )
+    }
+}
+
+/// Subtract two times component by component.
+///
+/// This is the inverse of addition, such that `(a + b) - a == b`. Panics if
+/// any component of `rhs` exceeds that of `self`; use `checked_sub` to handle
+/// this case.
+impl Sub for TimeValue {
+    type Output = TimeValue;
+
+    fn sub(self, rhs: TimeValue) -> TimeValue {
+        self.checked_sub(&rhs)
+            .expect("time subtraction would yield a negative time")
+    }
 }
 
 impl Display for TimeValue {
@@ -83,10 +273,32 @@ impl Debug for TimeValue {
     }
 }
 
+/// Write a time in seconds with an SI prefix.
+///
+/// Among the prefixes that leave a nonzero integer part, the one that yields
+/// the shortest exact decimal is chosen, such as `1.5ns` rather than `1500ps`.
+/// Times that have no finite decimal expansion are rounded.
 fn write_ratio_as_si(ratio: &BigRational, f: &mut std::fmt::Formatter) -> std::fmt::Result {
     if ratio.is_zero() {
         return write!(f, "0s");
     }
+    let mut shortest: Option<String> = None;
+    let mut prefix = Some(SiPrefix::Exa);
+    while let Some(p) = prefix {
+        let scaled = p.scale(ratio);
+        if scaled.abs() >= One::one() {
+            if let Some(digits) = exact_decimal(&scaled) {
+                let s = format!("{}{}s", digits, p.symbol());
+                if shortest.as_ref().map(|x| s.len() < x.len()).unwrap_or(true) {
+                    shortest = Some(s);
+                }
+            }
+        }
+        prefix = p.finer();
+    }
+    if let Some(s) = shortest {
+        return write!(f, "{}", s);
+    }
     let prefices = ["", "m", "u", "n", "p", "f", "a"];
     let mut scaled = ratio.clone();
     let mut prefix = 0;
@@ -118,10 +330,58 @@ fn write_ratio_as_si(ratio: &BigRational, f: &mut std::fmt::Formatter) -> std::f
     Ok(())
 }
 
+/// Format a rational number as a decimal, if its decimal expansion is finite.
+fn exact_decimal(ratio: &BigRational) -> Option<String> {
+    // The expansion is finite if the denominator has no prime factors other
+    // than two and five.
+    let two = BigInt::from(2);
+    let five = BigInt::from(5);
+    let mut rest = ratio.denom().clone();
+    while (&rest % &two).is_zero() {
+        rest /= &two;
+    }
+    while (&rest % &five).is_zero() {
+        rest /= &five;
+    }
+    if !rest.is_one() {
+        return None;
+    }
+    let mut scaled = ratio.abs();
+    let mut shift = 0;
+    while !scaled.is_integer() {
+        scaled *= BigRational::from_integer(BigInt::from(10));
+        shift += 1;
+    }
+    let digits = scaled.to_integer().to_string();
+    let sign = if ratio.is_negative() { "-" } else { "" };
+    if shift == 0 {
+        Some(format!("{}{}", sign, digits))
+    } else {
+        let (int, frac) = digits.split_at(digits.len() - shift);
+        Some(format!("{}{}.{}", sign, int, frac))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn time_formatting_with_prefix() {
+        let make = |num: usize, denom: usize, prefix| {
+            TimeValue::new(BigRational::new(num.into(), denom.into()), 0, 0).to_string_in(prefix)
+        };
+        assert_eq!(make(1, 1_000_000_000, SiPrefix::Pico), "1000ps");
+        assert_eq!(make(1, 1_000_000_000, SiPrefix::Nano), "1ns");
+        assert_eq!(make(1, 1_000_000_000, SiPrefix::Micro), "1ns");
+        assert_eq!(make(3, 2_000_000_000, SiPrefix::Nano), "1500ps");
+        assert_eq!(make(5000, 1, SiPrefix::Kilo), "5ks");
+        assert_eq!(make(0, 1, SiPrefix::Pico), "0ps");
+        assert_eq!(make(1, 3, SiPrefix::Nano), make(1, 3, SiPrefix::Base));
+        let t = TimeValue::new(BigRational::new(2.into(), 1_000_000_000.into()), 1, 2);
+        assert_eq!(t.to_string_in(SiPrefix::Pico), "2000ps 1d 2e");
+    }
+
     #[test]
     fn time_formatting() {
         let make = |num: usize, denom: usize, delta: usize, epsilon: usize| {
@@ -172,4 +432,87 @@ mod tests {
 
         assert_eq!(make(1, 3, 0, 0), "333.333333333ms");
     }
+
+    fn ns(num: usize) -> TimeValue {
+        TimeValue::new(BigRational::new(num.into(), 1000000000.into()), 0, 0)
+    }
+
+    #[test]
+    fn time_arithmetic() {
+        assert_eq!(ns(1) + ns(2), ns(3));
+        assert_eq!(ns(3) - ns(1), ns(2));
+        assert_eq!(
+            TimeValue::delta_step() + TimeValue::delta_step(),
+            TimeValue::new(Zero::zero(), 2, 0)
+        );
+        assert_eq!(
+            TimeValue::new(Zero::zero(), 1, 3) + ns(1),
+            TimeValue::new(ns(1).time, 1, 3)
+        );
+        let a = TimeValue::new(ns(1).time, 2, 3);
+        let b = TimeValue::new(Zero::zero(), 1, 1);
+        assert_eq!(a.clone() + b.clone() - a.clone(), b);
+        let c = TimeValue::new(ns(2).time, 0, 5);
+        assert_eq!(a.clone() + c.clone() - c, a);
+    }
+
+    #[test]
+    fn time_subtraction_underflow() {
+        let make = |num, delta, epsilon| TimeValue::new(ns(num).time, delta, epsilon);
+        assert_eq!(ns(1).checked_sub(&ns(2)), None);
+        assert_eq!(make(0, 1, 0).checked_sub(&make(0, 2, 0)), None);
+        assert_eq!(make(0, 1, 1).checked_sub(&make(0, 1, 2)), None);
+        assert_eq!(make(2, 0, 0).checked_sub(&make(1, 1, 0)), None);
+        assert_eq!(
+            make(2, 3, 1).checked_sub(&make(1, 1, 1)),
+            Some(make(1, 2, 0))
+        );
+    }
+
+    #[test]
+    #[should_panic]
+    fn time_subtraction_panics_on_underflow() {
+        let _ = ns(1) - ns(2);
+    }
+
+    #[test]
+    fn time_advance() {
+        let make = |num, delta, epsilon| TimeValue::new(ns(num).time, delta, epsilon);
+        assert_eq!(make(1, 3, 2).advance(&make(2, 0, 0)), make(3, 0, 0));
+        assert_eq!(make(1, 3, 2).advance(&make(2, 1, 0)), make(3, 1, 0));
+        assert_eq!(make(1, 3, 2).advance(&make(0, 1, 0)), make(1, 4, 0));
+        assert_eq!(make(1, 3, 2).advance(&make(0, 0, 1)), make(1, 3, 3));
+    }
+
+    #[test]
+    fn time_sum_formatting() {
+        let secs = |num: usize, denom: usize| {
+            TimeValue::new(BigRational::new(num.into(), denom.into()), 0, 0)
+        };
+        let sum = secs(3, 2_000_000_000) + secs(1, 2_000_000_000);
+        assert_eq!(sum, ns(2));
+        assert_eq!(sum.time().denom(), &BigInt::from(500_000_000));
+        assert_eq!(sum.to_string(), "2ns");
+        assert_eq!(secs(3, 2_000_000_000).to_string(), "1.5ns");
+        assert_eq!(ns(1250).to_string(), "1.25us");
+        assert_eq!(secs(5000, 1).to_string(), "5ks");
+
+        let fs = |num: usize| secs(num, 1_000_000_000_000_000);
+        for sum in [fs(1000) + fs(999), ns(1) + fs(1), ns(1) + fs(500_000)].iter() {
+            assert_eq!(&crate::assembly::parse_time(sum.to_string()).unwrap(), sum);
+        }
+        assert_eq!((fs(1000) + fs(999)).to_string(), "1999fs");
+        assert_eq!((ns(1) + fs(1)).to_string(), "1000001fs");
+        assert_eq!((ns(1) + fs(500_000)).to_string(), "1.5ns");
+    }
+
+    #[test]
+    fn time_ordering() {
+        let make = |delta, epsilon| TimeValue::new(Zero::zero(), delta, epsilon);
+        assert!(make(1, 0) < make(2, 0));
+        assert!(make(1, 5) < make(2, 0));
+        assert!(make(1, 1) < make(1, 2));
+        assert!(make(5, 5) < ns(1));
+        assert!(TimeValue::zero() < TimeValue::delta_step());
+    }
 }
@@ -8,10 +8,13 @@
 
 use crate::{
     ir::{prelude::*, InstData, UnitFlags, ValueData},
-    ty::{array_ty, int_ty, pointer_ty, signal_ty, time_ty, void_ty, Type},
+    ty::{array_ty, int_ty, pointer_ty, signal_ty, struct_ty, time_ty, void_ty, Type},
 };
 use std::{
+    borrow::Borrow,
+    collections::{HashMap, HashSet},
     fmt::Display,
+    hash::Hash,
     ops::{Deref, DerefMut},
 };
 
@@ -21,10 +24,14 @@ use std::{
 /// on. It keeps track of errors.
 #[derive(Default)]
 pub struct Verifier {
-    errors: VerifierErrors,
+    errors: Errors,
     unit_name: Option<String>,
     flags: UnitFlags,
     return_type: Option<Type>,
+    types_only: bool,
+    /// The signatures of the units defined or declared in the module being
+    /// verified, against which calls and instances are checked.
+    symbols: HashMap<UnitName, Signature>,
 }
 
 impl Verifier {
@@ -33,11 +40,28 @@ impl Verifier {
         Default::default()
     }
 
+    /// Create a new verifier that only checks the types of instructions.
+    ///
+    /// Structural properties such as terminators, dominance, and whether an
+    /// instruction may appear in a unit are not checked.
+    pub fn types_only() -> Self {
+        Self {
+            types_only: true,
+            ..Default::default()
+        }
+    }
+
     /// Verify the integrity of a `Module`.
     pub fn verify_module(&mut self, module: &Module) {
+        for (name, _, sig) in module.symbols() {
+            self.symbols
+                .entry(name.clone())
+                .or_insert_with(|| sig.clone());
+        }
         for unit in module.units() {
             self.verify_unit(unit);
         }
+        self.symbols.clear();
     }
 
     /// Verify the integrity of a `UnitData`.
@@ -56,20 +80,29 @@ impl Verifier {
             }
         }
 
-        let domtree = unit.domtree();
+        if self.types_only {
+            for inst in unit.all_insts() {
+                self.verify_inst(inst, unit);
+            }
+            self.unit_name = None;
+            self.return_type = None;
+            return;
+        }
+
+        let unit_name = self.unit_name.clone().unwrap_or_default();
         if unit.first_block().is_none() {
-            self.errors.push(VerifierError {
-                unit: self.unit_name.clone(),
-                object: None,
-                message: format!("layout has no entry block"),
-            });
+            self.errors.push(VerifyError::NoEntry { unit: unit_name });
+            self.unit_name = None;
+            self.return_type = None;
+            return;
         }
+        let domtree = unit.domtree();
         for bb in unit.blocks() {
             // Check that the block has at least one instruction.
             if unit.first_inst(bb).is_none() {
-                self.errors.push(VerifierError {
-                    unit: self.unit_name.clone(),
-                    object: Some(bb.to_string()),
+                self.errors.push(VerifyError::Terminator {
+                    unit: unit_name.clone(),
+                    object: bb.dump(&unit).to_string(),
                     message: format!("block is empty"),
                 })
             }
@@ -78,18 +111,18 @@ impl Verifier {
                 // Check that there are no terminator instructions in the middle
                 // of the block.
                 if unit[inst].opcode().is_terminator() && Some(inst) != unit.last_inst(bb) {
-                    self.errors.push(VerifierError {
-                        unit: self.unit_name.clone(),
-                        object: Some(inst.dump(&unit).to_string()),
+                    self.errors.push(VerifyError::Terminator {
+                        unit: unit_name.clone(),
+                        object: inst.dump(&unit).to_string(),
                         message: format!("terminator must be at the end of block {}", bb),
                     });
                 }
 
                 // Check that the last instruction in the block is a terminator.
                 if Some(inst) == unit.last_inst(bb) && !unit[inst].opcode().is_terminator() {
-                    self.errors.push(VerifierError {
-                        unit: self.unit_name.clone(),
-                        object: Some(bb.to_string()),
+                    self.errors.push(VerifyError::Terminator {
+                        unit: unit_name.clone(),
+                        object: bb.dump(&unit).to_string(),
                         message: format!(
                             "last instruction `{}` must be a terminator",
                             inst.dump(&unit)
@@ -104,10 +137,10 @@ impl Verifier {
                 if let Some(result) = unit.get_inst_result(inst) {
                     for &u in unit.uses(result) {
                         if !domtree.inst_dominates_inst(&unit, inst, u) {
-                            self.errors.push(VerifierError {
-                                unit: self.unit_name.clone(),
-                                object: Some(inst.dump(&unit).to_string()),
-                                message: format!("does not dominate use in `{}`", u.dump(&unit),),
+                            self.errors.push(VerifyError::UseBeforeDef {
+                                unit: unit_name.clone(),
+                                def: inst.dump(&unit).to_string(),
+                                inst: u.dump(&unit).to_string(),
                             });
                         }
                     }
@@ -123,10 +156,19 @@ impl Verifier {
     ///
     /// Consumes the verifier.
     pub fn finish(self) -> Result<(), VerifierErrors> {
-        if self.errors.is_empty() {
+        self.finish_typed()
+            .map_err(|errs| VerifierErrors(errs.into_iter().map(Into::into).collect()))
+    }
+
+    /// Finish verification and return the errors, told apart by the check
+    /// that failed.
+    ///
+    /// Consumes the verifier.
+    pub fn finish_typed(self) -> Result<(), Vec<VerifyError>> {
+        if self.errors.0.is_empty() {
             Ok(())
         } else {
-            Err(self.errors)
+            Err(self.errors.0)
         }
     }
 
@@ -150,6 +192,70 @@ impl Verifier {
     }
 }
 
+impl Unit<'_> {
+    /// Check that all instructions may appear in this kind of unit.
+    ///
+    /// Functions, for example, may not contain `wait`, `sig`, `prb`, or `drv`
+    /// instructions. Unlike the `Verifier`, this only looks at the opcodes and
+    /// can be used on units that are not yet well-formed otherwise.
+    pub fn validate(self) -> Result<(), VerifierErrors> {
+        let flags = match self.kind() {
+            UnitKind::Function => UnitFlags::FUNCTION,
+            UnitKind::Process => UnitFlags::PROCESS,
+            UnitKind::Entity => UnitFlags::ENTITY,
+        };
+        let errors: Vec<_> = self
+            .all_insts()
+            .filter(|&inst| !self[inst].opcode().valid_in().contains(flags))
+            .map(|inst| VerifierError {
+                unit: Some(format!("{} {}", self.kind(), self.name())),
+                object: Some(inst.dump(&self).to_string()),
+                message: format!("{} may not appear in this unit", self[inst].opcode()),
+            })
+            .collect();
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(VerifierErrors(errors))
+        }
+    }
+
+    /// Check that every block can reach a `wait`, `halt`, or `ret`.
+    ///
+    /// A process in which some path loops forever without waiting or halting
+    /// never lets simulation time advance. Returns the blocks from which no
+    /// such instruction can be reached, in layout order.
+    pub fn check_termination(self) -> Result<(), Vec<Block>> {
+        let predtbl = self.predtbl();
+        let mut done = HashSet::new();
+        let mut todo: Vec<Block> = self
+            .blocks()
+            .filter(|&bb| {
+                self.last_inst(bb)
+                    .map(|term| {
+                        let opcode = self[term].opcode();
+                        opcode.is_temporal() || opcode.is_return()
+                    })
+                    .unwrap_or(false)
+            })
+            .collect();
+        done.extend(todo.iter().cloned());
+        while let Some(bb) = todo.pop() {
+            for pred in predtbl.pred(bb) {
+                if done.insert(pred) {
+                    todo.push(pred);
+                }
+            }
+        }
+        let stuck: Vec<_> = self.blocks().filter(|bb| !done.contains(bb)).collect();
+        if stuck.is_empty() {
+            Ok(())
+        } else {
+            Err(stuck)
+        }
+    }
+}
+
 /// An instruction verifier.
 struct InstVerifier<'a> {
     verifier: &'a mut Verifier,
@@ -193,11 +299,11 @@ impl<'a> InstVerifier<'a> {
         let unit = self.unit;
 
         // Check that the instruction may appear in the surrounding unit.
-        if !unit[inst].opcode().valid_in().contains(self.flags) {
-            self.verifier.errors.push(VerifierError {
-                unit: self.verifier.unit_name.clone(),
-                object: Some(inst.dump(&unit).to_string()),
-                message: format!("{} may not appear in this unit", unit[inst].opcode()),
+        if !self.types_only && !unit[inst].opcode().valid_in().contains(self.flags) {
+            self.verifier.errors.push(VerifyError::NotAllowed {
+                unit: self.verifier.unit_name.clone().unwrap_or_default(),
+                inst: inst.dump(&unit).to_string(),
+                opcode: unit[inst].opcode(),
             });
         }
 
@@ -219,11 +325,11 @@ impl<'a> InstVerifier<'a> {
                 });
                 continue;
             }
-            if !self.is_value_defined(value) {
-                self.verifier.errors.push(VerifierError {
-                    unit: self.verifier.unit_name.clone(),
-                    object: Some(inst.dump(&unit).to_string()),
-                    message: format!("value {} has no definition", value.dump(&unit)),
+            if !self.types_only && !self.is_value_defined(value) {
+                self.verifier.errors.push(VerifyError::Undefined {
+                    unit: self.verifier.unit_name.clone().unwrap_or_default(),
+                    inst: inst.dump(&unit).to_string(),
+                    object: format!("value {}", value.dump(&unit)),
                 });
             }
         }
@@ -237,11 +343,11 @@ impl<'a> InstVerifier<'a> {
                 });
                 continue;
             }
-            if !self.is_block_defined(block) {
-                self.verifier.errors.push(VerifierError {
-                    unit: self.verifier.unit_name.clone(),
-                    object: Some(inst.dump(&unit).to_string()),
-                    message: format!("block {} has no definition", block.dump(&unit)),
+            if !self.types_only && !self.is_block_defined(block) {
+                self.verifier.errors.push(VerifyError::Undefined {
+                    unit: self.verifier.unit_name.clone().unwrap_or_default(),
+                    inst: inst.dump(&unit).to_string(),
+                    object: format!("block {}", block.dump(&unit)),
                 });
             }
         }
@@ -254,6 +360,9 @@ impl<'a> InstVerifier<'a> {
         match unit[inst].opcode() {
             Opcode::ConstInt => {}
             Opcode::ConstTime => {}
+            Opcode::ConstEnum => {}
+            Opcode::ConstUndef => {}
+            Opcode::ConstPoison => {}
             Opcode::Alias => {}
             Opcode::ArrayUniform => {}
             Opcode::Array => {
@@ -288,6 +397,11 @@ impl<'a> InstVerifier<'a> {
                 self.verify_arith_compatible_ty(inst);
                 self.verify_args_match_inst_ty(inst);
             }
+            Opcode::Uaddo | Opcode::Saddo | Opcode::Usubo | Opcode::Ssubo => {
+                self.assert_inst_binary(inst);
+                self.verify_arg_tys_match(inst);
+                self.verify_overflow_inst(inst);
+            }
             Opcode::And | Opcode::Or | Opcode::Xor => {
                 self.assert_inst_binary(inst);
                 self.verify_bitwise_compatible_ty(inst);
@@ -315,6 +429,10 @@ impl<'a> InstVerifier<'a> {
                 self.assert_inst_binary(inst);
                 self.verify_mux_inst(inst);
             }
+            Opcode::Sel => {
+                self.assert_inst_ternary(inst);
+                self.verify_sel_inst(inst);
+            }
             Opcode::Reg => {
                 self.assert_inst_reg(inst);
                 self.verify_reg_inst(inst);
@@ -350,11 +468,11 @@ impl<'a> InstVerifier<'a> {
                 );
                 self.verify_arg_matches_ty(inst, unit[inst].args()[2], &time_ty());
             }
-            Opcode::Call => {
-                // TODO: properly check argument types match the declaration
+            Opcode::Call | Opcode::Inst => {
+                self.verify_call_inst(inst);
             }
-            Opcode::Inst => {
-                // TODO: properly check argument types match the declaration
+            Opcode::CallIndirect => {
+                self.verify_call_indirect_inst(inst);
             }
             Opcode::Sig => {
                 self.assert_inst_unary(inst);
@@ -581,7 +699,11 @@ impl<'a> InstVerifier<'a> {
             self.verifier.errors.push(VerifierError {
                 unit: self.verifier.unit_name.clone(),
                 object: Some(inst.dump(&self.unit).to_string()),
-                message: format!("argument {} type must be a signal (but is {})", arg, ty),
+                message: format!(
+                    "argument {} type must be a signal (but is {})",
+                    arg.dump(&self.unit),
+                    ty
+                ),
             });
         }
     }
@@ -609,7 +731,9 @@ impl<'a> InstVerifier<'a> {
                 object: Some(inst.dump(&self.unit).to_string()),
                 message: format!(
                     "argument {} must be of type {} (but is {})",
-                    arg, ty, arg_ty,
+                    arg.dump(&self.unit),
+                    ty,
+                    arg_ty,
                 ),
             });
         }
@@ -738,15 +862,66 @@ impl<'a> InstVerifier<'a> {
         }
         let sel = self.unit()[inst].args()[1];
         let sel_ty = self.unit.value_type(sel);
-        if !sel_ty.is_int() && !(sel_ty.is_signal() && sel_ty.unwrap_signal().is_int()) {
+        let sel_ty = if sel_ty.is_signal() {
+            sel_ty.unwrap_signal().clone()
+        } else {
+            sel_ty
+        };
+        if !sel_ty.is_int() && !sel_ty.is_enum() {
             self.verifier.errors.push(VerifierError {
                 unit: self.verifier.unit_name.clone(),
                 object: Some(inst.dump(&self.unit).to_string()),
-                message: format!("type of selector must be iN or iN$ (but is {})", sel_ty),
+                message: format!(
+                    "type of selector must be iN, nN, iN$, or nN$ (but is {})",
+                    self.unit.value_type(sel)
+                ),
             });
         }
     }
 
+    /// Verify that the types of an overflow-checked instruction line up.
+    fn verify_overflow_inst(&mut self, inst: Inst) {
+        let arg_ty = self.unit.value_type(self.unit()[inst].args()[0]);
+        if arg_ty.is_int() {
+            self.verify_inst_ty(inst, &struct_ty(vec![arg_ty, int_ty(1)]));
+        } else {
+            self.verifier.errors.push(VerifierError {
+                unit: self.verifier.unit_name.clone(),
+                object: Some(inst.dump(&self.unit).to_string()),
+                message: format!("argument type must be iN (but is {})", arg_ty),
+            });
+        }
+    }
+
+    /// Verify that the types of a sel instruction line up.
+    fn verify_sel_inst(&mut self, inst: Inst) {
+        let args = self.unit()[inst].args().to_vec();
+        let cond_ty = self.unit.value_type(args[0]);
+        if cond_ty != int_ty(1) {
+            self.verifier.errors.push(VerifierError {
+                unit: self.verifier.unit_name.clone(),
+                object: Some(inst.dump(&self.unit).to_string()),
+                message: format!("type of condition must be i1 (but is {})", cond_ty),
+            });
+        }
+        let ty = self.unit.inst_type(inst);
+        for &arg in &args[1..] {
+            let arg_ty = self.unit.value_type(arg);
+            if arg_ty != ty {
+                self.verifier.errors.push(VerifierError {
+                    unit: self.verifier.unit_name.clone(),
+                    object: Some(inst.dump(&self.unit).to_string()),
+                    message: format!(
+                        "operand {} must be of type {} (but is {})",
+                        arg.dump(&self.unit),
+                        ty,
+                        arg_ty
+                    ),
+                });
+            }
+        }
+    }
+
     /// Verify that the types of a reg instruction line up.
     fn verify_reg_inst(&mut self, inst: Inst) {
         self.verify_arg_ty_is_signal(inst, self.unit()[inst].args()[0]);
@@ -909,6 +1084,88 @@ impl<'a> InstVerifier<'a> {
         self.verify_inst_ty(inst, &arg_ty);
     }
 
+    /// Verify that the argument types of a call or inst instruction match the
+    /// signature of the called unit.
+    ///
+    /// The called unit is looked up by name in the module being verified. If it
+    /// is not found there, such as when verifying a single unit, the external
+    /// unit declared in the calling unit is used instead.
+    fn verify_call_inst(&mut self, inst: Inst) {
+        let unit = self.unit;
+        let data = &unit[inst];
+        let ext = data.get_ext_unit().unwrap();
+        let sig = match self.verifier.symbols.get(unit.extern_name(ext)) {
+            Some(sig) => sig.clone(),
+            None => unit.extern_sig(ext).clone(),
+        };
+        let inputs: Vec<Type> = sig.inputs().map(|arg| sig.arg_type(arg)).collect();
+        let outputs: Vec<Type> = sig.outputs().map(|arg| sig.arg_type(arg)).collect();
+        if data.input_args().len() != inputs.len() || data.output_args().len() != outputs.len() {
+            self.verifier.errors.push(VerifierError {
+                unit: self.verifier.unit_name.clone(),
+                object: Some(inst.dump(&unit).to_string()),
+                message: format!(
+                    "signature {} requires {} inputs and {} outputs (but has {} and {})",
+                    sig,
+                    inputs.len(),
+                    outputs.len(),
+                    data.input_args().len(),
+                    data.output_args().len()
+                ),
+            });
+            return;
+        }
+        let args = data.input_args().iter().zip(&inputs);
+        let args = args.chain(data.output_args().iter().zip(&outputs));
+        for (&arg, ty) in args {
+            self.verify_arg_matches_ty(inst, arg, ty);
+        }
+        if data.opcode() == Opcode::Call {
+            self.verify_inst_ty(inst, &sig.return_type());
+        }
+    }
+
+    /// Verify that the argument types of an indirect call match the type of
+    /// the called value.
+    fn verify_call_indirect_inst(&mut self, inst: Inst) {
+        let unit = self.unit;
+        let (&callee, args) = match unit[inst].args().split_first() {
+            Some(x) => x,
+            None => return,
+        };
+        let callee_ty = unit.value_type(callee);
+        if !callee_ty.is_func() {
+            self.verifier.errors.push(VerifierError {
+                unit: self.verifier.unit_name.clone(),
+                object: Some(inst.dump(&unit).to_string()),
+                message: format!(
+                    "callee {} must be of function type (but is {})",
+                    callee.dump(&unit),
+                    callee_ty
+                ),
+            });
+            return;
+        }
+        let (inputs, retty) = callee_ty.unwrap_func();
+        if args.len() != inputs.len() {
+            self.verifier.errors.push(VerifierError {
+                unit: self.verifier.unit_name.clone(),
+                object: Some(inst.dump(&unit).to_string()),
+                message: format!(
+                    "function type {} requires {} inputs (but has {})",
+                    callee_ty,
+                    inputs.len(),
+                    args.len()
+                ),
+            });
+            return;
+        }
+        for (&arg, ty) in args.iter().zip(inputs) {
+            self.verify_arg_matches_ty(inst, arg, ty);
+        }
+        self.verify_inst_ty(inst, retty);
+    }
+
     /// Verify that the types of a sig instruction line up.
     fn verify_sig_inst(&mut self, inst: Inst) {
         let ty = self.unit.inst_type(inst);
@@ -1070,6 +1327,16 @@ impl Display for VerifierError {
 #[derive(Debug, Default)]
 pub struct VerifierErrors(pub Vec<VerifierError>);
 
+/// The errors collected by a `Verifier`.
+#[derive(Default)]
+struct Errors(Vec<VerifyError>);
+
+impl Errors {
+    fn push(&mut self, err: impl Into<VerifyError>) {
+        self.0.push(err.into());
+    }
+}
+
 impl Deref for VerifierErrors {
     type Target = Vec<VerifierError>;
 
@@ -1093,6 +1360,684 @@ impl Display for VerifierErrors {
     }
 }
 
+/// A type error found by `Module::typecheck`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TypeError {
+    /// The unit which contains the instruction.
+    pub unit: String,
+    /// The instruction whose types do not line up.
+    pub inst: String,
+    /// The mismatch.
+    pub message: String,
+}
+
+impl From<VerifierError> for TypeError {
+    fn from(err: VerifierError) -> TypeError {
+        TypeError {
+            unit: err.unit.unwrap_or_default(),
+            inst: err.object.unwrap_or_default(),
+            message: err.message,
+        }
+    }
+}
+
+impl Display for TypeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}: {}: {}", self.unit, self.inst, self.message)
+    }
+}
+
+/// An error found by `Module::try_verify`, told apart by the check that failed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VerifyError {
+    /// A unit has no entry block.
+    NoEntry {
+        /// The unit without entry block.
+        unit: String,
+    },
+    /// A block is empty, does not end in a terminator, or has a terminator in
+    /// its middle.
+    Terminator {
+        /// The unit which contains the block.
+        unit: String,
+        /// The offending block, or the terminator in the middle of a block.
+        object: String,
+        /// What is wrong with the block.
+        message: String,
+    },
+    /// The operands or result of an instruction are invalid, or their types do
+    /// not line up.
+    Type(TypeError),
+    /// An instruction appears in a unit which does not allow it, such as a
+    /// `wait` in a function.
+    NotAllowed {
+        /// The unit which contains the instruction.
+        unit: String,
+        /// The offending instruction.
+        inst: String,
+        /// The opcode of the instruction.
+        opcode: Opcode,
+    },
+    /// A value is used before it is defined, i.e. its definition does not
+    /// dominate the use.
+    UseBeforeDef {
+        /// The unit which contains the definition and the use.
+        unit: String,
+        /// The instruction defining the value.
+        def: String,
+        /// The instruction using the value.
+        inst: String,
+    },
+    /// An instruction refers to a value or block which is not defined in the
+    /// unit.
+    Undefined {
+        /// The unit which contains the instruction.
+        unit: String,
+        /// The offending instruction.
+        inst: String,
+        /// The value or block without definition.
+        object: String,
+    },
+}
+
+impl From<VerifierError> for VerifyError {
+    fn from(err: VerifierError) -> VerifyError {
+        VerifyError::Type(err.into())
+    }
+}
+
+impl From<VerifyError> for VerifierError {
+    fn from(err: VerifyError) -> VerifierError {
+        let (unit, object, message) = match err {
+            VerifyError::NoEntry { unit } => (unit, None, format!("layout has no entry block")),
+            VerifyError::Terminator {
+                unit,
+                object,
+                message,
+            } => (unit, Some(object), message),
+            VerifyError::Type(err) => (err.unit, Some(err.inst), err.message),
+            VerifyError::NotAllowed { unit, inst, opcode } => (
+                unit,
+                Some(inst),
+                format!("{} may not appear in this unit", opcode),
+            ),
+            VerifyError::UseBeforeDef { unit, def, inst } => (
+                unit,
+                Some(def),
+                format!("does not dominate use in `{}`", inst),
+            ),
+            VerifyError::Undefined { unit, inst, object } => {
+                (unit, Some(inst), format!("{} has no definition", object))
+            }
+        };
+        VerifierError {
+            unit: Some(unit).filter(|unit| !unit.is_empty()),
+            object,
+            message,
+        }
+    }
+}
+
+impl Display for VerifyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", VerifierError::from(self.clone()))
+    }
+}
+
+impl std::error::Error for VerifyError {}
+
+/// The severity of a finding in a `VerifyReport`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Severity {
+    /// The IR is malformed.
+    Error,
+    /// The IR is well-formed, but likely not what was intended.
+    Warning,
+}
+
+impl Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Severity::Error => write!(f, "error"),
+            Severity::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+/// A single finding reported by a `Check`.
+#[derive(Debug, Clone)]
+pub struct Finding {
+    /// The name of the check which reported the finding.
+    pub check: &'static str,
+    /// The severity of the finding.
+    pub severity: Severity,
+    /// The object which caused the finding.
+    pub object: Option<String>,
+    /// The message describing the finding.
+    pub message: String,
+}
+
+impl Display for Finding {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{} [{}]: ", self.severity, self.check)?;
+        if let Some(ref object) = self.object {
+            write!(f, "{}: ", object)?;
+        }
+        write!(f, "{}", self.message)
+    }
+}
+
+/// The findings reported for a single unit.
+#[derive(Debug, Clone)]
+pub struct UnitReport {
+    /// The unit the findings relate to, e.g. `func @foo`.
+    pub unit: String,
+    /// The findings, in the order they were reported.
+    pub findings: Vec<Finding>,
+    check: &'static str,
+}
+
+impl UnitReport {
+    fn new(unit: String) -> Self {
+        Self {
+            unit,
+            findings: vec![],
+            check: "",
+        }
+    }
+
+    /// Report a finding of the given severity.
+    pub fn report(
+        &mut self,
+        severity: Severity,
+        object: Option<String>,
+        message: impl Into<String>,
+    ) {
+        self.findings.push(Finding {
+            check: self.check,
+            severity,
+            object,
+            message: message.into(),
+        });
+    }
+
+    /// Report an error.
+    pub fn error(&mut self, object: Option<String>, message: impl Into<String>) {
+        self.report(Severity::Error, object, message)
+    }
+
+    /// Report a warning.
+    pub fn warning(&mut self, object: Option<String>, message: impl Into<String>) {
+        self.report(Severity::Warning, object, message)
+    }
+
+    /// Return an iterator over the findings of a given severity.
+    pub fn with_severity(&self, severity: Severity) -> impl Iterator<Item = &Finding> {
+        self.findings.iter().filter(move |f| f.severity == severity)
+    }
+
+    /// Return an iterator over the errors reported for the unit.
+    pub fn errors(&self) -> impl Iterator<Item = &Finding> {
+        self.with_severity(Severity::Error)
+    }
+
+    /// Return an iterator over the warnings reported for the unit.
+    pub fn warnings(&self) -> impl Iterator<Item = &Finding> {
+        self.with_severity(Severity::Warning)
+    }
+}
+
+impl Display for UnitReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        writeln!(f, "{}:", self.unit)?;
+        for severity in &[Severity::Error, Severity::Warning] {
+            for finding in self.with_severity(*severity) {
+                writeln!(f, "  - {}", finding)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A report of all findings of a set of checks on a module.
+///
+/// Findings are grouped by unit, and within each unit by severity. Units
+/// without any findings are omitted.
+#[derive(Debug, Clone, Default)]
+pub struct VerifyReport {
+    units: Vec<UnitReport>,
+}
+
+impl VerifyReport {
+    /// Run a set of checks on a module and collect the findings.
+    pub fn run(module: &Module, checks: &[&dyn Check]) -> Self {
+        let mut units = vec![];
+        for unit in module.units() {
+            let mut report = UnitReport::new(format!("{} {}", unit.kind(), unit.name()));
+            for check in checks {
+                report.check = check.name();
+                check.check_unit(module, unit, &mut report);
+            }
+            if !report.findings.is_empty() {
+                units.push(report);
+            }
+        }
+        Self { units }
+    }
+
+    /// Return the reports of the units that have findings.
+    pub fn units(&self) -> &[UnitReport] {
+        &self.units
+    }
+
+    /// Return the report for a unit, e.g. `func @foo`.
+    pub fn unit(&self, name: &str) -> Option<&UnitReport> {
+        self.units.iter().find(|r| r.unit == name)
+    }
+
+    /// Return the number of errors in the report.
+    pub fn num_errors(&self) -> usize {
+        self.units.iter().map(|r| r.errors().count()).sum()
+    }
+
+    /// Return the number of warnings in the report.
+    pub fn num_warnings(&self) -> usize {
+        self.units.iter().map(|r| r.warnings().count()).sum()
+    }
+
+    /// Check whether the report contains no errors.
+    pub fn is_ok(&self) -> bool {
+        self.num_errors() == 0
+    }
+}
+
+impl Display for VerifyReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        for unit in &self.units {
+            write!(f, "{}", unit)?;
+        }
+        Ok(())
+    }
+}
+
+/// A check that contributes findings to a `VerifyReport`.
+///
+/// Implement this trait to add custom checks to `Module::verify_with`.
+pub trait Check {
+    /// The name of the check, as shown in the report.
+    fn name(&self) -> &'static str;
+
+    /// Check a unit and report any findings.
+    fn check_unit(&self, module: &Module, unit: Unit, report: &mut UnitReport);
+}
+
+/// Return the checks run by `Module::verify_all`.
+pub fn default_checks() -> Vec<&'static dyn Check> {
+    vec![&IntegrityCheck, &CallCheck, &DriverCheck, &EntryCheck]
+}
+
+/// Check the structural integrity of a unit.
+///
+/// This covers block terminators, dominance of definitions over their uses,
+/// type consistency, and aggregate indices, as checked by the `Verifier`.
+pub struct IntegrityCheck;
+
+impl Check for IntegrityCheck {
+    fn name(&self) -> &'static str {
+        "integrity"
+    }
+
+    fn check_unit(&self, _module: &Module, unit: Unit, report: &mut UnitReport) {
+        let mut verifier = Verifier::new();
+        verifier.verify_unit(unit);
+        if let Err(errs) = verifier.finish() {
+            for err in errs.0 {
+                report.error(err.object, err.message);
+            }
+        }
+    }
+}
+
+/// Check that calls and instances match the signature of their target.
+pub struct CallCheck;
+
+impl Check for CallCheck {
+    fn name(&self) -> &'static str {
+        "calls"
+    }
+
+    fn check_unit(&self, module: &Module, unit: Unit, report: &mut UnitReport) {
+        let symbols: HashMap<_, _> = module.symbols().map(|(name, _, sig)| (name, sig)).collect();
+        for inst in unit.all_insts() {
+            let ext = match unit[inst].get_ext_unit() {
+                Some(ext) => ext,
+                None => continue,
+            };
+            let name = unit.extern_name(ext);
+            let sig = unit.extern_sig(ext);
+            let object = Some(inst.dump(&unit).to_string());
+            let target = match symbols.get(name) {
+                Some(target) => target,
+                None => {
+                    report.error(object, format!("unit {} not found", name));
+                    continue;
+                }
+            };
+            if let Some(message) = arity_mismatch(name, target, sig) {
+                report.error(object, message);
+            } else if *target != sig {
+                report.error(
+                    object,
+                    format!("{} has signature {}, but is used as {}", name, target, sig),
+                );
+            }
+        }
+    }
+}
+
+/// Check that calls and instances pass as many arguments as their target
+/// expects.
+///
+/// Returns one message per mismatch. Targets which are neither defined nor
+/// declared in the module are skipped.
+pub(crate) fn verify_arity(module: &Module) -> Vec<String> {
+    let symbols: HashMap<_, _> = module.symbols().map(|(name, _, sig)| (name, sig)).collect();
+    let mut errors = vec![];
+    for unit in module.units() {
+        verify_unit_arity(unit, &symbols, &mut errors);
+    }
+    errors
+}
+
+/// Check that the calls and instances in a unit pass as many arguments as
+/// their target in `symbols` expects.
+pub(crate) fn verify_unit_arity<K, V>(unit: Unit, symbols: &HashMap<K, V>, errors: &mut Vec<String>)
+where
+    K: Borrow<UnitName> + Hash + Eq,
+    V: Borrow<Signature>,
+{
+    for inst in unit.all_insts() {
+        let ext = match unit[inst].get_ext_unit() {
+            Some(ext) => ext,
+            None => continue,
+        };
+        let name = unit.extern_name(ext);
+        let target = match symbols.get(name) {
+            Some(target) => target.borrow(),
+            None => continue,
+        };
+        if let Some(message) = arity_mismatch(name, target, unit.extern_sig(ext)) {
+            errors.push(format!(
+                "{} {}: {}: {}",
+                unit.kind(),
+                unit.name(),
+                inst.dump(&unit),
+                message
+            ));
+        }
+    }
+}
+
+/// Describe a mismatch between the number of arguments of a call or instance
+/// and the signature of its target.
+fn arity_mismatch(name: &UnitName, target: &Signature, sig: &Signature) -> Option<String> {
+    let (want_ins, want_outs) = (target.inputs().count(), target.outputs().count());
+    let (have_ins, have_outs) = (sig.inputs().count(), sig.outputs().count());
+    if want_ins == have_ins && want_outs == have_outs {
+        return None;
+    }
+    Some(format!(
+        "{} expects {} inputs and {} outputs, but {} and {} were given",
+        name, want_ins, want_outs, have_ins, have_outs
+    ))
+}
+
+/// Check that signals in entities have at most one driver.
+pub struct DriverCheck;
+
+impl Check for DriverCheck {
+    fn name(&self) -> &'static str {
+        "drivers"
+    }
+
+    fn check_unit(&self, _module: &Module, unit: Unit, report: &mut UnitReport) {
+        if !unit.is_entity() {
+            return;
+        }
+        let mut drivers = HashMap::<Value, Vec<Inst>>::new();
+        for inst in unit.all_insts() {
+            match unit[inst].opcode() {
+                Opcode::Drv | Opcode::DrvCond | Opcode::Reg => {
+                    drivers.entry(unit[inst].args()[0]).or_default().push(inst);
+                }
+                _ => (),
+            }
+        }
+        let mut signals: Vec<_> = drivers.into_iter().filter(|(_, d)| d.len() > 1).collect();
+        signals.sort_by_key(|(_, d)| d[0]);
+        for (signal, insts) in signals {
+            report.warning(
+                Some(signal.dump(&unit).to_string()),
+                format!("signal has {} drivers", insts.len()),
+            );
+        }
+    }
+}
+
+/// Check that the entry block of a function or process has no predecessors.
+///
+/// Control flow looping back into the entry block usually indicates malformed
+/// IR. Processes resuming at their entry block after a `wait` are fine, since
+/// such edges cross into a new instant of time.
+pub struct EntryCheck;
+
+impl Check for EntryCheck {
+    fn name(&self) -> &'static str {
+        "entry"
+    }
+
+    fn check_unit(&self, _module: &Module, unit: Unit, report: &mut UnitReport) {
+        if unit.is_entity() {
+            return;
+        }
+        let entry = match unit.first_block() {
+            Some(bb) => bb,
+            None => return,
+        };
+        let pt = unit.temporal_predtbl();
+        let mut preds: Vec<_> = pt.pred(entry).collect();
+        if preds.is_empty() {
+            return;
+        }
+        preds.sort();
+        let preds: Vec<_> = preds.iter().map(|bb| bb.dump(&unit).to_string()).collect();
+        report.warning(
+            Some(entry.dump(&unit).to_string()),
+            format!("entry block has predecessors {}", preds.join(", ")),
+        );
+    }
+}
+
 fn identity(ty: Type) -> Type {
     ty
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::assembly::parse_module_unchecked;
+    use crate::ir::{Signature, UnitData, UnitKind, UnitName};
+    use crate::{int_ty, signal_ty};
+
+    #[test]
+    fn check_termination() {
+        let module = parse_module_unchecked(
+            "
+            proc @spin (i1$ %a) -> () {
+            entry:
+                %ap = prb i1$ %a
+                br %ap, %loop, %done
+            loop:
+                br %body
+            body:
+                br %loop
+            done:
+                halt
+            }
+
+            proc @waits (i1$ %a) -> () {
+            entry:
+                %ap = prb i1$ %a
+                br %ap, %entry, %idle
+            idle:
+                wait %entry, %a
+            }
+            ",
+        )
+        .unwrap();
+        let mut units = module.units();
+        let spin = units.next().unwrap();
+        let stuck = spin.check_termination().unwrap_err();
+        let names: Vec<_> = stuck.iter().map(|&bb| bb.dump(&spin).to_string()).collect();
+        assert_eq!(names, vec!["%loop", "%body"]);
+        assert_eq!(units.next().unwrap().check_termination(), Ok(()));
+    }
+
+    #[test]
+    fn verify_all_reports_each_problem() {
+        let module = parse_module_unchecked(
+            "
+            declare @ext (i32) void
+
+            func @noterm (i32 %a) void {
+            entry:
+                %0 = add i32 %a, %a
+            }
+
+            func @badcall (i8 %a) void {
+            entry:
+                call void @ext (i8 %a)
+                ret
+            }
+
+            entity @multi (i1$ %x) -> (i1$ %y) {
+                %xp = prb i1$ %x
+                %t = const time 0s 1e
+                drv i1$ %y, %xp, %t
+                drv i1$ %y, %xp, %t
+            }
+
+            func @order (i1 %c) void {
+            entry:
+                br %c, %then, %else
+            then:
+                %0 = const i32 0
+                br %else
+            else:
+                call void @ext (i32 %0)
+                ret
+            }
+            ",
+        )
+        .unwrap();
+        let report = module.verify_all();
+        assert_eq!(report.units().len(), 4);
+        assert_eq!(report.num_errors(), 3);
+        assert_eq!(report.num_warnings(), 1);
+
+        let unit = report.unit("func @noterm").unwrap();
+        let errors: Vec<_> = unit.errors().collect();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].check, "integrity");
+
+        let unit = report.unit("func @badcall").unwrap();
+        let errors: Vec<_> = unit.errors().collect();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].check, "calls");
+
+        let unit = report.unit("entity @multi").unwrap();
+        assert_eq!(unit.errors().count(), 0);
+        let warnings: Vec<_> = unit.warnings().collect();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(warnings[0].check, "drivers");
+        assert_eq!(warnings[0].object.as_deref(), Some("%y"));
+
+        let unit = report.unit("func @order").unwrap();
+        let errors: Vec<_> = unit.errors().collect();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].check, "integrity");
+    }
+
+    #[test]
+    fn entity_def_dominates_use() {
+        // The parser rejects such units, so build one by hand.
+        let mut sig = Signature::new();
+        sig.add_input(signal_ty(int_ty(1)));
+        let mut data = UnitData::new(UnitKind::Entity, UnitName::global("unit3"), sig);
+        let mut builder = UnitBuilder::new_anonymous(&mut data);
+        builder.insert_at_beginning();
+        let y = builder.ins().const_int((32, 42));
+        builder.set_name(y, "y".to_owned());
+        let z = builder.ins().not(y);
+        builder.set_name(z, "z".to_owned());
+        let def = builder.value_inst(y);
+        builder.remove_inst(def);
+        builder.insert_inst_after(def, builder.value_inst(z));
+
+        let mut verifier = Verifier::new();
+        verifier.verify_unit(Unit::new_anonymous(&data));
+        assert_eq!(
+            verifier.finish_typed(),
+            Err(vec![VerifyError::UseBeforeDef {
+                unit: "entity @unit3".to_owned(),
+                def: "%y = const i32 42".to_owned(),
+                inst: "%z = not i32 %y".to_owned(),
+            }])
+        );
+    }
+
+    #[test]
+    fn validate_function_insts() {
+        let body = "{
+            entry:
+                %t = const time 1ns
+                wait %entry for %t
+            }";
+        let err = parse_module_unchecked(format!("func @foo () void {}", body)).err();
+        assert!(err
+            .unwrap()
+            .to_string()
+            .contains("wait may not appear in this unit"));
+        let module = parse_module_unchecked(format!("proc @foo () -> () {}", body)).unwrap();
+        assert!(module.units().next().unwrap().validate().is_ok());
+    }
+
+    #[test]
+    fn entry_with_predecessors_warns() {
+        let module = parse_module_unchecked(
+            "
+            func @loop (i1 %c) void {
+            %entry:
+                br %c, %entry, %exit
+            %exit:
+                ret
+            }
+
+            proc @wait (i1$ %x) -> () {
+            %entry:
+                wait %entry, %x
+            }
+            ",
+        )
+        .unwrap();
+        let unit = module.units().next().unwrap();
+        assert_eq!(unit.get_block_name(unit.entry()), Some("entry"));
+
+        let report = module.verify_with(&[&super::EntryCheck]);
+        assert_eq!(report.num_warnings(), 1);
+        let warnings: Vec<_> = report.unit("func @loop").unwrap().warnings().collect();
+        assert_eq!(warnings[0].object.as_deref(), Some("%entry"));
+        assert_eq!(warnings[0].message, "entry block has predecessors %entry");
+        assert!(report.unit("proc @wait").is_none());
+    }
+}